@@ -1,6 +1,12 @@
 use clap::{Parser, Subcommand};
-use keyboard_map_shift::{HotkeySpec, run_transform_once, update_hotkey};
-use std::io::{self, Write};
+use keyboard_map_shift::keyboard_mapping::builtin::no_layouts_error;
+use keyboard_map_shift::keyboard_mapping::custom::config_layout_warnings;
+use keyboard_map_shift::keyboard_mapping::import::load_layout_file;
+use keyboard_map_shift::{
+    HotkeySpec, LayoutChoice, LayoutSelector, RunAction, ShiftMode, TransliterationScheme,
+    all_layout_vk_maps, convert_text, run_action, update_hotkey, update_previous_hotkey,
+};
+use std::io::{self, Read, Write};
 mod wizard;
 
 #[derive(Parser, Debug)]
@@ -13,25 +19,142 @@ pub struct Cli {
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    Run,
+    Run {
+        /// How to pick the target layout: `best` or `cycle` (defaults to the config value)
+        #[arg(long)]
+        mode: Option<ShiftMode>,
+        /// What to do with the text: `shift`, `fix-caps`, `shift-fix-caps`, `previous` or `transliterate`;
+        /// `previous` rejects `--to` and `--mode`
+        #[arg(long, default_value = "shift")]
+        action: RunAction,
+        /// Layout the text was typed in (id, name, language code or index) instead of detecting it
+        #[arg(long)]
+        from: Option<LayoutSelector>,
+        /// Layout to convert into (id, name, language code or index) instead of using the mode
+        #[arg(long)]
+        to: Option<LayoutSelector>,
+        /// Romanization table for `--action transliterate`: `default`, `bgn` or `iso9`;
+        /// vowels of unpointed Hebrew words other than common words and names are guessed
+        #[arg(long, default_value = "default")]
+        scheme: TransliterationScheme,
+    },
+    /// Convert the text given as an argument, or read from stdin, and print
+    /// the result; works without a graphical session
+    Convert {
+        /// Text to convert; read from stdin when left out
+        text: Option<String>,
+        /// How to pick the target layout: `best` or `cycle` (defaults to the config value)
+        #[arg(long)]
+        mode: Option<ShiftMode>,
+        /// What to do with the text: `shift`, `fix-caps`, `shift-fix-caps`, `previous` or `transliterate`;
+        /// `previous` rejects `--to` and `--mode`
+        #[arg(long, default_value = "shift")]
+        action: RunAction,
+        /// Layout the text was typed in (id, name, language code or index) instead of detecting it
+        #[arg(long)]
+        from: Option<LayoutSelector>,
+        /// Layout to convert into (id, name, language code or index) instead of using the mode
+        #[arg(long)]
+        to: Option<LayoutSelector>,
+        /// Romanization table for `--action transliterate`: `default`, `bgn` or `iso9`;
+        /// vowels of unpointed Hebrew words other than common words and names are guessed
+        #[arg(long, default_value = "default")]
+        scheme: TransliterationScheme,
+    },
+    /// List the configured layouts with the ids `--from` and `--to` accept
+    Layouts,
     Setup,
     Settings {
         #[arg(long)]
         hotkey: Option<String>,
+        /// Hotkey that converts the selection to the previous layout
+        #[arg(long)]
+        previous_hotkey: Option<String>,
     },
 }
 
 pub fn execute(cli: Cli) -> Result<(), String> {
-    match cli.command.unwrap_or(Commands::Run) {
-        Commands::Run => run_transform_once(),
+    let default_run = Commands::Run {
+        mode: None,
+        action: RunAction::default(),
+        from: None,
+        to: None,
+        scheme: TransliterationScheme::default(),
+    };
+    match cli.command.unwrap_or(default_run) {
+        Commands::Run {
+            mode,
+            action,
+            from,
+            to,
+            scheme,
+        } => run_action(action, mode, &LayoutChoice { from, to }, scheme),
+        Commands::Convert {
+            text,
+            mode,
+            action,
+            from,
+            to,
+            scheme,
+        } => {
+            let text = match text {
+                Some(text) => text,
+                None => read_input(io::stdin())?,
+            };
+            let converted = convert_text(&text, action, mode, &LayoutChoice { from, to }, scheme)?;
+            print!("{}", converted);
+            if !converted.ends_with('\n') {
+                println!();
+            }
+            Ok(())
+        }
+        Commands::Layouts => {
+            let layout_maps = all_layout_vk_maps();
+            if layout_maps.is_empty() {
+                eprintln!("Warning: {}", no_layouts_error());
+            }
+            for (index, layout_map) in layout_maps.iter().enumerate() {
+                let layout = &layout_map.layout;
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    index,
+                    layout.id,
+                    layout.lang_name,
+                    layout.language.as_deref().unwrap_or("-")
+                );
+            }
+            // Layout files, overrides and custom layouts that fail to apply
+            // are left out above; say why.
+            let cfg = keyboard_map_shift::config::load_config()?;
+            for file in &cfg.layout_files {
+                if let Err(e) = load_layout_file(file.path()) {
+                    eprintln!("Warning: {}", e);
+                }
+            }
+            for warning in config_layout_warnings(&cfg, &layout_maps) {
+                eprintln!("Warning: {}", warning);
+            }
+            Ok(())
+        }
         Commands::Setup => wizard::run_wizard(),
-        Commands::Settings { hotkey } => {
-            if let Some(hk) = hotkey {
-                let spec = HotkeySpec::from_display(&hk)?;
-                update_hotkey(&spec)
+        Commands::Settings {
+            hotkey,
+            previous_hotkey,
+        } => {
+            if hotkey.is_some() || previous_hotkey.is_some() {
+                if let Some(hk) = hotkey {
+                    update_hotkey(&HotkeySpec::from_display(&hk)?)?;
+                }
+                if let Some(hk) = previous_hotkey {
+                    update_previous_hotkey(&HotkeySpec::from_display(&hk)?)?;
+                }
+                Ok(())
             } else {
                 let cfg = keyboard_map_shift::config::load_config()?;
                 println!("Current hotkey: {}", cfg.hotkey);
+                if let Some(previous) = &cfg.previous_hotkey {
+                    println!("Previous layout hotkey: {}", previous);
+                }
                 print!("Change it now via interactive wizard? [Y/n]: ");
                 io::stdout().flush().map_err(|e| e.to_string())?;
                 let mut input = String::new();
@@ -52,5 +175,13 @@ pub fn execute(cli: Cli) -> Result<(), String> {
     }
 }
 
+fn read_input(mut input: impl Read) -> Result<String, String> {
+    let mut text = String::new();
+    input
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read the text: {}", e))?;
+    Ok(text)
+}
+
 #[cfg(test)]
 mod tests;
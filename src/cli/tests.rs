@@ -10,11 +10,117 @@ fn parse_default_run() {
 fn parse_run_subcommand() {
     let cli = Cli::parse_from(["keyboard_map_shift", "run"]);
     match cli.command.unwrap() {
-        Commands::Run => {}
+        Commands::Run {
+            mode,
+            action,
+            from,
+            to,
+            scheme,
+        } => {
+            assert_eq!(scheme, TransliterationScheme::Default);
+            assert_eq!(mode, None);
+            assert_eq!(action, RunAction::Shift);
+            assert_eq!(from, None);
+            assert_eq!(to, None);
+        }
+        _ => panic!("expected Run"),
+    }
+}
+
+#[test]
+fn parse_run_with_mode() {
+    let cli = Cli::parse_from(["keyboard_map_shift", "run", "--mode", "cycle"]);
+    match cli.command.unwrap() {
+        Commands::Run { mode, .. } => assert_eq!(mode, Some(ShiftMode::Cycle)),
+        _ => panic!("expected Run"),
+    }
+}
+
+#[test]
+fn parse_run_rejects_unknown_mode() {
+    assert!(Cli::try_parse_from(["keyboard_map_shift", "run", "--mode", "random"]).is_err());
+}
+
+#[test]
+fn parse_run_with_action() {
+    let cli = Cli::parse_from(["keyboard_map_shift", "run", "--action", "shift-fix-caps"]);
+    match cli.command.unwrap() {
+        Commands::Run { action, .. } => assert_eq!(action, RunAction::ShiftFixCaps),
+        _ => panic!("expected Run"),
+    }
+
+    let cli = Cli::parse_from(["keyboard_map_shift", "run", "--action", "fix-caps"]);
+    match cli.command.unwrap() {
+        Commands::Run { action, .. } => assert_eq!(action, RunAction::FixCaps),
+        _ => panic!("expected Run"),
+    }
+}
+
+#[test]
+fn parse_run_with_from_and_to() {
+    let cli = Cli::parse_from(["keyboard_map_shift", "run", "--from", "he", "--to", "1"]);
+    match cli.command.unwrap() {
+        Commands::Run { from, to, .. } => {
+            assert_eq!(from, Some(LayoutSelector::Name("he".to_string())));
+            assert_eq!(to, Some(LayoutSelector::Index(1)));
+        }
         _ => panic!("expected Run"),
     }
 }
 
+#[test]
+fn parse_run_rejects_empty_layout() {
+    assert!(Cli::try_parse_from(["keyboard_map_shift", "run", "--to", " "]).is_err());
+}
+
+#[test]
+fn parse_run_previous_action() {
+    let cli = Cli::parse_from(["keyboard_map_shift", "run", "--action", "previous"]);
+    match cli.command.unwrap() {
+        Commands::Run { action, .. } => assert_eq!(action, RunAction::Previous),
+        _ => panic!("expected Run"),
+    }
+}
+
+#[test]
+fn parse_run_transliterate_with_scheme() {
+    let cli = Cli::parse_from([
+        "keyboard_map_shift",
+        "run",
+        "--action",
+        "transliterate",
+        "--scheme",
+        "iso9",
+    ]);
+    match cli.command.unwrap() {
+        Commands::Run { action, scheme, .. } => {
+            assert_eq!(action, RunAction::Transliterate);
+            assert_eq!(scheme, TransliterationScheme::Iso9);
+        }
+        _ => panic!("expected Run"),
+    }
+}
+
+#[test]
+fn parse_settings_with_previous_hotkey() {
+    let cli = Cli::parse_from([
+        "keyboard_map_shift",
+        "settings",
+        "--previous-hotkey",
+        "Ctrl+Alt+J",
+    ]);
+    match cli.command.unwrap() {
+        Commands::Settings {
+            hotkey,
+            previous_hotkey,
+        } => {
+            assert_eq!(hotkey, None);
+            assert_eq!(previous_hotkey, Some("Ctrl+Alt+J".to_string()));
+        }
+        _ => panic!("expected Settings"),
+    }
+}
+
 #[test]
 fn parse_setup_subcommand() {
     let cli = Cli::parse_from(["keyboard_map_shift", "setup"]);
@@ -28,9 +134,65 @@ fn parse_setup_subcommand() {
 fn parse_settings_with_hotkey() {
     let cli = Cli::parse_from(["keyboard_map_shift", "settings", "--hotkey", "Ctrl+Alt+K"]);
     match cli.command.unwrap() {
-        Commands::Settings { hotkey } => {
+        Commands::Settings {
+            hotkey,
+            previous_hotkey,
+        } => {
             assert_eq!(hotkey, Some("Ctrl+Alt+K".to_string()));
+            assert_eq!(previous_hotkey, None);
         }
         _ => panic!("expected Settings"),
     }
 }
+
+#[test]
+fn parse_run_with_layout_id() {
+    let cli = Cli::parse_from(["keyboard_map_shift", "run", "--to", "il(phonetic)"]);
+    match cli.command.unwrap() {
+        Commands::Run { to, .. } => {
+            assert_eq!(to, Some(LayoutSelector::Name("il(phonetic)".to_string())))
+        }
+        _ => panic!("expected Run"),
+    }
+}
+
+#[test]
+fn parse_layouts_subcommand() {
+    let cli = Cli::parse_from(["keyboard_map_shift", "layouts"]);
+    assert!(matches!(cli.command, Some(Commands::Layouts)));
+}
+
+#[test]
+fn parse_convert_with_text() {
+    let cli = Cli::parse_from(["keyboard_map_shift", "convert", "ghbdtn", "--to", "ru"]);
+    match cli.command.unwrap() {
+        Commands::Convert {
+            text, action, to, ..
+        } => {
+            assert_eq!(text, Some("ghbdtn".to_string()));
+            assert_eq!(action, RunAction::Shift);
+            assert_eq!(to, Some(LayoutSelector::Name("ru".to_string())));
+        }
+        _ => panic!("expected Convert"),
+    }
+}
+
+#[test]
+fn parse_convert_reads_stdin_without_text() {
+    let cli = Cli::parse_from(["keyboard_map_shift", "convert", "--action", "fix-caps"]);
+    match cli.command.unwrap() {
+        Commands::Convert { text, action, .. } => {
+            assert_eq!(text, None);
+            assert_eq!(action, RunAction::FixCaps);
+        }
+        _ => panic!("expected Convert"),
+    }
+}
+
+#[test]
+fn read_input_reads_everything() {
+    assert_eq!(
+        read_input("ghbdtn\nvbh\n".as_bytes()),
+        Ok("ghbdtn\nvbh\n".to_string())
+    );
+}
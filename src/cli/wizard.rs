@@ -1,7 +1,7 @@
 use std::io::{self, Write};
 
 use keyboard_map_shift::config::{DEFAULT_HOTKEY_DISPLAY, load_config};
-use keyboard_map_shift::{HotkeySpec, update_hotkey};
+use keyboard_map_shift::{HotkeySpec, update_hotkey, update_previous_hotkey};
 
 pub fn run_wizard() -> Result<(), String> {
     let cfg = load_config().unwrap_or_else(|_| keyboard_map_shift::config::Config::with_defaults());
@@ -26,5 +26,24 @@ pub fn run_wizard() -> Result<(), String> {
     let spec = HotkeySpec::from_display(display)?;
     update_hotkey(&spec)?;
     println!("Hotkey applied: {}", spec.display);
+
+    match &cfg.previous_hotkey {
+        Some(previous) => print!(
+            "Hotkey for converting to the previous layout (Enter keeps {}): ",
+            previous
+        ),
+        None => print!("Hotkey for converting to the previous layout (Enter to skip): "),
+    }
+    io::stdout().flush().map_err(|e| e.to_string())?;
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .map_err(|e| e.to_string())?;
+    let chosen = input.trim();
+    if !chosen.is_empty() {
+        let spec = HotkeySpec::from_display(chosen)?;
+        update_previous_hotkey(&spec)?;
+        println!("Previous layout hotkey applied: {}", spec.display);
+    }
     Ok(())
 }
@@ -9,6 +9,8 @@ pub fn load_config() -> Result<Config, String> {
         std::fs::read_to_string(&path).map_err(|e| format!("Failed to read config: {}", e))?;
     let cfg: Config =
         toml::from_str(&data).map_err(|e| format!("Failed to parse config: {}", e))?;
+    cfg.validate()
+        .map_err(|e| format!("Invalid config: {}", e))?;
     Ok(cfg)
 }
 
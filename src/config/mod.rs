@@ -3,5 +3,8 @@ mod model;
 mod paths;
 
 pub use io::{load_config, save_config};
-pub use model::{Config, DEFAULT_HOTKEY_DISPLAY};
-pub use paths::config_file_path;
+pub use model::{
+    Config, CustomLayout, DEFAULT_HOTKEY_DISPLAY, KeyLevels, LayoutFile, LayoutOverride,
+    LayoutSource, LayoutSpec, LevelTable, ShiftMode,
+};
+pub use paths::{CACHE_DIR_ENV, cache_dir, config_file_path, data_dir};
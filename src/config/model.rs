@@ -1,17 +1,357 @@
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::types::ShiftLevel;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 pub const DEFAULT_HOTKEY_DISPLAY: &str = "Ctrl+Alt+K";
 
+/// How the target layout is chosen once the source layout is known.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShiftMode {
+    /// Convert into every layout and keep the most plausible result.
+    #[default]
+    Best,
+    /// Convert into the layout following the source layout.
+    Cycle,
+}
+
+impl FromStr for ShiftMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "best" => Ok(ShiftMode::Best),
+            "cycle" => Ok(ShiftMode::Cycle),
+            other => Err(format!(
+                "Unknown shift mode: {} (expected best or cycle)",
+                other
+            )),
+        }
+    }
+}
+
+/// Where the Linux backend looks for the configured keyboard layouts.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutSource {
+    /// GNOME `org.gnome.desktop.input-sources sources`.
+    Gnome,
+    /// KDE Plasma `kxkbrc`.
+    Kde,
+    /// The X server's `_XKB_RULES_NAMES` (`setxkbmap -query`).
+    X11,
+    /// `/etc/default/keyboard`.
+    EtcDefaultKeyboard,
+    /// `localectl status`.
+    Localectl,
+    /// `XKBLAYOUT` properties in the udev database.
+    Udev,
+    /// Layouts guessed from the installed locales.
+    Locale,
+    /// The Linux console (kbd) keymap. Not in the default order: it is only
+    /// used on its own when running on a virtual terminal.
+    Console,
+}
+
+impl LayoutSource {
+    pub const DEFAULT_ORDER: [LayoutSource; 7] = [
+        LayoutSource::Gnome,
+        LayoutSource::Kde,
+        LayoutSource::X11,
+        LayoutSource::EtcDefaultKeyboard,
+        LayoutSource::Localectl,
+        LayoutSource::Udev,
+        LayoutSource::Locale,
+    ];
+}
+
+impl FromStr for LayoutSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "gnome" => Ok(LayoutSource::Gnome),
+            "kde" => Ok(LayoutSource::Kde),
+            "x11" => Ok(LayoutSource::X11),
+            "etc-default-keyboard" => Ok(LayoutSource::EtcDefaultKeyboard),
+            "localectl" => Ok(LayoutSource::Localectl),
+            "udev" => Ok(LayoutSource::Udev),
+            "locale" => Ok(LayoutSource::Locale),
+            "console" => Ok(LayoutSource::Console),
+            other => Err(format!(
+                "Unknown layout source: {} (expected gnome, kde, x11, etc-default-keyboard, localectl, udev, locale or console)",
+                other
+            )),
+        }
+    }
+}
+
+/// An XKB layout with an optional variant, written `us` or `us(dvorak)`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct LayoutSpec {
+    pub layout: String,
+    pub variant: Option<String>,
+}
+
+fn is_xkb_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+impl FromStr for LayoutSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spec = s.trim();
+        let (layout, variant) = match spec.split_once('(') {
+            Some((layout, rest)) => {
+                let variant = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| format!("Invalid layout: {} (missing \")\")", spec))?;
+                (layout, Some(variant))
+            }
+            None => (spec, None),
+        };
+        if !is_xkb_name(layout) || !variant.is_none_or(is_xkb_name) {
+            return Err(format!(
+                "Invalid layout: {} (expected a layout such as us or us(dvorak))",
+                spec
+            ));
+        }
+        Ok(LayoutSpec {
+            layout: layout.to_string(),
+            variant: variant.map(str::to_string),
+        })
+    }
+}
+
+impl TryFrom<String> for LayoutSpec {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<LayoutSpec> for String {
+    fn from(spec: LayoutSpec) -> Self {
+        spec.to_string()
+    }
+}
+
+impl fmt::Display for LayoutSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.variant {
+            Some(variant) => write!(f, "{}({})", self.layout, variant),
+            None => write!(f, "{}", self.layout),
+        }
+    }
+}
+
+/// A layout file, as a path or as a table that also gives the language of
+/// its layouts, e.g. `{ path = "hebphon.keylayout", language = "he" }`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum LayoutFile {
+    Path(PathBuf),
+    Table {
+        path: PathBuf,
+        /// BCP 47 language tag, replacing whatever the file says.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+    },
+}
+
+impl LayoutFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            LayoutFile::Path(path) | LayoutFile::Table { path, .. } => path,
+        }
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        match self {
+            LayoutFile::Path(_) => None,
+            LayoutFile::Table { language, .. } => language.as_deref(),
+        }
+    }
+}
+
+/// Levels named in a table, e.g. `{ base = "'", shift = "\"" }`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LevelTable {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shift: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altgr: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shift_altgr: Option<String>,
+}
+
+/// What a key types at each shift level: a list in level order (base,
+/// shift, altgr, shift_altgr) or a table naming the levels. An empty string
+/// means the key types nothing at that level; levels left out are kept.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum KeyLevels {
+    List(Vec<String>),
+    Table(LevelTable),
+}
+
+impl KeyLevels {
+    pub fn get(&self, level: ShiftLevel) -> Option<&str> {
+        match self {
+            KeyLevels::List(list) => {
+                let index = ShiftLevel::ALL.iter().position(|l| *l == level)?;
+                list.get(index).map(String::as_str)
+            }
+            KeyLevels::Table(table) => match level {
+                ShiftLevel::Base => table.base.as_deref(),
+                ShiftLevel::Shift => table.shift.as_deref(),
+                ShiftLevel::AltGr => table.altgr.as_deref(),
+                ShiftLevel::ShiftAltGr => table.shift_altgr.as_deref(),
+            },
+        }
+    }
+
+    fn validate(&self, key: PhysicalKey, layout: &str) -> Result<(), String> {
+        if let KeyLevels::List(list) = self
+            && list.len() > ShiftLevel::ALL.len()
+        {
+            return Err(format!(
+                "Too many levels for {} in {} (at most base, shift, altgr and shift_altgr)",
+                key, layout
+            ));
+        }
+        let has_control = ShiftLevel::ALL
+            .iter()
+            .filter_map(|level| self.get(*level))
+            .any(|s| s.chars().any(char::is_control));
+        if has_control {
+            return Err(format!("Control character for {} in {}", key, layout));
+        }
+        Ok(())
+    }
+}
+
+/// A layout defined in the config, keyed by physical key (`KeyQ`,
+/// `Quote`, ...). With a `base`, it starts as a copy of that layout.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CustomLayout {
+    pub id: String,
+    /// Display name; the id when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// BCP 47 language tag, e.g. `he`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Layout to copy, chosen like `--from`/`--to`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    #[serde(default)]
+    pub keys: BTreeMap<PhysicalKey, KeyLevels>,
+}
+
+/// Changes to the keys of an existing layout, chosen like `--from`/`--to`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LayoutOverride {
+    pub layout: String,
+    #[serde(default)]
+    pub keys: BTreeMap<PhysicalKey, KeyLevels>,
+}
+
+fn default_layout_sources() -> Vec<LayoutSource> {
+    LayoutSource::DEFAULT_ORDER.to_vec()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Config {
     pub hotkey: String,
+    #[serde(default)]
+    pub shift_mode: ShiftMode,
+    /// Hotkey converting the selection to the previous layout, if bound.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_hotkey: Option<String>,
+    /// Linux layout sources, tried in order until one reports layouts.
+    #[serde(default = "default_layout_sources")]
+    pub layout_sources: Vec<LayoutSource>,
+    /// Layouts to use instead of the ones read from `layout_sources`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layouts: Vec<LayoutSpec>,
+    /// XKB options such as `lv3:ralt_switch`, replacing the detected ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xkb_options: Option<Vec<String>>,
+    /// XKB model, replacing the detected one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xkb_model: Option<String>,
+    /// XKB rules file, replacing the detected one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xkb_rules: Option<String>,
+    /// Layout files (`.klc`, `.keylayout`, CLDR `.xml`, `.xkb`) loaded next to the
+    /// system layouts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layout_files: Vec<LayoutFile>,
+    /// Layouts defined here, added after the system layouts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_layouts: Vec<CustomLayout>,
+    /// Key changes applied to system layouts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layout_overrides: Vec<LayoutOverride>,
 }
 
 impl Config {
     pub fn with_defaults() -> Self {
         Self {
             hotkey: DEFAULT_HOTKEY_DISPLAY.to_string(),
+            shift_mode: ShiftMode::default(),
+            previous_hotkey: None,
+            layout_sources: default_layout_sources(),
+            layouts: Vec::new(),
+            xkb_options: None,
+            xkb_model: None,
+            xkb_rules: None,
+            layout_files: Vec::new(),
+            custom_layouts: Vec::new(),
+            layout_overrides: Vec::new(),
+        }
+    }
+
+    /// Checks what the TOML types can't: custom layouts need a unique id,
+    /// and keys at most four levels without control characters.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut ids = HashSet::new();
+        for layout in &self.custom_layouts {
+            let id = layout.id.trim();
+            if id.is_empty() {
+                return Err("Custom layout without an id".to_string());
+            }
+            if !ids.insert(id.to_lowercase()) {
+                return Err(format!("Duplicate custom layout id: {}", id));
+            }
+            for (key, levels) in &layout.keys {
+                levels.validate(*key, id)?;
+            }
+        }
+        for layout_override in &self.layout_overrides {
+            let layout = layout_override.layout.trim();
+            if layout.is_empty() {
+                return Err("Layout override without a layout".to_string());
+            }
+            for (key, levels) in &layout_override.keys {
+                levels.validate(*key, layout)?;
+            }
         }
+        Ok(())
     }
 }
 
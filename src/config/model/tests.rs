@@ -4,14 +4,201 @@ use super::*;
 fn defaults_match_constant() {
     let cfg = Config::with_defaults();
     assert_eq!(cfg.hotkey, DEFAULT_HOTKEY_DISPLAY);
+    assert_eq!(cfg.shift_mode, ShiftMode::Best);
 }
 
 #[test]
 fn toml_round_trip() {
     let cfg = Config {
         hotkey: "Ctrl+Alt+K".to_string(),
+        shift_mode: ShiftMode::Cycle,
+        previous_hotkey: Some("Ctrl+Alt+J".to_string()),
+        layout_sources: vec![LayoutSource::Kde, LayoutSource::Udev],
+        layouts: vec!["us(dvorak)".parse().unwrap(), "il".parse().unwrap()],
+        xkb_options: Some(vec!["lv3:ralt_switch".to_string()]),
+        xkb_model: Some("pc105".to_string()),
+        xkb_rules: None,
+        layout_files: vec![
+            LayoutFile::Path(PathBuf::from("/home/me/layouts/hebphon.klc")),
+            LayoutFile::Table {
+                path: PathBuf::from("/home/me/layouts/hebphon.keylayout"),
+                language: Some("he".to_string()),
+            },
+        ],
+        custom_layouts: vec![CustomLayout {
+            id: "hebphon".to_string(),
+            name: Some("Hebrew phonetic".to_string()),
+            language: Some("he".to_string()),
+            base: None,
+            keys: BTreeMap::from([
+                (
+                    PhysicalKey::KeyA,
+                    KeyLevels::List(vec!["א".to_string(), "".to_string()]),
+                ),
+                (
+                    PhysicalKey::Quote,
+                    KeyLevels::Table(LevelTable {
+                        shift: Some("\"".to_string()),
+                        ..LevelTable::default()
+                    }),
+                ),
+            ]),
+        }],
+        layout_overrides: vec![LayoutOverride {
+            layout: "il".to_string(),
+            keys: BTreeMap::from([(PhysicalKey::KeyW, KeyLevels::List(vec!["'".to_string()]))]),
+        }],
     };
     let s = toml::to_string_pretty(&cfg).unwrap();
     let back: Config = toml::from_str(&s).unwrap();
     assert_eq!(cfg, back);
 }
+
+#[test]
+fn missing_shift_mode_defaults_to_best() {
+    let cfg: Config = toml::from_str("hotkey = \"Ctrl+Alt+K\"").unwrap();
+    assert_eq!(cfg.shift_mode, ShiftMode::Best);
+    assert_eq!(cfg.previous_hotkey, None);
+}
+
+#[test]
+fn unset_previous_hotkey_is_not_written() {
+    let s = toml::to_string_pretty(&Config::with_defaults()).unwrap();
+    assert!(!s.contains("previous_hotkey"));
+}
+
+#[test]
+fn shift_mode_from_str() {
+    assert_eq!("cycle".parse::<ShiftMode>(), Ok(ShiftMode::Cycle));
+    assert_eq!(" Best ".parse::<ShiftMode>(), Ok(ShiftMode::Best));
+    assert!("random".parse::<ShiftMode>().is_err());
+}
+
+#[test]
+fn missing_layout_sources_use_default_order() {
+    let cfg: Config = toml::from_str("hotkey = \"Ctrl+Alt+K\"").unwrap();
+    assert_eq!(cfg.layout_sources, LayoutSource::DEFAULT_ORDER.to_vec());
+}
+
+#[test]
+fn layout_sources_parse_kebab_case() {
+    let cfg: Config = toml::from_str(
+        "hotkey = \"Ctrl+Alt+K\"\nlayout_sources = [\"etc-default-keyboard\", \"x11\"]",
+    )
+    .unwrap();
+    assert_eq!(
+        cfg.layout_sources,
+        vec![LayoutSource::EtcDefaultKeyboard, LayoutSource::X11]
+    );
+    assert_eq!("Udev".parse(), Ok(LayoutSource::Udev));
+    assert_eq!("console".parse(), Ok(LayoutSource::Console));
+    assert!("wayland".parse::<LayoutSource>().is_err());
+}
+
+#[test]
+fn layouts_parse_rmlvo_specs() {
+    let cfg: Config = toml::from_str(
+        "hotkey = \"Ctrl+Alt+K\"\nlayouts = [\"us(dvorak)\", \"ru(phonetic)\", \"il\"]\nxkb_options = [\"lv3:ralt_switch\"]",
+    )
+    .unwrap();
+    let specs: Vec<String> = cfg.layouts.iter().map(LayoutSpec::to_string).collect();
+    assert_eq!(specs, ["us(dvorak)", "ru(phonetic)", "il"]);
+    assert_eq!(cfg.layouts[0].layout, "us");
+    assert_eq!(cfg.layouts[0].variant.as_deref(), Some("dvorak"));
+    assert_eq!(cfg.layouts[2].variant, None);
+    assert_eq!(cfg.xkb_options, Some(vec!["lv3:ralt_switch".to_string()]));
+}
+
+#[test]
+fn invalid_layout_spec_is_rejected() {
+    assert!("us(dvorak".parse::<LayoutSpec>().is_err());
+    assert!("(dvorak)".parse::<LayoutSpec>().is_err());
+    assert!("us()".parse::<LayoutSpec>().is_err());
+    assert!("us,il".parse::<LayoutSpec>().is_err());
+    assert!(toml::from_str::<Config>("hotkey = \"K\"\nlayouts = [\"us(\"]").is_err());
+}
+
+#[test]
+fn unset_xkb_settings_are_not_written() {
+    let s = toml::to_string_pretty(&Config::with_defaults()).unwrap();
+    assert!(!s.contains("layouts ="));
+    assert!(!s.contains("xkb_"));
+    assert!(!s.contains("layout_files"));
+    assert!(!s.contains("custom_layouts"));
+    assert!(!s.contains("layout_overrides"));
+}
+
+fn parse_and_validate(toml_text: &str) -> Result<Config, String> {
+    let cfg: Config = toml::from_str(&format!("hotkey = \"Ctrl+Alt+K\"\n{}", toml_text))
+        .map_err(|e| e.to_string())?;
+    cfg.validate()?;
+    Ok(cfg)
+}
+
+#[test]
+fn layout_files_parse_paths_and_tables() {
+    let cfg = parse_and_validate(
+        "layout_files = [\"a.klc\", { path = \"b.keylayout\", language = \"he\" }]\n",
+    )
+    .unwrap();
+    assert_eq!(cfg.layout_files[0].path(), Path::new("a.klc"));
+    assert_eq!(cfg.layout_files[0].language(), None);
+    assert_eq!(cfg.layout_files[1].path(), Path::new("b.keylayout"));
+    assert_eq!(cfg.layout_files[1].language(), Some("he"));
+}
+
+#[test]
+fn custom_layouts_parse_lists_and_tables() {
+    let cfg = parse_and_validate(
+        "[[custom_layouts]]\nid = \"mine\"\nkeys.KeyQ = [\"q\", \"Q\"]\n\
+         keys.Quote = { altgr = \"’\" }\n",
+    )
+    .unwrap();
+    let keys = &cfg.custom_layouts[0].keys;
+    assert_eq!(keys[&PhysicalKey::KeyQ].get(ShiftLevel::Shift), Some("Q"));
+    assert_eq!(keys[&PhysicalKey::KeyQ].get(ShiftLevel::AltGr), None);
+    assert_eq!(keys[&PhysicalKey::Quote].get(ShiftLevel::AltGr), Some("’"));
+    assert_eq!(keys[&PhysicalKey::Quote].get(ShiftLevel::Base), None);
+}
+
+#[test]
+fn custom_layouts_reject_bad_keys_and_levels() {
+    let err = parse_and_validate("[[custom_layouts]]\nid = \"mine\"\nkeys.Hyper = [\"q\"]\n")
+        .unwrap_err();
+    assert!(err.contains("Unknown key code: Hyper"), "{}", err);
+    assert!(
+        parse_and_validate("[[layout_overrides]]\nlayout = \"il\"\nkeys.KeyQ = { bass = \"q\" }\n")
+            .is_err()
+    );
+    assert_eq!(
+        parse_and_validate(
+            "[[custom_layouts]]\nid = \"mine\"\nkeys.KeyQ = [\"q\", \"Q\", \"\", \"\", \"x\"]\n"
+        )
+        .unwrap_err(),
+        "Too many levels for KeyQ in mine (at most base, shift, altgr and shift_altgr)"
+    );
+    assert_eq!(
+        parse_and_validate("[[layout_overrides]]\nlayout = \"il\"\nkeys.Enter = [\"\\n\"]\n")
+            .unwrap_err(),
+        "Control character for Enter in il"
+    );
+}
+
+#[test]
+fn custom_layouts_need_unique_ids() {
+    assert_eq!(
+        parse_and_validate("[[custom_layouts]]\nid = \" \"\n").unwrap_err(),
+        "Custom layout without an id"
+    );
+    assert_eq!(
+        parse_and_validate(
+            "[[custom_layouts]]\nid = \"Mine\"\n[[custom_layouts]]\nid = \"mine\"\n"
+        )
+        .unwrap_err(),
+        "Duplicate custom layout id: mine"
+    );
+    assert_eq!(
+        parse_and_validate("[[layout_overrides]]\nlayout = \"\"\n").unwrap_err(),
+        "Layout override without a layout"
+    );
+}
@@ -1,9 +1,16 @@
 use directories::ProjectDirs;
+use std::ffi::OsString;
 use std::path::PathBuf;
 
+pub const CACHE_DIR_ENV: &str = "KEYBOARD_MAP_SHIFT_CACHE_DIR";
+
+fn project_dirs() -> Result<ProjectDirs, String> {
+    ProjectDirs::from("com", "keyboard-map-shift", "keyboard_map_shift")
+        .ok_or_else(|| "Could not determine configuration directory".to_string())
+}
+
 pub fn config_file_path() -> Result<PathBuf, String> {
-    let dirs = ProjectDirs::from("com", "keyboard-map-shift", "keyboard_map_shift")
-        .ok_or_else(|| "Could not determine configuration directory".to_string())?;
+    let dirs = project_dirs()?;
     let dir = dirs.config_dir();
     std::fs::create_dir_all(dir)
         .map_err(|e| format!("Failed to create config directory: {}", e))?;
@@ -12,5 +19,21 @@ pub fn config_file_path() -> Result<PathBuf, String> {
     Ok(path)
 }
 
+pub fn data_dir() -> Result<PathBuf, String> {
+    Ok(project_dirs()?.data_dir().to_path_buf())
+}
+
+/// The user cache directory, or `KEYBOARD_MAP_SHIFT_CACHE_DIR` when set.
+pub fn cache_dir() -> Result<PathBuf, String> {
+    cache_dir_from(std::env::var_os(CACHE_DIR_ENV))
+}
+
+fn cache_dir_from(override_dir: Option<OsString>) -> Result<PathBuf, String> {
+    match override_dir.filter(|dir| !dir.is_empty()) {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(project_dirs()?.cache_dir().to_path_buf()),
+    }
+}
+
 #[cfg(test)]
 mod tests;
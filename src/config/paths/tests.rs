@@ -5,3 +5,26 @@ fn config_file_path_smoke_and_filename() {
     let path = config_file_path().unwrap();
     assert_eq!(path.file_name().unwrap().to_string_lossy(), "config.toml");
 }
+
+#[test]
+fn data_dir_is_app_specific() {
+    let dir = data_dir().unwrap();
+    assert!(dir.to_string_lossy().contains("keyboard_map_shift"));
+}
+
+#[test]
+fn cache_dir_is_app_specific() {
+    let dir = cache_dir().unwrap();
+    assert!(dir.to_string_lossy().contains("keyboard_map_shift"));
+    assert_ne!(dir, data_dir().unwrap());
+}
+
+#[test]
+fn cache_dir_env_override() {
+    let dir = PathBuf::from("/tmp/kms_cache_override");
+    assert_eq!(cache_dir_from(Some(dir.clone().into())).unwrap(), dir);
+    assert_eq!(
+        cache_dir_from(Some(OsString::new())).unwrap(),
+        cache_dir_from(None).unwrap()
+    );
+}
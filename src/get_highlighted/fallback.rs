@@ -1,11 +1,12 @@
 #![cfg(not(any(target_os = "windows", target_os = "linux")))]
 
+/// No selection can be read on this OS.
 pub fn get_highlighted_text() -> Option<String> {
-    panic!("get_highlighted: get_highlighted_text is not implemented for this OS");
+    None
 }
 
 pub fn replace_highlighted_text(_new_text: &str) -> Result<(), String> {
-    panic!("get_highlighted: replace_highlighted_text is not implemented for this OS");
+    Err("Replacing the highlighted text is not supported on this OS".to_string())
 }
 
 #[cfg(test)]
@@ -1,15 +1,14 @@
 use super::*;
 
 #[test]
-#[should_panic(expected = "get_highlighted: get_highlighted_text is not implemented for this OS")]
-fn test_get_highlighted_text_panics() {
-    get_highlighted_text();
+fn test_get_highlighted_text_is_none() {
+    assert_eq!(get_highlighted_text(), None);
 }
 
 #[test]
-#[should_panic(
-    expected = "get_highlighted: replace_highlighted_text is not implemented for this OS"
-)]
-fn test_replace_highlighted_text_panics() {
-    replace_highlighted_text("test");
+fn test_replace_highlighted_text_errors() {
+    assert_eq!(
+        replace_highlighted_text("test"),
+        Err("Replacing the highlighted text is not supported on this OS".to_string())
+    );
 }
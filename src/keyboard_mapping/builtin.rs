@@ -0,0 +1,65 @@
+use super::custom::custom_layout_map;
+use super::types::LayoutMap;
+use crate::config::CustomLayout;
+use std::sync::OnceLock;
+
+const BUILTIN_LAYOUTS: &[(&str, &str)] = &[
+    ("us", include_str!("builtin/data/us.toml")),
+    ("he", include_str!("builtin/data/he.toml")),
+    ("ru", include_str!("builtin/data/ru.toml")),
+    ("uk", include_str!("builtin/data/uk.toml")),
+    ("ar", include_str!("builtin/data/ar.toml")),
+    ("el", include_str!("builtin/data/el.toml")),
+    ("de", include_str!("builtin/data/de.toml")),
+    ("fr", include_str!("builtin/data/fr.toml")),
+    ("fa", include_str!("builtin/data/fa.toml")),
+];
+
+fn parse_builtin_layout(name: &str, data: &str) -> Result<LayoutMap, String> {
+    let custom: CustomLayout =
+        toml::from_str(data).map_err(|e| format!("built-in layout {}: {}", name, e))?;
+    custom_layout_map(&custom, &[]).ok_or_else(|| format!("built-in layout {}: no layout", name))
+}
+
+/// The compiled-in layouts, stand-ins for when the system has no layout
+/// data. Ids are the XKB layout names (`us`, `il`, `ru`, ...). Every table
+/// parses, which the tests check; one that didn't would be left out.
+pub fn builtin_layout_maps() -> &'static [LayoutMap] {
+    static MAPS: OnceLock<Vec<LayoutMap>> = OnceLock::new();
+    MAPS.get_or_init(|| {
+        BUILTIN_LAYOUTS
+            .iter()
+            .filter_map(|(name, data)| parse_builtin_layout(name, data).ok())
+            .collect()
+    })
+}
+
+/// The built-in layouts with the given ids, in that order, or all of them
+/// when `ids` is empty. Ids that aren't built in are left out rather than
+/// replaced by layouts nobody configured, so this can be empty.
+pub fn builtin_layout_maps_for(ids: &[String]) -> Vec<LayoutMap> {
+    let maps = builtin_layout_maps();
+    if ids.is_empty() {
+        return maps.to_vec();
+    }
+    ids.iter()
+        .filter_map(|id| maps.iter().find(|m| m.layout.id == *id))
+        .cloned()
+        .collect()
+}
+
+/// Why no layout is available: the configured ones are neither installed
+/// nor built in.
+pub fn no_layouts_error() -> String {
+    let ids: Vec<&str> = builtin_layout_maps()
+        .iter()
+        .map(|m| m.layout.id.as_str())
+        .collect();
+    format!(
+        "No keyboard layout is available: the configured layouts are neither installed nor built in (built-in layouts: {})",
+        ids.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,91 @@
+use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::remap::shift_text_language;
+use crate::keyboard_mapping::types::{KeyStroke, KeyboardDirection, ShiftLevel};
+
+fn builtin(id: &str) -> &'static LayoutMap {
+    builtin_layout_maps()
+        .iter()
+        .find(|m| m.layout.id == id)
+        .unwrap_or_else(|| panic!("no built-in layout {}", id))
+}
+
+#[test]
+fn test_builtin_layouts_parse() {
+    for (name, data) in BUILTIN_LAYOUTS {
+        if let Err(e) = parse_builtin_layout(name, data) {
+            panic!("{}", e);
+        }
+    }
+    let ids: Vec<&str> = builtin_layout_maps()
+        .iter()
+        .map(|m| m.layout.id.as_str())
+        .collect();
+    assert_eq!(ids, ["us", "il", "ru", "ua", "ara", "gr", "de", "fr", "ir"]);
+    for layout_map in builtin_layout_maps() {
+        assert!(!layout_map.map.is_empty(), "{}", layout_map.layout.id);
+        assert!(layout_map.layout.language.is_some());
+        assert!(layout_map.layout.script.is_some());
+    }
+}
+
+#[test]
+fn test_builtin_layout_keys() {
+    let us = builtin("us");
+    assert_eq!(
+        us.map
+            .get(&KeyStroke::new(PhysicalKey::KeyQ, ShiftLevel::Shift)),
+        Some(&"Q".to_string())
+    );
+    let he = builtin("il");
+    assert_eq!(
+        he.map.get(&KeyStroke::base(PhysicalKey::KeyT)),
+        Some(&"א".to_string())
+    );
+    assert_eq!(he.layout.direction, KeyboardDirection::RTL);
+    assert_eq!(he.layout.language.as_deref(), Some("he"));
+    let de = builtin("de");
+    assert_eq!(
+        de.map
+            .get(&KeyStroke::new(PhysicalKey::KeyQ, ShiftLevel::AltGr)),
+        Some(&"@".to_string())
+    );
+}
+
+#[test]
+fn test_builtin_layouts_shift_text() {
+    assert_eq!(
+        shift_text_language("ghbdtn", builtin("us"), builtin("ru")),
+        "привет"
+    );
+    assert_eq!(
+        shift_text_language("akuo", builtin("us"), builtin("il")),
+        "שלום"
+    );
+}
+
+#[test]
+fn test_builtin_layout_maps_for_selects_in_order() {
+    let ids: Vec<String> = builtin_layout_maps_for(&["ru".to_string(), "us".to_string()])
+        .into_iter()
+        .map(|m| m.layout.id)
+        .collect();
+    assert_eq!(ids, ["ru", "us"]);
+}
+
+#[test]
+fn test_builtin_layout_maps_for_leaves_out_unknown_ids() {
+    assert_eq!(builtin_layout_maps_for(&["xx".to_string()]), []);
+    let ids: Vec<String> = builtin_layout_maps_for(&["xx".to_string(), "ru".to_string()])
+        .into_iter()
+        .map(|m| m.layout.id)
+        .collect();
+    assert_eq!(ids, ["ru"]);
+    assert_eq!(
+        builtin_layout_maps_for(&[]).len(),
+        builtin_layout_maps().len()
+    );
+    assert!(
+        no_layouts_error().ends_with("(built-in layouts: us, il, ru, ua, ara, gr, de, fr, ir)")
+    );
+}
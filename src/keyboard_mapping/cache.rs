@@ -0,0 +1,238 @@
+use super::physical_key::PhysicalKey;
+use super::types::{KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, ShiftLevel};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+const CACHE_FILE: &str = "layout_maps.toml";
+
+// Bump when the cached format or the way layout maps are built changes, so
+// stale caches from older versions are rebuilt.
+const CACHE_VERSION: u32 = 8;
+
+thread_local! {
+    // Off in tests that measure the work of building layout maps, per
+    // thread so other tests keep using the cache.
+    static CACHE_ENABLED: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Turns the layout cache on or off for the current thread.
+#[cfg(test)]
+pub fn set_cache_enabled(enabled: bool) {
+    CACHE_ENABLED.with(|cell| cell.set(enabled));
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    key: String,
+    #[serde(default)]
+    layouts: Vec<CachedLayout>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedLayout {
+    id: String,
+    lang_name: String,
+    direction: KeyboardDirection,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    variant: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    script: Option<String>,
+    #[serde(default)]
+    keys: Vec<CachedKey>,
+    #[serde(default)]
+    sequences: Vec<CachedSequence>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedKey {
+    key: PhysicalKey,
+    level: ShiftLevel,
+    output: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSequence {
+    strokes: Vec<KeyStroke>,
+    output: String,
+}
+
+impl CachedLayout {
+    fn from_layout_map(layout_map: &LayoutMap) -> Self {
+        let mut keys: Vec<CachedKey> = layout_map
+            .map
+            .iter()
+            .map(|(stroke, output)| CachedKey {
+                key: stroke.key,
+                level: stroke.level,
+                output: output.clone(),
+            })
+            .collect();
+        keys.sort_by_key(|k| (k.key, k.level));
+        let mut sequences: Vec<CachedSequence> = layout_map
+            .sequences
+            .iter()
+            .map(|(strokes, output)| CachedSequence {
+                strokes: strokes.clone(),
+                output: output.clone(),
+            })
+            .collect();
+        sequences.sort_by(|a, b| a.strokes.cmp(&b.strokes));
+        CachedLayout {
+            id: layout_map.layout.id.clone(),
+            lang_name: layout_map.layout.lang_name.clone(),
+            direction: layout_map.layout.direction,
+            variant: layout_map.layout.variant.clone(),
+            language: layout_map.layout.language.clone(),
+            script: layout_map.layout.script.clone(),
+            keys,
+            sequences,
+        }
+    }
+
+    fn into_layout_map(self) -> LayoutMap {
+        let layout = KeyboardLayout {
+            id: self.id,
+            lang_name: self.lang_name,
+            direction: self.direction,
+            variant: self.variant,
+            language: self.language,
+            script: self.script,
+        };
+        let map = self
+            .keys
+            .into_iter()
+            .map(|k| (KeyStroke::new(k.key, k.level), k.output))
+            .collect();
+        let mut layout_map = LayoutMap::new(layout, map);
+        layout_map.sequences = self
+            .sequences
+            .into_iter()
+            .map(|s| (s.strokes, s.output))
+            .collect();
+        layout_map
+    }
+}
+
+/// Describes what a set of layout maps was built from: the layout
+/// configuration plus the modification time of each data file, so editing
+/// either produces a different key. A directory is stamped with the number of
+/// directories under it and the newest of their mtimes: adding, removing or
+/// replacing a file, as package updates do, changes its directory's mtime.
+/// Files under a directory are not stat'ed so a cache hit stays cheap; pass
+/// the files that are read as well to notice them being edited in place.
+pub fn cache_key(configuration: &str, data_files: &[PathBuf]) -> String {
+    let mut key = configuration.to_string();
+    for path in data_files {
+        let stamp = if path.is_dir() {
+            let mut dirs = vec![path.clone()];
+            collect_dirs(path, &mut dirs);
+            let newest = dirs.iter().filter_map(|dir| mtime(dir)).max();
+            format!("{} dirs, newest {}", dirs.len(), format_mtime(newest))
+        } else {
+            format_mtime(mtime(path))
+        };
+        key.push_str(&format!("\n{} {}", path.display(), stamp));
+    }
+    key
+}
+
+fn mtime(path: &Path) -> Option<Duration> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+}
+
+fn format_mtime(mtime: Option<Duration>) -> String {
+    match mtime {
+        Some(d) => format!("{}.{:09}", d.as_secs(), d.subsec_nanos()),
+        None => "missing".to_string(),
+    }
+}
+
+/// Lists the directories under `dir`, recursively. Symlinked directories
+/// are not followed, so a link cycle can't loop.
+fn collect_dirs(dir: &Path, dirs: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            let path = entry.path();
+            dirs.push(path.clone());
+            collect_dirs(&path, dirs);
+        }
+    }
+}
+
+/// Reads cached layout maps, or `None` when the file is missing, unreadable
+/// or was written for a different key.
+pub fn load_layout_maps(path: &Path, key: &str) -> Option<Vec<LayoutMap>> {
+    let data = fs::read_to_string(path).ok()?;
+    let cache: CacheFile = toml::from_str(&data).ok()?;
+    if cache.version != CACHE_VERSION || cache.key != key {
+        return None;
+    }
+    Some(
+        cache
+            .layouts
+            .into_iter()
+            .map(CachedLayout::into_layout_map)
+            .collect(),
+    )
+}
+
+pub fn store_layout_maps(path: &Path, key: &str, layout_maps: &[LayoutMap]) -> Result<(), String> {
+    let cache = CacheFile {
+        version: CACHE_VERSION,
+        key: key.to_string(),
+        layouts: layout_maps
+            .iter()
+            .map(CachedLayout::from_layout_map)
+            .collect(),
+    };
+    let data =
+        toml::to_string(&cache).map_err(|e| format!("Failed to serialize layout cache: {}", e))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create cache directory: {}", e))?;
+    }
+    // Write then rename so a concurrent run never reads a partial file.
+    let tmp = path.with_extension("toml.tmp");
+    fs::write(&tmp, data).map_err(|e| format!("Failed to write layout cache: {}", e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to write layout cache: {}", e))
+}
+
+/// The path of cache file `name` in the user cache directory, or `None`
+/// when there is no cache directory or caching is off.
+pub fn cache_path(name: &str) -> Option<PathBuf> {
+    crate::config::cache_dir()
+        .ok()
+        .filter(|_| CACHE_ENABLED.with(Cell::get))
+        .map(|dir| dir.join(name))
+}
+
+/// Returns the layout maps cached for `key` in the user cache directory,
+/// building and caching them with `build` on a miss. An empty build result
+/// is not cached.
+pub fn cached_layout_maps(key: &str, build: impl FnOnce() -> Vec<LayoutMap>) -> Vec<LayoutMap> {
+    let path = cache_path(CACHE_FILE);
+    if let Some(maps) = path.as_deref().and_then(|p| load_layout_maps(p, key)) {
+        return maps;
+    }
+    let maps = build();
+    if let Some(path) = path
+        && !maps.is_empty()
+    {
+        let _ = store_layout_maps(&path, key, &maps);
+    }
+    maps
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,135 @@
+use super::*;
+use crate::test_utils::{TempDirGuard, temp_dir, use_temp_cache_dir};
+use std::collections::HashMap;
+
+fn sample_layout_map() -> LayoutMap {
+    let mut map = HashMap::new();
+    map.insert(KeyStroke::base(PhysicalKey::KeyA), "ש".to_string());
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Shift),
+        "A".to_string(),
+    );
+    map.insert(KeyStroke::base(PhysicalKey::Quote), "\"".to_string());
+    let mut layout_map = LayoutMap::new(
+        KeyboardLayout {
+            id: "il(phonetic)".to_string(),
+            lang_name: "Hebrew".to_string(),
+            direction: KeyboardDirection::RTL,
+            variant: Some("phonetic".to_string()),
+            language: Some("he".to_string()),
+            script: Some("Hebr".to_string()),
+        },
+        map,
+    );
+    layout_map.sequences.insert(
+        vec![
+            KeyStroke::new(PhysicalKey::Quote, ShiftLevel::AltGr),
+            KeyStroke::base(PhysicalKey::KeyE),
+        ],
+        "é".to_string(),
+    );
+    layout_map
+}
+
+#[test]
+fn test_store_and_load_round_trip() {
+    let dir = temp_dir("cache_round_trip");
+    let _guard = TempDirGuard::new(dir.clone());
+    let path = dir.join("nested").join(CACHE_FILE);
+    let maps = vec![sample_layout_map()];
+
+    store_layout_maps(&path, "key", &maps).unwrap();
+    assert_eq!(load_layout_maps(&path, "key"), Some(maps));
+}
+
+#[test]
+fn test_load_rejects_other_key() {
+    let dir = temp_dir("cache_other_key");
+    let _guard = TempDirGuard::new(dir.clone());
+    let path = dir.join(CACHE_FILE);
+
+    store_layout_maps(&path, "us,il", &[sample_layout_map()]).unwrap();
+    assert_eq!(load_layout_maps(&path, "us,ru"), None);
+}
+
+#[test]
+fn test_load_rejects_corrupt_file() {
+    let dir = temp_dir("cache_corrupt");
+    let _guard = TempDirGuard::new(dir.clone());
+    let path = dir.join(CACHE_FILE);
+    fs::write(&path, "not = [valid").unwrap();
+
+    assert_eq!(load_layout_maps(&path, "key"), None);
+    assert_eq!(load_layout_maps(&dir.join("missing.toml"), "key"), None);
+}
+
+#[test]
+fn test_cache_key_tracks_data_file_mtime() {
+    let dir = temp_dir("cache_mtime");
+    let _guard = TempDirGuard::new(dir.clone());
+    let data = dir.join("evdev.xml");
+    fs::write(&data, "<xkbConfigRegistry/>").unwrap();
+
+    let before = cache_key("us,il", std::slice::from_ref(&data));
+    assert_eq!(before, cache_key("us,il", std::slice::from_ref(&data)));
+    assert_ne!(before, cache_key("us,ru", std::slice::from_ref(&data)));
+
+    let file = fs::File::options().write(true).open(&data).unwrap();
+    file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))
+        .unwrap();
+    assert_ne!(before, cache_key("us,il", std::slice::from_ref(&data)));
+}
+
+#[test]
+fn test_cache_key_tracks_directories() {
+    let dir = temp_dir("cache_dir_mtime");
+    let _guard = TempDirGuard::new(dir.clone());
+    let symbols = dir.join("symbols");
+    fs::create_dir_all(symbols.join("nested")).unwrap();
+    let data = symbols.join("nested").join("il");
+    fs::write(&data, "xkb_symbols \"basic\" {};").unwrap();
+
+    let before = cache_key("us,il", std::slice::from_ref(&symbols));
+    assert!(before.contains(" 2 dirs, newest "), "{}", before);
+    assert_eq!(before, cache_key("us,il", std::slice::from_ref(&symbols)));
+
+    fs::create_dir(symbols.join("nested").join("more")).unwrap();
+    let after = cache_key("us,il", std::slice::from_ref(&symbols));
+    assert!(after.contains(" 3 dirs, newest "), "{}", after);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cache_key_tracks_nested_directory_mtime() {
+    let dir = temp_dir("cache_nested_mtime");
+    let _guard = TempDirGuard::new(dir.clone());
+    let nested = dir.join("symbols").join("nested");
+    fs::create_dir_all(&nested).unwrap();
+    let symbols = dir.join("symbols");
+
+    let before = cache_key("us", std::slice::from_ref(&symbols));
+    // A package update replacing a file under `nested` bumps its mtime.
+    fs::File::open(&nested)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))
+        .unwrap();
+    assert_ne!(before, cache_key("us", std::slice::from_ref(&symbols)));
+}
+
+#[test]
+fn test_cached_layout_maps_stay_out_of_the_user_cache() {
+    use_temp_cache_dir();
+    let dir = crate::config::cache_dir().unwrap();
+    assert!(dir.starts_with(std::env::temp_dir()), "{}", dir.display());
+
+    let key = format!("test {:?}", std::time::SystemTime::now());
+    let maps = vec![sample_layout_map()];
+    assert_eq!(cached_layout_maps(&key, || maps.clone()), maps);
+    assert!(dir.join(CACHE_FILE).exists());
+}
+
+#[test]
+fn test_cache_key_marks_missing_files() {
+    let key = cache_key("us", &[PathBuf::from("/nonexistent/kms/evdev.xml")]);
+    assert!(key.ends_with("/nonexistent/kms/evdev.xml missing"));
+}
@@ -0,0 +1,101 @@
+use super::physical_key::PhysicalKey;
+use super::types::{KeyStroke, LayoutMap, ShiftLevel};
+
+/// True when the text looks typed with Caps Lock on: most words have a
+/// lowercase first letter followed only by uppercase letters ("hELLO
+/// wORLD"), or every letter is uppercase over several words ("ЩЫЦ ЦЫЩ"). A
+/// single all-caps word is more likely an acronym ("NASA", "ЦРУ") or
+/// shouting than Caps Lock, so it is left alone. This only guesses for
+/// callers detecting Caps Lock on their own; the fix-caps actions invert the
+/// case whenever they are asked to.
+pub fn is_caps_inverted(text: &str) -> bool {
+    let mut inverted = 0usize;
+    let mut upper = 0usize;
+    let mut cased_words = 0usize;
+    for word in text.split(|c: char| !c.is_alphabetic()) {
+        let cased: Vec<char> = word
+            .chars()
+            .filter(|c| c.is_lowercase() || c.is_uppercase())
+            .collect();
+        if cased.len() < 2 {
+            continue;
+        }
+        cased_words += 1;
+        if cased[0].is_lowercase() && cased[1..].iter().all(|c| c.is_uppercase()) {
+            inverted += 1;
+        } else if cased.iter().all(|c| c.is_uppercase()) {
+            upper += 1;
+        }
+    }
+    let all_caps = !text.chars().any(char::is_lowercase);
+    if all_caps && upper >= 2 {
+        return true;
+    }
+    inverted > 0 && inverted * 2 > cased_words
+}
+
+/// Swaps the case of every cased character.
+pub fn invert_case(text: &str) -> String {
+    text.chars()
+        .flat_map(|ch| {
+            let swapped: Vec<char> = if ch.is_uppercase() {
+                ch.to_lowercase().collect()
+            } else if ch.is_lowercase() {
+                ch.to_uppercase().collect()
+            } else {
+                vec![ch]
+            };
+            swapped
+        })
+        .collect()
+}
+
+fn caps_partner(level: ShiftLevel) -> ShiftLevel {
+    match level {
+        ShiftLevel::Base => ShiftLevel::Shift,
+        ShiftLevel::Shift => ShiftLevel::Base,
+        ShiftLevel::AltGr => ShiftLevel::ShiftAltGr,
+        ShiftLevel::ShiftAltGr => ShiftLevel::AltGr,
+    }
+}
+
+/// Whether Caps Lock swaps `level` with its Shift partner on `key`, i.e. the
+/// two levels hold the lower and upper case of the same letter.
+fn is_caps_affected(layout: &LayoutMap, key: PhysicalKey, level: ShiftLevel) -> bool {
+    let lower_level = if level.has_shift() {
+        caps_partner(level)
+    } else {
+        level
+    };
+    let (Some(lower), Some(upper)) = (
+        layout.output(key, lower_level),
+        layout.output(key, caps_partner(lower_level)),
+    ) else {
+        return false;
+    };
+    lower != upper && lower.to_uppercase() == upper
+}
+
+/// Re-types `text` as if Caps Lock had been off, using the levels recorded in
+/// `layout`. Characters the layout can't type fall back to a plain case swap.
+pub fn toggle_caps_levels(text: &str, layout: &LayoutMap) -> String {
+    let mut strokes: Vec<(&KeyStroke, &String)> = layout.map.iter().collect();
+    strokes.sort_by_key(|(stroke, _)| **stroke);
+    text.chars()
+        .map(|ch| {
+            let mut buf = [0u8; 4];
+            let needle: &str = ch.encode_utf8(&mut buf);
+            strokes
+                .iter()
+                .find(|(stroke, output)| {
+                    output.as_str() == needle && is_caps_affected(layout, stroke.key, stroke.level)
+                })
+                .and_then(|(stroke, _)| layout.output(stroke.key, caps_partner(stroke.level)))
+                .map(str::to_string)
+                .unwrap_or_else(|| invert_case(needle))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,114 @@
+use super::*;
+use crate::keyboard_mapping::types::{KeyboardDirection, KeyboardLayout};
+use std::collections::HashMap;
+
+fn create_layout(name: &str, keys: Vec<(PhysicalKey, &str, &str)>) -> LayoutMap {
+    let mut map = HashMap::new();
+    for (key, base, shifted) in keys {
+        map.insert(KeyStroke::base(key), base.to_string());
+        map.insert(KeyStroke::new(key, ShiftLevel::Shift), shifted.to_string());
+    }
+    LayoutMap {
+        layout: KeyboardLayout {
+            id: name.to_string(),
+            lang_name: name.to_string(),
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        map,
+        sequences: HashMap::new(),
+    }
+}
+
+fn create_ru_layout() -> LayoutMap {
+    create_layout(
+        "Russian",
+        vec![
+            (PhysicalKey::KeyO, "щ", "Щ"),
+            (PhysicalKey::KeyS, "ы", "Ы"),
+            (PhysicalKey::KeyW, "ц", "Ц"),
+            (PhysicalKey::Digit4, "4", ";"),
+        ],
+    )
+}
+
+#[test]
+fn test_is_caps_inverted_detects_pattern() {
+    assert!(is_caps_inverted("hELLO wORLD"));
+    assert!(is_caps_inverted("пРИВЕТ"));
+    assert!(is_caps_inverted("hELLO, wORLD and tHINGS"));
+}
+
+#[test]
+fn test_is_caps_inverted_detects_all_caps() {
+    assert!(is_caps_inverted("ЩЫЦ ЦЫЩ"));
+    assert!(is_caps_inverted("HELLO WORLD!"));
+    assert!(!is_caps_inverted("A"));
+    assert!(!is_caps_inverted("A 1"));
+}
+
+#[test]
+fn test_is_caps_inverted_leaves_single_all_caps_words() {
+    assert!(!is_caps_inverted("NASA"));
+    assert!(!is_caps_inverted("OK"));
+    assert!(!is_caps_inverted("ЦРУ"));
+    assert!(!is_caps_inverted("ЩЫЦ!"));
+    assert!(!is_caps_inverted("NASA 2024"));
+    assert!(!is_caps_inverted("I AM"));
+}
+
+#[test]
+fn test_is_caps_inverted_rejects_normal_text() {
+    assert!(!is_caps_inverted("Hello World"));
+    assert!(!is_caps_inverted("NASA and ESA"));
+    assert!(!is_caps_inverted("hello world"));
+    assert!(!is_caps_inverted("שלום"));
+    assert!(!is_caps_inverted(""));
+}
+
+#[test]
+fn test_invert_case() {
+    assert_eq!(invert_case("hELLO wORLD!"), "Hello World!");
+    assert_eq!(invert_case("пРИВЕТ"), "Привет");
+    assert_eq!(invert_case("123 שלום"), "123 שלום");
+}
+
+#[test]
+fn test_toggle_caps_levels_uses_layout_levels() {
+    let ru = create_ru_layout();
+    assert_eq!(toggle_caps_levels("ЩЫЦ", &ru), "щыц");
+    assert_eq!(toggle_caps_levels("щЫЦ", &ru), "Щыц");
+}
+
+#[test]
+fn test_toggle_caps_levels_leaves_non_letter_keys() {
+    let ru = create_ru_layout();
+    assert_eq!(toggle_caps_levels(";4", &ru), ";4");
+}
+
+#[test]
+fn test_toggle_caps_levels_falls_back_to_case_swap() {
+    let ru = create_ru_layout();
+    assert_eq!(toggle_caps_levels("ЩaB", &ru), "щAb");
+}
+
+#[test]
+fn test_toggle_then_shift_repairs_caps_lock_typing() {
+    let ru = create_ru_layout();
+    let en = create_layout(
+        "English",
+        vec![
+            (PhysicalKey::KeyO, "o", "O"),
+            (PhysicalKey::KeyS, "s", "S"),
+            (PhysicalKey::KeyW, "w", "W"),
+        ],
+    );
+
+    let fixed = toggle_caps_levels("ЩЫЦ", &ru);
+    assert_eq!(
+        crate::keyboard_mapping::shift_text_language(&fixed, &ru, &en),
+        "osw"
+    );
+}
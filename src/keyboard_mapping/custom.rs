@@ -0,0 +1,146 @@
+use super::import::load_layout_files;
+use super::physical_key::PhysicalKey;
+use super::remap::is_rtl_char;
+use super::types::{
+    KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, LayoutSelector, ShiftLevel,
+    script_for_language,
+};
+use crate::config::{Config, CustomLayout, KeyLevels};
+use std::collections::{BTreeMap, HashMap};
+
+/// Writes the levels `keys` set into `layout_map`; an empty string removes
+/// the key at that level. Sequences through a changed keystroke no longer
+/// hold and are dropped.
+fn patch_keys(layout_map: &mut LayoutMap, keys: &BTreeMap<PhysicalKey, KeyLevels>) {
+    let mut changed = Vec::new();
+    for (key, levels) in keys {
+        for level in ShiftLevel::ALL {
+            let Some(output) = levels.get(level) else {
+                continue;
+            };
+            let stroke = KeyStroke::new(*key, level);
+            if output.is_empty() {
+                layout_map.map.remove(&stroke);
+            } else {
+                layout_map.map.insert(stroke, output.to_string());
+            }
+            changed.push(stroke);
+        }
+    }
+    layout_map
+        .sequences
+        .retain(|strokes, _| !strokes.iter().any(|stroke| changed.contains(stroke)));
+}
+
+fn direction_of(layout_map: &LayoutMap) -> KeyboardDirection {
+    if layout_map
+        .map
+        .values()
+        .flat_map(|s| s.chars())
+        .any(is_rtl_char)
+    {
+        KeyboardDirection::RTL
+    } else {
+        KeyboardDirection::LTR
+    }
+}
+
+/// Builds a custom layout, starting from a copy of its base when it has
+/// one. `None` when the base isn't among `layout_maps`.
+pub fn custom_layout_map(custom: &CustomLayout, layout_maps: &[LayoutMap]) -> Option<LayoutMap> {
+    let base = match &custom.base {
+        Some(base) => Some(LayoutSelector::Name(base.clone()).select(layout_maps)?),
+        None => None,
+    };
+    let mut layout_map = match base {
+        Some(base) => base.clone(),
+        None => LayoutMap::new(
+            KeyboardLayout {
+                id: String::new(),
+                lang_name: String::new(),
+                direction: KeyboardDirection::LTR,
+                variant: None,
+                language: None,
+                script: None,
+            },
+            HashMap::new(),
+        ),
+    };
+    patch_keys(&mut layout_map, &custom.keys);
+    let id = custom.id.trim().to_string();
+    let mut layout = KeyboardLayout {
+        lang_name: custom.name.clone().unwrap_or_else(|| id.clone()),
+        id,
+        direction: direction_of(&layout_map),
+        variant: None,
+        language: custom
+            .language
+            .clone()
+            .or_else(|| base.and_then(|base| base.layout.language.clone())),
+        script: None,
+    };
+    if layout.language.is_none() {
+        layout.language = layout.language_code().map(str::to_string);
+    }
+    layout.script = layout
+        .language
+        .as_deref()
+        .and_then(script_for_language)
+        .map(str::to_string);
+    layout_map.layout = layout;
+    Some(layout_map)
+}
+
+/// Applies the config's layout overrides to `layout_maps`, then appends its
+/// custom layouts. Overrides and custom layouts naming a layout that isn't
+/// there are skipped.
+pub fn apply_config_layouts(layout_maps: &mut Vec<LayoutMap>, cfg: &Config) {
+    for layout_override in &cfg.layout_overrides {
+        let selector = LayoutSelector::Name(layout_override.layout.clone());
+        let Some(index) = selector
+            .select(layout_maps)
+            .and_then(|selected| layout_maps.iter().position(|m| std::ptr::eq(m, selected)))
+        else {
+            continue;
+        };
+        let layout_map = &mut layout_maps[index];
+        patch_keys(layout_map, &layout_override.keys);
+        layout_map.layout.direction = direction_of(layout_map);
+    }
+    for custom in &cfg.custom_layouts {
+        if let Some(layout_map) = custom_layout_map(custom, layout_maps) {
+            layout_maps.push(layout_map);
+        }
+    }
+}
+
+/// Why overrides and custom layouts of `cfg` were left out of
+/// `layout_maps`, the layouts they were applied to.
+pub fn config_layout_warnings(cfg: &Config, layout_maps: &[LayoutMap]) -> Vec<String> {
+    let overrides = cfg
+        .layout_overrides
+        .iter()
+        .filter(|layout_override| {
+            LayoutSelector::Name(layout_override.layout.clone())
+                .select(layout_maps)
+                .is_none()
+        })
+        .map(|layout_override| format!("Layout override: no layout {}", layout_override.layout));
+    let customs = cfg.custom_layouts.iter().filter_map(|custom| {
+        let base = custom.base.as_deref()?;
+        let missing = !layout_maps.iter().any(|m| m.layout.id == custom.id.trim());
+        missing.then(|| format!("Custom layout {}: no base layout {}", custom.id, base))
+    });
+    overrides.chain(customs).collect()
+}
+
+/// The system layouts with the layout files, overrides and custom layouts
+/// of `cfg` added.
+pub fn with_configured_layouts(mut layout_maps: Vec<LayoutMap>, cfg: &Config) -> Vec<LayoutMap> {
+    layout_maps.extend(load_layout_files(&cfg.layout_files));
+    apply_config_layouts(&mut layout_maps, cfg);
+    layout_maps
+}
+
+#[cfg(test)]
+mod tests;
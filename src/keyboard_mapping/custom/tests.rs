@@ -0,0 +1,152 @@
+use super::*;
+use crate::keyboard_mapping::remap::shift_text_language;
+
+fn layout_map(id: &str, language: &str, pairs: &[(PhysicalKey, ShiftLevel, &str)]) -> LayoutMap {
+    let map = pairs
+        .iter()
+        .map(|(key, level, output)| (KeyStroke::new(*key, *level), output.to_string()))
+        .collect();
+    LayoutMap::new(
+        KeyboardLayout {
+            id: id.to_string(),
+            lang_name: id.to_string(),
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: Some(language.to_string()),
+            script: None,
+        },
+        map,
+    )
+}
+
+fn system_layouts() -> Vec<LayoutMap> {
+    let mut us = layout_map(
+        "us",
+        "en",
+        &[
+            (PhysicalKey::KeyA, ShiftLevel::Base, "a"),
+            (PhysicalKey::KeyW, ShiftLevel::Base, "w"),
+            (PhysicalKey::Quote, ShiftLevel::Base, "'"),
+            (PhysicalKey::KeyE, ShiftLevel::Base, "e"),
+        ],
+    );
+    us.sequences.insert(
+        vec![
+            KeyStroke::base(PhysicalKey::Backquote),
+            KeyStroke::base(PhysicalKey::KeyE),
+        ],
+        "è".to_string(),
+    );
+    let il = layout_map(
+        "il",
+        "he",
+        &[
+            (PhysicalKey::KeyA, ShiftLevel::Base, "ש"),
+            (PhysicalKey::KeyW, ShiftLevel::Base, "'"),
+            (PhysicalKey::Quote, ShiftLevel::Base, ","),
+            (PhysicalKey::Quote, ShiftLevel::Shift, "\""),
+        ],
+    );
+    vec![us, il]
+}
+
+fn config(toml_text: &str) -> Config {
+    let cfg: Config = toml::from_str(&format!("hotkey = \"Ctrl+Alt+K\"\n{}", toml_text)).unwrap();
+    cfg.validate().unwrap();
+    cfg
+}
+
+#[test]
+fn test_layout_override_patches_levels() {
+    let cfg = config(
+        "[[layout_overrides]]\nlayout = \"Hebrew\"\n\
+         keys.KeyW = { base = \"ק\" }\nkeys.Quote = [\"'\", \"\"]\n",
+    );
+    let mut maps = system_layouts();
+    maps[1].layout.lang_name = "Hebrew".to_string();
+    apply_config_layouts(&mut maps, &cfg);
+    assert_eq!(maps.len(), 2);
+    let il = &maps[1];
+    assert_eq!(il.output(PhysicalKey::KeyW, ShiftLevel::Base), Some("ק"));
+    assert_eq!(il.output(PhysicalKey::Quote, ShiftLevel::Base), Some("'"));
+    assert_eq!(il.output(PhysicalKey::Quote, ShiftLevel::Shift), None);
+    assert_eq!(il.output(PhysicalKey::KeyA, ShiftLevel::Base), Some("ש"));
+    assert_eq!(il.layout.direction, KeyboardDirection::RTL);
+}
+
+#[test]
+fn test_layout_override_drops_sequences_through_changed_keys() {
+    let cfg = config("[[layout_overrides]]\nlayout = \"us\"\nkeys.KeyE = [\"ε\"]\n");
+    let mut maps = system_layouts();
+    apply_config_layouts(&mut maps, &cfg);
+    assert!(maps[0].sequences.is_empty());
+}
+
+#[test]
+fn test_custom_layout_from_scratch() {
+    let cfg = config(
+        "[[custom_layouts]]\nid = \"hebphon\"\nname = \"Hebrew phonetic\"\n\
+         keys.KeyA = [\"א\", \"\", \"á\"]\nkeys.KeyW = [\"ו\"]\n",
+    );
+    let mut maps = system_layouts();
+    apply_config_layouts(&mut maps, &cfg);
+    let custom = &maps[2];
+    assert_eq!(custom.layout.id, "hebphon");
+    assert_eq!(custom.layout.lang_name, "Hebrew phonetic");
+    assert_eq!(custom.layout.language.as_deref(), Some("he"));
+    assert_eq!(custom.layout.script.as_deref(), Some("Hebr"));
+    assert_eq!(custom.layout.direction, KeyboardDirection::RTL);
+    assert_eq!(custom.map.len(), 3);
+    assert_eq!(
+        custom.output(PhysicalKey::KeyA, ShiftLevel::AltGr),
+        Some("á")
+    );
+    assert_eq!(custom.output(PhysicalKey::KeyA, ShiftLevel::Shift), None);
+}
+
+#[test]
+fn test_custom_layout_with_base_copies_it() {
+    let cfg = config(
+        "[[custom_layouts]]\nid = \"il-internal\"\nbase = \"il\"\n\
+         keys.KeyW = { base = \"ק\" }\nkeys.Quote = { base = \"'\" }\n",
+    );
+    let mut maps = system_layouts();
+    apply_config_layouts(&mut maps, &cfg);
+    assert_eq!(maps.len(), 3);
+    let custom = &maps[2];
+    assert_eq!(custom.layout.id, "il-internal");
+    assert_eq!(custom.layout.language.as_deref(), Some("he"));
+    assert_eq!(
+        custom.output(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("ש")
+    );
+    assert_eq!(
+        custom.output(PhysicalKey::Quote, ShiftLevel::Shift),
+        Some("\"")
+    );
+    // The base itself is left alone.
+    assert_eq!(
+        maps[1].output(PhysicalKey::KeyW, ShiftLevel::Base),
+        Some("'")
+    );
+
+    assert_eq!(shift_text_language("aw'", &maps[0], custom), "שק'");
+}
+
+#[test]
+fn test_unresolved_layouts_are_skipped_with_warnings() {
+    let cfg = config(
+        "[[layout_overrides]]\nlayout = \"dvorak\"\nkeys.KeyA = [\"x\"]\n\
+         [[custom_layouts]]\nid = \"mine\"\nbase = \"colemak\"\n",
+    );
+    let mut maps = system_layouts();
+    apply_config_layouts(&mut maps, &cfg);
+    assert_eq!(maps, system_layouts());
+    assert_eq!(
+        config_layout_warnings(&cfg, &maps),
+        [
+            "Layout override: no layout dvorak",
+            "Custom layout mine: no base layout colemak"
+        ]
+    );
+}
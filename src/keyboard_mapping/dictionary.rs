@@ -0,0 +1,284 @@
+use super::cache::{cache_key, cache_path};
+use encoding_rs::{Encoding, UTF_8};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Bump when the cached word list format or the way dictionaries are parsed
+// changes, so stale caches are rebuilt.
+const DICTIONARY_CACHE_VERSION: u32 = 1;
+
+const SYSTEM_DICTIONARY_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/share/dict",
+];
+
+// Word lists shipped by distributions under language names rather than codes.
+const WORD_LIST_ALIASES: &[(&str, &[&str])] = &[
+    ("en", &["words", "american-english", "british-english"]),
+    ("de", &["ngerman", "ogerman", "german"]),
+    ("fr", &["french"]),
+    ("es", &["spanish"]),
+    ("ru", &["russian"]),
+    ("uk", &["ukrainian"]),
+];
+
+/// A set of known words for one language, compared case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Parses a plain word list with one word per line.
+    pub fn from_word_list(data: &str) -> Self {
+        let words = data
+            .lines()
+            .map(str::trim)
+            .filter(|w| !w.is_empty() && !w.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+        Dictionary { words }
+    }
+
+    /// Parses a hunspell `.dic` file: a word count line followed by
+    /// `word/FLAGS` entries. Affix expansion is not applied.
+    pub fn from_hunspell_dic(data: &str) -> Self {
+        let words = data
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split(['/', '\t']).next())
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Dictionary { words }
+    }
+
+    /// Loads a hunspell `.dic`, decoded as its `.aff` file's `SET` line
+    /// says, or a UTF-8 word list. Text that isn't valid in its encoding is
+    /// an error rather than words full of replacement characters.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let hunspell = path.extension().and_then(|e| e.to_str()) == Some("dic");
+        let encoding = if hunspell {
+            hunspell_encoding(&path.with_extension("aff"))?
+        } else {
+            UTF_8
+        };
+        let data = encoding
+            .decode_without_bom_handling_and_without_replacement(&bytes)
+            .ok_or_else(|| format!("{} is not valid {}", path.display(), encoding.name()))?;
+        if hunspell {
+            Ok(Dictionary::from_hunspell_dic(&data))
+        } else {
+            Ok(Dictionary::from_word_list(&data))
+        }
+    }
+
+    pub fn extend(&mut self, other: Dictionary) {
+        self.words.extend(other.words);
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(&word.to_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Fraction of the words in `text` that are known, or `None` when the
+    /// text has no words.
+    pub fn known_word_ratio(&self, text: &str) -> Option<f64> {
+        let words: Vec<&str> = text
+            .split(|c: char| !c.is_alphabetic())
+            .filter(|w| !w.is_empty())
+            .collect();
+        if words.is_empty() {
+            return None;
+        }
+        let known = words.iter().filter(|w| self.contains(w)).count();
+        Some(known as f64 / words.len() as f64)
+    }
+}
+
+/// Dictionaries keyed by ISO 639-1 code.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dictionaries {
+    by_language: HashMap<String, Dictionary>,
+}
+
+impl Dictionaries {
+    /// Loads the dictionaries found on disk for the given languages. Each
+    /// language's words are cached in the user cache directory, keyed by its
+    /// files and their mtimes, so a run only parses them after they change.
+    pub fn discover(codes: &[&str]) -> Self {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        if let Ok(data) = crate::config::data_dir() {
+            dirs.push(data.join("dictionaries"));
+        }
+        dirs.extend(SYSTEM_DICTIONARY_DIRS.iter().map(PathBuf::from));
+        let mut dictionaries = Dictionaries::default();
+        for code in codes {
+            let files = dictionary_files(&dirs, code);
+            if files.is_empty() {
+                continue;
+            }
+            let key = cache_key(code, &files);
+            dictionaries.insert(code, cached_dictionary(code, &key, || load_all(&files)));
+        }
+        dictionaries
+    }
+
+    /// Loads the dictionaries in `dirs` for the given languages, uncached.
+    pub fn discover_in(dirs: &[PathBuf], codes: &[&str]) -> Self {
+        let mut dictionaries = Dictionaries::default();
+        for code in codes {
+            dictionaries.insert(code, load_all(&dictionary_files(dirs, code)));
+        }
+        dictionaries
+    }
+
+    /// Adds `dictionary` to the words known for `code`.
+    pub fn insert(&mut self, code: &str, dictionary: Dictionary) {
+        self.by_language
+            .entry(code.to_lowercase())
+            .or_default()
+            .extend(dictionary);
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Dictionary> {
+        self.by_language
+            .get(&code.to_lowercase())
+            .filter(|d| !d.is_empty())
+    }
+}
+
+/// The words of all `files`, skipping those that fail to load.
+fn load_all(files: &[PathBuf]) -> Dictionary {
+    let mut dictionary = Dictionary::default();
+    for path in files {
+        if let Ok(loaded) = Dictionary::load(path) {
+            dictionary.extend(loaded);
+        }
+    }
+    dictionary
+}
+
+fn dictionary_cache_file(code: &str) -> String {
+    format!("dictionary_{}.txt", code)
+}
+
+/// Reads a cached word list: a header with the cache version and `key`, a
+/// blank line, then one word per line. `None` when the file is missing or
+/// was written for another key.
+fn load_cached_dictionary(path: &Path, key: &str) -> Option<Dictionary> {
+    let data = fs::read_to_string(path).ok()?;
+    let (header, words) = data.split_once("\n\n")?;
+    if header != format!("{}\n{}", DICTIONARY_CACHE_VERSION, key) {
+        return None;
+    }
+    Some(Dictionary {
+        words: words.lines().map(str::to_string).collect(),
+    })
+}
+
+fn store_cached_dictionary(path: &Path, key: &str, dictionary: &Dictionary) -> Result<(), String> {
+    let mut data = format!("{}\n{}\n\n", DICTIONARY_CACHE_VERSION, key);
+    for word in &dictionary.words {
+        data.push_str(word);
+        data.push('\n');
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create cache directory: {}", e))?;
+    }
+    // Write then rename so a concurrent run never reads a partial file.
+    let tmp = path.with_extension("txt.tmp");
+    fs::write(&tmp, data).map_err(|e| format!("Failed to write dictionary cache: {}", e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to write dictionary cache: {}", e))
+}
+
+/// Returns the words cached for `code` under `key`, loading and caching
+/// them with `load` on a miss. An empty dictionary is not cached.
+fn cached_dictionary(code: &str, key: &str, load: impl FnOnce() -> Dictionary) -> Dictionary {
+    let path = cache_path(&dictionary_cache_file(code));
+    if let Some(dictionary) = path.as_deref().and_then(|p| load_cached_dictionary(p, key)) {
+        return dictionary;
+    }
+    let dictionary = load();
+    if let Some(path) = path
+        && !dictionary.is_empty()
+    {
+        let _ = store_cached_dictionary(&path, key, &dictionary);
+    }
+    dictionary
+}
+
+/// The encoding named by the `SET` line of a hunspell `.aff` file, UTF-8
+/// when the file or the line is missing. Hunspell names some encodings
+/// differently from the WHATWG labels, e.g. `microsoft-cp1251`.
+fn hunspell_encoding(aff: &Path) -> Result<&'static Encoding, String> {
+    let Ok(bytes) = fs::read(aff) else {
+        return Ok(UTF_8);
+    };
+    // The SET line is ASCII whatever the encoding.
+    let Some(label) = String::from_utf8_lossy(&bytes).lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("SET")
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .map(|rest| rest.trim().to_string())
+    }) else {
+        return Ok(UTF_8);
+    };
+    let label = label.strip_prefix("microsoft-").unwrap_or(&label);
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| format!("Unknown encoding {} in {}", label, aff.display()))
+}
+
+fn dictionary_files(dirs: &[PathBuf], code: &str) -> Vec<PathBuf> {
+    let aliases: &[&str] = WORD_LIST_ALIASES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, names)| *names)
+        .unwrap_or(&[]);
+    let mut files = Vec::new();
+    // `words` is often a link to `american-english`; read each file once.
+    let mut seen = HashSet::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut matched: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+                let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+                let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                let code_match = stem == code
+                    || stem.starts_with(&format!("{}_", code))
+                    || stem.starts_with(&format!("{}-", code));
+                (code_match && matches!(ext, "dic" | "txt")) || aliases.contains(&name)
+            })
+            .collect();
+        matched.sort();
+        files.extend(
+            matched
+                .into_iter()
+                .filter(|p| seen.insert(fs::canonicalize(p).unwrap_or_else(|_| p.clone()))),
+        );
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,128 @@
+use super::*;
+use crate::test_utils::{TempDirGuard, temp_dir, use_temp_cache_dir};
+
+#[test]
+fn test_word_list_is_case_insensitive() {
+    let dict = Dictionary::from_word_list("Hello\nworld\n\n# comment\n");
+    assert_eq!(dict.len(), 2);
+    assert!(dict.contains("hello"));
+    assert!(dict.contains("WORLD"));
+    assert!(!dict.contains("comment"));
+}
+
+#[test]
+fn test_hunspell_dic_strips_count_and_flags() {
+    let dict = Dictionary::from_hunspell_dic("3\nשלום/ABC\nhouse/S\ntree\n");
+    assert_eq!(dict.len(), 3);
+    assert!(dict.contains("שלום"));
+    assert!(dict.contains("house"));
+    assert!(dict.contains("tree"));
+    assert!(!dict.contains("3"));
+}
+
+#[test]
+fn test_known_word_ratio() {
+    let dict = Dictionary::from_word_list("the\nhouse");
+    assert_eq!(dict.known_word_ratio("The house, the car!"), Some(0.75));
+    assert_eq!(dict.known_word_ratio("123 ..."), None);
+}
+
+#[test]
+fn test_discover_in_matches_codes_and_aliases() {
+    let dir = temp_dir("dict_discover");
+    let _guard = TempDirGuard::new(dir.clone());
+    fs::write(dir.join("he_IL.dic"), "1\nשלום/X\n").unwrap();
+    fs::write(dir.join("words"), "hello\n").unwrap();
+    fs::write(dir.join("en_US.dic"), "1\nworld/S\n").unwrap();
+    fs::write(dir.join("en_US.aff"), "SET UTF-8\n").unwrap();
+
+    let dictionaries = Dictionaries::discover_in(std::slice::from_ref(&dir), &["en", "he", "ru"]);
+    let en = dictionaries.get("en").unwrap();
+    assert!(en.contains("hello"));
+    assert!(en.contains("world"));
+    assert!(!en.contains("set"));
+    assert!(dictionaries.get("he").unwrap().contains("שלום"));
+    assert!(dictionaries.get("ru").is_none());
+}
+
+#[test]
+fn test_load_decodes_the_aff_set_encoding() {
+    let dir = temp_dir("dict_encoding");
+    let _guard = TempDirGuard::new(dir.clone());
+    // "привет" and "hallo" in KOI8-R, "straße" in ISO-8859-1.
+    fs::write(dir.join("ru_RU.aff"), "SET KOI8-R\nTRY оеаинтср\n").unwrap();
+    fs::write(
+        dir.join("ru_RU.dic"),
+        b"2\n\xd0\xd2\xc9\xd7\xc5\xd4/A\nhallo\n",
+    )
+    .unwrap();
+    fs::write(dir.join("de_DE.aff"), "SET ISO8859-1\n").unwrap();
+    fs::write(dir.join("de_DE.dic"), b"1\nstra\xdfe/N\n").unwrap();
+    fs::write(dir.join("uk_UA.aff"), "SET microsoft-cp1251\n").unwrap();
+    fs::write(dir.join("uk_UA.dic"), b"1\n\xea\xe8\xbf\xe2\n").unwrap();
+
+    let ru = Dictionary::load(&dir.join("ru_RU.dic")).unwrap();
+    assert!(ru.contains("привет"));
+    assert!(ru.contains("hallo"));
+    let de = Dictionary::load(&dir.join("de_DE.dic")).unwrap();
+    assert!(de.contains("straße"));
+    let uk = Dictionary::load(&dir.join("uk_UA.dic")).unwrap();
+    assert!(uk.contains("київ"));
+}
+
+#[test]
+fn test_load_rejects_text_invalid_in_its_encoding() {
+    let dir = temp_dir("dict_invalid");
+    let _guard = TempDirGuard::new(dir.clone());
+    fs::write(dir.join("ru_RU.dic"), b"1\n\xd0\xd2\xc9\n").unwrap();
+    fs::write(dir.join("words"), b"caf\xe9\n").unwrap();
+    fs::write(dir.join("xx.aff"), "SET NO-SUCH-CHARSET\n").unwrap();
+    fs::write(dir.join("xx.dic"), "1\nword\n").unwrap();
+
+    let invalid = |name: &str| Dictionary::load(&dir.join(name)).unwrap_err();
+    assert!(invalid("ru_RU.dic").ends_with("ru_RU.dic is not valid UTF-8"));
+    assert!(invalid("words").ends_with("words is not valid UTF-8"));
+    assert!(invalid("xx.dic").starts_with("Unknown encoding NO-SUCH-CHARSET in "));
+}
+
+#[test]
+fn test_discover_in_missing_dir() {
+    let dictionaries =
+        Dictionaries::discover_in(&[PathBuf::from("/nonexistent/kms/dicts")], &["en"]);
+    assert!(dictionaries.get("en").is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dictionary_files_reads_linked_files_once() {
+    let dir = temp_dir("dict_links");
+    let _guard = TempDirGuard::new(dir.clone());
+    fs::write(dir.join("american-english"), "hello\n").unwrap();
+    std::os::unix::fs::symlink(dir.join("american-english"), dir.join("words")).unwrap();
+
+    assert_eq!(
+        dictionary_files(std::slice::from_ref(&dir), "en"),
+        vec![dir.join("american-english")]
+    );
+}
+
+#[test]
+fn test_cached_dictionary_round_trip() {
+    use_temp_cache_dir();
+    let code = format!("test{:?}", std::time::SystemTime::now())
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>();
+    let words = || Dictionary::from_word_list("hello\nworld\n");
+
+    assert_eq!(cached_dictionary(&code, "key", words), words());
+    let path = cache_path(&dictionary_cache_file(&code)).unwrap();
+    assert!(path.exists());
+    assert_eq!(
+        cached_dictionary(&code, "key", || panic!("loaded despite the cache")),
+        words()
+    );
+    let rebuilt = cached_dictionary(&code, "other key", || Dictionary::from_word_list("hi"));
+    assert!(rebuilt.contains("hi") && !rebuilt.contains("hello"));
+    let _ = fs::remove_file(path);
+}
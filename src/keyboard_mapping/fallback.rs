@@ -1,23 +1,65 @@
 #![cfg(not(any(target_os = "windows", target_os = "linux")))]
+use super::builtin::builtin_layout_maps_for;
+use super::custom::with_configured_layouts;
+use super::types::{KeyboardDirection, KeyboardLayout, LayoutMap};
+use crate::config::Config;
+use std::collections::HashMap;
 
-pub fn get_layout(_index: usize) -> Option<KeyboardLayout> {
-    panic!("keyboard_mapping: get_layout is not implemented for this OS");
+fn load_config() -> Config {
+    crate::config::load_config().unwrap_or_else(|_| Config::with_defaults())
+}
+
+/// There is no system layout source here, so the built-in layouts named by
+/// the config's `layouts` stand in, or all of them when it names none.
+fn system_layout_maps(cfg: &Config) -> Vec<LayoutMap> {
+    let ids: Vec<String> = cfg.layouts.iter().map(|spec| spec.layout.clone()).collect();
+    builtin_layout_maps_for(&ids)
+}
+
+pub fn get_layout(index: usize) -> Option<KeyboardLayout> {
+    system_layout_maps(&load_config())
+        .into_iter()
+        .nth(index)
+        .map(|map| map.layout)
 }
 
 pub fn list_layouts() -> Vec<KeyboardLayout> {
-    panic!("keyboard_mapping: list_layouts is not implemented for this OS");
+    system_layout_maps(&load_config())
+        .into_iter()
+        .map(|map| map.layout)
+        .collect()
 }
 
-pub fn vk_to_char_map_for_layout(_index: u32) -> LayoutMap {
-    panic!("keyboard_mapping: vk_to_char_map_for_layout is not implemented for this OS");
+pub fn vk_to_char_map_for_layout(index: u32) -> LayoutMap {
+    system_layout_maps(&load_config())
+        .into_iter()
+        .nth(index as usize)
+        .unwrap_or_else(|| {
+            LayoutMap::new(
+                KeyboardLayout {
+                    id: index.to_string(),
+                    lang_name: index.to_string(),
+                    direction: KeyboardDirection::LTR,
+                    variant: None,
+                    language: None,
+                    script: None,
+                },
+                HashMap::new(),
+            )
+        })
 }
 
 pub fn vk_to_char_map_default() -> LayoutMap {
-    panic!("keyboard_mapping: vk_to_char_map_default is not implemented for this OS");
+    vk_to_char_map_for_layout(0)
 }
 
 pub fn all_layout_vk_maps() -> Vec<LayoutMap> {
-    panic!("keyboard_mapping: all_layout_vk_maps is not implemented for this OS");
+    all_layout_vk_maps_for(&load_config())
+}
+
+/// `all_layout_vk_maps` for an already loaded config.
+pub fn all_layout_vk_maps_for(cfg: &Config) -> Vec<LayoutMap> {
+    with_configured_layouts(system_layout_maps(cfg), cfg)
 }
 
 #[cfg(test)]
@@ -1,35 +1,31 @@
 use super::*;
 
 #[test]
-#[should_panic(expected = "keyboard_mapping: get_layout is not implemented for this OS")]
-fn test_get_layout_panics() {
-    get_layout(0);
+fn test_list_layouts_returns_builtin_layouts() {
+    let layouts = list_layouts();
+    assert!(!layouts.is_empty());
+    assert_eq!(get_layout(0), layouts.first().cloned());
 }
 
 #[test]
-#[should_panic(expected = "keyboard_mapping: list_layouts is not implemented for this OS")]
-fn test_list_layouts_panics() {
-    list_layouts();
+fn test_get_layout_invalid_index() {
+    assert!(get_layout(list_layouts().len()).is_none());
 }
 
 #[test]
-#[should_panic(
-    expected = "keyboard_mapping: vk_to_char_map_for_layout is not implemented for this OS"
-)]
-fn test_vk_to_char_map_for_layout_panics() {
-    vk_to_char_map_for_layout(0);
+fn test_vk_to_char_map_default_has_keys() {
+    assert!(!vk_to_char_map_default().map.is_empty());
 }
 
 #[test]
-#[should_panic(
-    expected = "keyboard_mapping: vk_to_char_map_default is not implemented for this OS"
-)]
-fn test_vk_to_char_map_default_panics() {
-    vk_to_char_map_default();
+fn test_vk_to_char_map_for_layout_invalid_index_is_empty() {
+    let index = list_layouts().len() as u32;
+    let layout_map = vk_to_char_map_for_layout(index);
+    assert!(layout_map.map.is_empty());
+    assert_eq!(layout_map.layout.id, index.to_string());
 }
 
 #[test]
-#[should_panic(expected = "keyboard_mapping: all_layout_vk_maps is not implemented for this OS")]
-fn test_all_layout_vk_maps_panics() {
-    all_layout_vk_maps();
+fn test_all_layout_vk_maps_returns_builtin_layouts() {
+    assert!(all_layout_vk_maps().len() >= list_layouts().len());
 }
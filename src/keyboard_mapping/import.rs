@@ -0,0 +1,92 @@
+pub mod cldr;
+pub mod keylayout;
+pub mod klc;
+
+use super::types::{LayoutMap, script_for_language};
+use crate::config::LayoutFile;
+use std::fs;
+use std::path::Path;
+
+/// Decodes a layout file: UTF-16 when it starts with a byte order mark, as
+/// Keyboard Layout Creator saves them, UTF-8 otherwise.
+fn decode_text(bytes: &[u8]) -> Result<String, String> {
+    let utf16 = |units: Vec<u16>| {
+        String::from_utf16(&units).map_err(|e| format!("Invalid UTF-16 text: {}", e))
+    };
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(
+            rest.chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect(),
+        ),
+        [0xFE, 0xFF, rest @ ..] => utf16(
+            rest.chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect(),
+        ),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => {
+            String::from_utf8(rest.to_vec()).map_err(|e| format!("Invalid UTF-8 text: {}", e))
+        }
+        _ => String::from_utf8(bytes.to_vec()).map_err(|e| format!("Invalid UTF-8 text: {}", e)),
+    }
+}
+
+/// Reads the layouts of a layout file, choosing the format by its
+/// extension. XKB keymaps give one layout per group; the other formats
+/// hold one layout.
+pub fn load_layout_file(path: &Path) -> Result<Vec<LayoutMap>, String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let bytes = fs::read(path)
+        .map_err(|e| format!("Failed to read layout file {}: {}", path.display(), e))?;
+    let text = decode_text(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match extension.as_deref() {
+        Some("klc") => klc::parse_klc(&text).map(|map| vec![map]),
+        Some("keylayout") => keylayout::parse_keylayout(&text).map(|map| vec![map]),
+        Some("xml") => cldr::parse_cldr_keyboard(&text).map(|map| vec![map]),
+        Some("xkb") => xkb_layout_maps(&text, path),
+        _ => Err(format!(
+            "Unknown layout file format: {} (expected .klc, .keylayout, .xml or .xkb)",
+            path.display()
+        )),
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(target_os = "linux")]
+fn xkb_layout_maps(text: &str, path: &Path) -> Result<Vec<LayoutMap>, String> {
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    super::linux::layout_maps_from_xkb(text, &name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn xkb_layout_maps(_text: &str, _path: &Path) -> Result<Vec<LayoutMap>, String> {
+    Err("XKB keymaps can only be loaded on Linux".to_string())
+}
+
+/// Loads every file in `files`, skipping the ones that fail to load. A
+/// language given for a file replaces the one read from it.
+pub fn load_layout_files(files: &[LayoutFile]) -> Vec<LayoutMap> {
+    let mut layout_maps = Vec::new();
+    for file in files {
+        let Ok(mut maps) = load_layout_file(file.path()) else {
+            continue;
+        };
+        if let Some(language) = file.language() {
+            for layout_map in &mut maps {
+                layout_map.layout.language = Some(language.to_string());
+                layout_map.layout.script = script_for_language(language).map(str::to_string);
+            }
+        }
+        layout_maps.extend(maps);
+    }
+    layout_maps
+}
+
+#[cfg(test)]
+mod tests;
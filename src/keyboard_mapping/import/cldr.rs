@@ -0,0 +1,593 @@
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::remap::is_rtl_char;
+use crate::keyboard_mapping::types::{
+    KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, ShiftLevel, script_for_language,
+};
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Scan codes of each row of the hardware forms, as in CLDR's
+// `scanCodes.xml`, top row first.
+const FORMS: &[(&str, &[&str])] = &[
+    (
+        "us",
+        &[
+            "29 02 03 04 05 06 07 08 09 0A 0B 0C 0D",
+            "10 11 12 13 14 15 16 17 18 19 1A 1B 2B",
+            "1E 1F 20 21 22 23 24 25 26 27 28",
+            "2C 2D 2E 2F 30 31 32 33 34 35",
+            "39",
+        ],
+    ),
+    (
+        "iso",
+        &[
+            "29 02 03 04 05 06 07 08 09 0A 0B 0C 0D",
+            "10 11 12 13 14 15 16 17 18 19 1A 1B",
+            "1E 1F 20 21 22 23 24 25 26 27 28 2B",
+            "56 2C 2D 2E 2F 30 31 32 33 34 35",
+            "39",
+        ],
+    ),
+    (
+        "jis",
+        &[
+            "29 02 03 04 05 06 07 08 09 0A 0B 0C 0D 7D",
+            "10 11 12 13 14 15 16 17 18 19 1A 1B",
+            "1E 1F 20 21 22 23 24 25 26 27 28 2B",
+            "2C 2D 2E 2F 30 31 32 33 34 35 73",
+            "39",
+        ],
+    ),
+    (
+        "abnt2",
+        &[
+            "29 02 03 04 05 06 07 08 09 0A 0B 0C 0D",
+            "10 11 12 13 14 15 16 17 18 19 1A 1B",
+            "1E 1F 20 21 22 23 24 25 26 27 28 2B",
+            "56 2C 2D 2E 2F 30 31 32 33 34 35 73",
+            "39",
+        ],
+    ),
+    (
+        "ks",
+        &[
+            "29 02 03 04 05 06 07 08 09 0A 0B 0C 0D",
+            "10 11 12 13 14 15 16 17 18 19 1A 1B 2B",
+            "1E 1F 20 21 22 23 24 25 26 27 28",
+            "2C 2D 2E 2F 30 31 32 33 34 35",
+            "39",
+        ],
+    ),
+];
+
+// Keys CLDR keyboards may use without defining them, from the implied
+// punctuation key list. Letters and digits are implied as themselves.
+const IMPLIED_KEYS: &[(&str, &str)] = &[
+    ("grave", "`"),
+    ("tilde", "~"),
+    ("bang", "!"),
+    ("at", "@"),
+    ("hash", "#"),
+    ("dollar", "$"),
+    ("percent", "%"),
+    ("caret", "^"),
+    ("amp", "&"),
+    ("asterisk", "*"),
+    ("open-paren", "("),
+    ("close-paren", ")"),
+    ("hyphen", "-"),
+    ("underscore", "_"),
+    ("equal", "="),
+    ("plus", "+"),
+    ("open-square", "["),
+    ("close-square", "]"),
+    ("open-curly", "{"),
+    ("close-curly", "}"),
+    ("backslash", "\\"),
+    ("pipe", "|"),
+    ("semi-colon", ";"),
+    ("colon", ":"),
+    ("apos", "'"),
+    ("double-quote", "\""),
+    ("comma", ","),
+    ("less-than", "<"),
+    ("period", "."),
+    ("greater-than", ">"),
+    ("slash", "/"),
+    ("question", "?"),
+    ("space", " "),
+];
+
+// Markers (`\m{acute}`) stand for dead-key state. They are kept in outputs
+// as private use characters so transforms can match them.
+const MARKER_BASE: u32 = 0xF0000;
+
+#[derive(Debug, Deserialize)]
+struct Keyboard3 {
+    #[serde(rename = "@locale")]
+    locale: String,
+    #[serde(default)]
+    info: Info,
+    #[serde(default)]
+    keys: Keys,
+    #[serde(rename = "layers", default)]
+    layers: Vec<Layers>,
+    #[serde(default)]
+    variables: Variables,
+    #[serde(rename = "transforms", default)]
+    transforms: Vec<Transforms>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Info {
+    #[serde(rename = "@name")]
+    name: Option<String>,
+    #[serde(rename = "@layout")]
+    layout: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Keys {
+    #[serde(rename = "key", default)]
+    keys: Vec<KeyDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyDef {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@output")]
+    output: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Layers {
+    #[serde(rename = "@formId")]
+    form_id: Option<String>,
+    #[serde(rename = "layer", default)]
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Layer {
+    #[serde(rename = "@modifiers")]
+    modifiers: Option<String>,
+    #[serde(rename = "row", default)]
+    rows: Vec<Row>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Row {
+    #[serde(rename = "@keys")]
+    keys: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Variables {
+    #[serde(rename = "$value", default)]
+    variables: Vec<Variable>,
+}
+
+#[derive(Debug, Deserialize)]
+enum Variable {
+    #[serde(rename = "string")]
+    String(VariableDef),
+    #[serde(rename = "set")]
+    Set(VariableDef),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct VariableDef {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@value")]
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Transforms {
+    #[serde(rename = "@type")]
+    kind: String,
+    #[serde(rename = "transformGroup", default)]
+    groups: Vec<TransformGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransformGroup {
+    #[serde(rename = "transform", default)]
+    transforms: Vec<Transform>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Transform {
+    #[serde(rename = "@from")]
+    from: String,
+    #[serde(rename = "@to", default)]
+    to: String,
+}
+
+/// Part of an output or transform after escapes are resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    /// `$[set]` in a transform's `from`.
+    Set(String),
+    /// `$[1:set]` in a transform's `to`: the element of `set` at the
+    /// position matched by the `from` set.
+    SetMap(String),
+}
+
+#[derive(Default)]
+struct Context {
+    strings: HashMap<String, String>,
+    sets: HashMap<String, Vec<String>>,
+    markers: HashMap<String, char>,
+}
+
+impl Context {
+    fn marker(&mut self, name: &str) -> char {
+        let next = MARKER_BASE + self.markers.len() as u32;
+        *self
+            .markers
+            .entry(name.to_string())
+            .or_insert_with(|| char::from_u32(next).unwrap_or('\u{FFFD}'))
+    }
+
+    /// Resolves `\u{...}`, `\m{...}` and `${...}` in `text`. In transform
+    /// patterns, sets are kept as pieces and regex syntax other than
+    /// grouping makes the result `None`.
+    fn parse(&mut self, text: &str, pattern: bool) -> Option<Vec<Piece>> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        let braced = |chars: &mut std::iter::Peekable<std::str::Chars>, open: char| {
+            chars.next_if_eq(&open)?;
+            let close = if open == '{' { '}' } else { ']' };
+            let mut inner = String::new();
+            for c in chars.by_ref() {
+                if c == close {
+                    return Some(inner);
+                }
+                inner.push(c);
+            }
+            None
+        };
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.peek().copied() {
+                    Some('u') => {
+                        chars.next();
+                        for code in braced(&mut chars, '{')?.split_whitespace() {
+                            literal.push(char::from_u32(u32::from_str_radix(code, 16).ok()?)?);
+                        }
+                    }
+                    Some('m') => {
+                        chars.next();
+                        let name = braced(&mut chars, '{')?;
+                        if name == "." {
+                            return None;
+                        }
+                        literal.push(self.marker(&name));
+                    }
+                    Some(escaped) => {
+                        chars.next();
+                        literal.push(escaped);
+                    }
+                    None => return None,
+                },
+                '$' if chars.peek() == Some(&'{') => {
+                    let name = braced(&mut chars, '{')?;
+                    literal.push_str(self.strings.get(&name)?);
+                }
+                '$' if pattern && chars.peek() == Some(&'[') => {
+                    let name = braced(&mut chars, '[')?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(match name.split_once(':') {
+                        Some((_, set)) => Piece::SetMap(set.to_string()),
+                        None => Piece::Set(name),
+                    });
+                }
+                '(' | ')' if pattern => {}
+                '[' | ']' | '{' | '}' | '*' | '+' | '?' | '|' | '.' | '^' | '$' if pattern => {
+                    return None;
+                }
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Text(literal));
+        }
+        Some(pieces)
+    }
+
+    fn text(&mut self, text: &str) -> Option<String> {
+        self.parse(text, false).map(|pieces| {
+            pieces
+                .into_iter()
+                .map(|piece| match piece {
+                    Piece::Text(text) => text,
+                    _ => String::new(),
+                })
+                .collect()
+        })
+    }
+
+    /// Expands a transform into literal `from` -> `to` pairs. A set in
+    /// `from` yields one pair per element, mapped through `$[1:set]` in
+    /// `to` when present.
+    fn expand_transform(&mut self, transform: &Transform) -> Vec<(String, String)> {
+        let (Some(from), Some(to)) = (
+            self.parse(&transform.from, true),
+            self.parse(&transform.to, true),
+        ) else {
+            return Vec::new();
+        };
+        let from_sets: Vec<&String> = from
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Set(set) => Some(set),
+                _ => None,
+            })
+            .collect();
+        let to_sets: Vec<&String> = to
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::SetMap(set) => Some(set),
+                _ => None,
+            })
+            .collect();
+        if from_sets.len() > 1 || to_sets.len() > 1 || (from_sets.is_empty() && !to_sets.is_empty())
+        {
+            return Vec::new();
+        }
+        let join = |pieces: &[Piece], element: &str| -> String {
+            pieces
+                .iter()
+                .map(|piece| match piece {
+                    Piece::Text(text) => text.as_str(),
+                    _ => element,
+                })
+                .collect()
+        };
+        let Some(set) = from_sets.first() else {
+            return vec![(join(&from, ""), join(&to, ""))];
+        };
+        let Some(elements) = self.sets.get(*set) else {
+            return Vec::new();
+        };
+        let mapped = match to_sets.first() {
+            Some(to_set) => match self.sets.get(*to_set) {
+                Some(mapped) if mapped.len() == elements.len() => Some(mapped),
+                _ => return Vec::new(),
+            },
+            None => None,
+        };
+        elements
+            .iter()
+            .enumerate()
+            .map(|(i, element)| {
+                let target = mapped.map(|m| m[i].as_str()).unwrap_or("");
+                (join(&from, element), join(&to, target))
+            })
+            .collect()
+    }
+
+    fn is_marker(&self, c: char) -> bool {
+        (MARKER_BASE..MARKER_BASE + self.markers.len() as u32).contains(&(c as u32))
+    }
+}
+
+/// The shift level of a layer. Alternative modifier sets are separated by
+/// commas; the first one with a level is used. Right Alt and Ctrl+Alt are
+/// AltGr; Caps Lock layers are left out.
+fn layer_level(modifiers: &str) -> Option<ShiftLevel> {
+    modifiers.split(',').find_map(|set| {
+        let (mut shift, mut alt, mut ctrl, mut alt_r) = (false, false, false, false);
+        for token in set.split_whitespace() {
+            match token {
+                "none" => {}
+                "shift" | "shiftL" | "shiftR" => shift = true,
+                "altR" => alt_r = true,
+                "alt" | "altL" => alt = true,
+                "ctrl" | "ctrlL" | "ctrlR" => ctrl = true,
+                _ => return None,
+            }
+        }
+        let altgr = alt_r || (alt && ctrl);
+        if !altgr && (alt || ctrl) {
+            return None;
+        }
+        Some(match (shift, altgr) {
+            (false, false) => ShiftLevel::Base,
+            (true, false) => ShiftLevel::Shift,
+            (false, true) => ShiftLevel::AltGr,
+            (true, true) => ShiftLevel::ShiftAltGr,
+        })
+    })
+}
+
+fn form_rows(form: &str) -> Option<Vec<Vec<PhysicalKey>>> {
+    let (_, rows) = FORMS.iter().find(|(id, _)| *id == form)?;
+    Some(
+        rows.iter()
+            .map(|row| {
+                row.split_whitespace()
+                    .filter_map(|code| u16::from_str_radix(code, 16).ok())
+                    .filter_map(PhysicalKey::from_scan_code)
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+/// The variant part of the layout id: the `info` layout family, such as
+/// `qwerty`, and the hardware form, so `mt` keyboards get ids like
+/// `mt(qwerty-iso)` that don't change with their display name.
+fn layout_variant(family: Option<&str>, form: &str) -> String {
+    let family: String = family
+        .unwrap_or("")
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    if family.is_empty() {
+        form.to_string()
+    } else {
+        format!("{}-{}", family, form)
+    }
+}
+
+/// The script subtag of a locale such as `sr-Cyrl`, or the usual script
+/// of its language.
+fn locale_script(locale: &str) -> Option<String> {
+    locale
+        .split(['-', '_'])
+        .skip(1)
+        .find(|tag| tag.len() == 4 && tag.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(|tag| tag[..1].to_ascii_uppercase() + &tag[1..].to_ascii_lowercase())
+        .or_else(|| script_for_language(locale).map(str::to_string))
+}
+
+fn implied_output(id: &str) -> Option<String> {
+    let mut chars = id.chars();
+    if let (Some(c), None) = (chars.next(), chars.next())
+        && c.is_ascii_alphanumeric()
+    {
+        return Some(c.to_string());
+    }
+    IMPLIED_KEYS
+        .iter()
+        .find(|(implied, _)| *implied == id)
+        .map(|(_, output)| output.to_string())
+}
+
+/// Parses a CLDR keyboard (LDML Keyboard 3.0, `<keyboard3>`). The hardware
+/// layers give the key map, and `simple` transforms that combine two
+/// keystrokes, such as a dead-key marker followed by a letter, give the
+/// sequences. Transforms using regular expressions beyond sets are
+/// skipped.
+pub fn parse_cldr_keyboard(xml: &str) -> Result<LayoutMap, String> {
+    let keyboard: Keyboard3 =
+        from_str(xml).map_err(|e| format!("Invalid CLDR keyboard XML: {}", e))?;
+    let layers = keyboard
+        .layers
+        .iter()
+        .find(|layers| layers.form_id.as_deref() != Some("touch"))
+        .ok_or("No hardware layers")?;
+    let form = layers.form_id.as_deref().unwrap_or("us");
+    let rows = form_rows(form).ok_or_else(|| format!("Unsupported form: {}", form))?;
+
+    let mut context = Context::default();
+    for variable in &keyboard.variables.variables {
+        match variable {
+            Variable::String(def) => {
+                if let Some(value) = context.text(&def.value) {
+                    context.strings.insert(def.id.clone(), value);
+                }
+            }
+            Variable::Set(def) => {
+                let elements = def
+                    .value
+                    .split_whitespace()
+                    .filter_map(|element| context.text(element))
+                    .collect();
+                context.sets.insert(def.id.clone(), elements);
+            }
+            Variable::Other => {}
+        }
+    }
+    let mut key_outputs: HashMap<&str, String> = HashMap::new();
+    for key in &keyboard.keys.keys {
+        if let Some(output) = key.output.as_deref().and_then(|o| context.text(o)) {
+            key_outputs.insert(key.id.as_str(), output);
+        }
+    }
+
+    let mut map: HashMap<KeyStroke, String> = HashMap::new();
+    // What each keystroke emits, markers included, for matching transforms.
+    let mut emitted: Vec<(KeyStroke, String)> = Vec::new();
+    for layer in &layers.layers {
+        let Some(level) = layer_level(layer.modifiers.as_deref().unwrap_or("none")) else {
+            continue;
+        };
+        for (row, keys) in layer.rows.iter().zip(&rows) {
+            for (id, key) in row.keys.split_whitespace().zip(keys) {
+                let Some(raw) = key_outputs.get(id).cloned().or_else(|| implied_output(id)) else {
+                    continue;
+                };
+                let stroke = KeyStroke::new(*key, level);
+                if emitted.iter().any(|(s, _)| *s == stroke) {
+                    continue;
+                }
+                let text: String = raw.chars().filter(|c| !context.is_marker(*c)).collect();
+                if !text.is_empty()
+                    && !text.chars().any(char::is_control)
+                    && (level == ShiftLevel::Base || map.get(&KeyStroke::base(*key)) != Some(&text))
+                {
+                    map.insert(stroke, text);
+                } else if text == raw {
+                    // Left out and not a dead key either.
+                    continue;
+                }
+                emitted.push((stroke, raw));
+            }
+        }
+    }
+
+    let mut sequences = HashMap::new();
+    for transforms in keyboard.transforms.iter().filter(|t| t.kind == "simple") {
+        for transform in transforms.groups.iter().flat_map(|g| &g.transforms) {
+            for (from, to) in context.expand_transform(transform) {
+                if to.is_empty() || to.chars().any(|c| context.is_marker(c)) {
+                    continue;
+                }
+                for (first, first_raw) in &emitted {
+                    let Some(rest) = from.strip_prefix(first_raw.as_str()) else {
+                        continue;
+                    };
+                    for (second, _) in emitted
+                        .iter()
+                        .filter(|(s, raw)| raw == rest && map.contains_key(s))
+                    {
+                        sequences
+                            .entry(vec![*first, *second])
+                            .or_insert_with(|| to.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    let direction = if map.values().flat_map(|s| s.chars()).any(is_rtl_char) {
+        KeyboardDirection::RTL
+    } else {
+        KeyboardDirection::LTR
+    };
+    let variant = layout_variant(keyboard.info.layout.as_deref(), form);
+    let name = keyboard
+        .info
+        .name
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| keyboard.locale.clone());
+    let layout = KeyboardLayout {
+        id: format!("{}({})", keyboard.locale, variant),
+        lang_name: name,
+        direction,
+        variant: Some(variant),
+        script: locale_script(&keyboard.locale),
+        language: Some(keyboard.locale),
+    };
+    let mut layout_map = LayoutMap::new(layout, map);
+    layout_map.sequences = sequences;
+    Ok(layout_map)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,169 @@
+use super::*;
+
+const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE keyboard3 SYSTEM "../dtd/ldmlKeyboard3.dtd">
+<keyboard3 xmlns="https://schemas.unicode.org/cldr/45/keyboard3" locale="mt" conformsTo="45">
+    <info name="Maltese Test" author="test" layout="QWERTY" indicator="MT"/>
+    <keys>
+        <import base="cldr" path="45/keys-Latn-implied.xml"/>
+        <key id="c-dot" output="ċ"/>
+        <key id="C-dot" output="Ċ"/>
+        <key id="euro" output="${euro}"/>
+        <key id="acute" output="\m{acute}"/>
+        <key id="hmaqaf" output="\u{2010}"/>
+        <key id="enter" output="\u{d}"/>
+        <key id="shift" layerId="shift"/>
+    </keys>
+    <layers formId="touch">
+        <layer id="base">
+            <row keys="c-dot"/>
+        </layer>
+    </layers>
+    <layers formId="iso">
+        <layer modifiers="none">
+            <row keys="c-dot 1 2 3 4 5 6 7 8 9 0 hyphen equal"/>
+            <row keys="q w e r t y u i o p open-square close-square"/>
+            <row keys="a s d f g h j k l semi-colon acute enter"/>
+            <row keys="backslash z x c v b n m comma period slash"/>
+            <row keys="space"/>
+        </layer>
+        <layer modifiers="shift">
+            <row keys="C-dot bang"/>
+            <row keys="Q W E"/>
+            <row keys="A"/>
+        </layer>
+        <layer modifiers="altR, ctrl alt">
+            <row keys="grave hmaqaf"/>
+            <row keys="q w euro"/>
+        </layer>
+        <layer modifiers="caps">
+            <row keys="C-dot"/>
+        </layer>
+    </layers>
+    <variables>
+        <string id="euro" value="\u{20ac}"/>
+        <set id="vowel" value="a e A E"/>
+        <set id="acuteVowel" value="á é Á É"/>
+    </variables>
+    <transforms type="simple">
+        <transformGroup>
+            <transform from="\m{acute}($[vowel])" to="$[1:acuteVowel]"/>
+            <transform from="\m{acute}c" to="ć"/>
+            <transform from="q(.)" to="x"/>
+        </transformGroup>
+    </transforms>
+    <transforms type="backspace">
+        <transformGroup>
+            <transform from="\m{acute}" to=""/>
+        </transformGroup>
+    </transforms>
+</keyboard3>
+"#;
+
+#[test]
+fn test_parse_cldr_metadata() {
+    let layout_map = parse_cldr_keyboard(SAMPLE).unwrap();
+    assert_eq!(layout_map.layout.id, "mt(qwerty-iso)");
+    assert_eq!(layout_map.layout.variant.as_deref(), Some("qwerty-iso"));
+    assert_eq!(layout_map.layout.lang_name, "Maltese Test");
+    assert_eq!(layout_map.layout.language.as_deref(), Some("mt"));
+    assert_eq!(layout_map.layout.direction, KeyboardDirection::LTR);
+}
+
+#[test]
+fn test_layout_variant() {
+    assert_eq!(layout_variant(Some("QWERTY"), "iso"), "qwerty-iso");
+    assert_eq!(layout_variant(Some("Dvorak Left"), "us"), "dvorak-left-us");
+    assert_eq!(layout_variant(None, "jis"), "jis");
+    assert_eq!(layout_variant(Some(" "), "us"), "us");
+}
+
+#[test]
+fn test_locale_script() {
+    assert_eq!(locale_script("sr-Cyrl").as_deref(), Some("Cyrl"));
+    assert_eq!(locale_script("az-latn-AZ").as_deref(), Some("Latn"));
+    assert_eq!(locale_script("he").as_deref(), Some("Hebr"));
+    assert_eq!(locale_script("mt-t-k0-47key"), None);
+}
+
+#[test]
+fn test_parse_cldr_layers_follow_the_form() {
+    let layout_map = parse_cldr_keyboard(SAMPLE).unwrap();
+    let output = |key, level| layout_map.output(key, level);
+    assert_eq!(output(PhysicalKey::Backquote, ShiftLevel::Base), Some("ċ"));
+    assert_eq!(output(PhysicalKey::Backquote, ShiftLevel::Shift), Some("Ċ"));
+    assert_eq!(output(PhysicalKey::Digit1, ShiftLevel::Shift), Some("!"));
+    assert_eq!(output(PhysicalKey::KeyQ, ShiftLevel::Base), Some("q"));
+    assert_eq!(output(PhysicalKey::KeyE, ShiftLevel::Shift), Some("E"));
+    // The iso form puts the extra key left of Z.
+    assert_eq!(
+        output(PhysicalKey::IntlBackslash, ShiftLevel::Base),
+        Some("\\")
+    );
+    assert_eq!(output(PhysicalKey::KeyZ, ShiftLevel::Base), Some("z"));
+    assert_eq!(output(PhysicalKey::Space, ShiftLevel::Base), Some(" "));
+    // AltGr, with escapes and string variables resolved; keys equal to the
+    // base level are not repeated.
+    assert_eq!(output(PhysicalKey::Backquote, ShiftLevel::AltGr), Some("`"));
+    assert_eq!(
+        output(PhysicalKey::Digit1, ShiftLevel::AltGr),
+        Some("\u{2010}")
+    );
+    assert_eq!(output(PhysicalKey::KeyE, ShiftLevel::AltGr), Some("€"));
+    assert_eq!(output(PhysicalKey::KeyQ, ShiftLevel::AltGr), None);
+    // Control characters, layer switches and the touch layout are left out.
+    assert_eq!(output(PhysicalKey::Backslash, ShiftLevel::Base), None);
+    assert_eq!(layout_map.map.values().filter(|s| *s == "Ċ").count(), 1);
+}
+
+#[test]
+fn test_parse_cldr_transforms() {
+    let layout_map = parse_cldr_keyboard(SAMPLE).unwrap();
+    let dead = KeyStroke::base(PhysicalKey::Quote);
+    assert_eq!(layout_map.map.get(&dead), None);
+    let sequence = |stroke| {
+        layout_map
+            .sequences
+            .get(&vec![dead, stroke])
+            .map(String::as_str)
+    };
+    assert_eq!(sequence(KeyStroke::base(PhysicalKey::KeyA)), Some("á"));
+    assert_eq!(
+        sequence(KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Shift)),
+        Some("É")
+    );
+    assert_eq!(sequence(KeyStroke::base(PhysicalKey::KeyC)), Some("ć"));
+    // `q(.)` is a regular expression and is skipped; backspace transforms
+    // are ignored.
+    assert_eq!(layout_map.sequences.len(), 5);
+}
+
+#[test]
+fn test_layer_level() {
+    assert_eq!(layer_level("none"), Some(ShiftLevel::Base));
+    assert_eq!(layer_level("shift"), Some(ShiftLevel::Shift));
+    assert_eq!(layer_level("altR"), Some(ShiftLevel::AltGr));
+    assert_eq!(layer_level("ctrl alt shift"), Some(ShiftLevel::ShiftAltGr));
+    assert_eq!(layer_level("caps, shift"), Some(ShiftLevel::Shift));
+    assert_eq!(layer_level("caps"), None);
+    assert_eq!(layer_level("ctrl"), None);
+}
+
+#[test]
+fn test_parse_cldr_errors() {
+    assert!(
+        parse_cldr_keyboard("<keyboard3>")
+            .unwrap_err()
+            .starts_with("Invalid CLDR keyboard XML")
+    );
+    let unknown_form = SAMPLE.replace("formId=\"iso\"", "formId=\"dvorak\"");
+    assert_eq!(
+        parse_cldr_keyboard(&unknown_form).unwrap_err(),
+        "Unsupported form: dvorak"
+    );
+    let touch_only = SAMPLE.replace("formId=\"iso\"", "formId=\"touch\"");
+    assert_eq!(
+        parse_cldr_keyboard(&touch_only).unwrap_err(),
+        "No hardware layers"
+    );
+}
@@ -0,0 +1,334 @@
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::remap::is_rtl_char;
+use crate::keyboard_mapping::types::{
+    KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, ShiftLevel, script_for_language,
+};
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Base keymaps can chain; real layouts use one level.
+const MAX_BASE_DEPTH: usize = 8;
+
+// Script codes of `<keyboard group>` that tell the language. Roman (0) and
+// Unicode (126), the group of most custom layouts, don't.
+const GROUP_LANGUAGES: &[(&str, &str)] = &[("4", "ar"), ("5", "he"), ("6", "el"), ("7", "ru")];
+
+#[derive(Debug, Deserialize)]
+struct Keyboard {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "@group")]
+    group: Option<String>,
+    layouts: Layouts,
+    #[serde(rename = "modifierMap", default)]
+    modifier_maps: Vec<ModifierMap>,
+    #[serde(rename = "keyMapSet", default)]
+    key_map_sets: Vec<KeyMapSet>,
+    #[serde(default)]
+    actions: Actions,
+}
+
+#[derive(Debug, Deserialize)]
+struct Layouts {
+    #[serde(rename = "layout", default)]
+    layouts: Vec<LayoutRef>,
+}
+
+/// Which modifier map and key map set apply to a range of hardware
+/// keyboard types.
+#[derive(Debug, Deserialize)]
+struct LayoutRef {
+    #[serde(rename = "@first", default)]
+    first: u32,
+    #[serde(rename = "@modifiers")]
+    modifiers: String,
+    #[serde(rename = "@mapSet")]
+    map_set: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModifierMap {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@defaultIndex", default)]
+    default_index: u32,
+    #[serde(rename = "keyMapSelect", default)]
+    selects: Vec<KeyMapSelect>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyMapSelect {
+    #[serde(rename = "@mapIndex")]
+    map_index: u32,
+    #[serde(rename = "modifier", default)]
+    modifiers: Vec<Modifier>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Modifier {
+    #[serde(rename = "@keys", default)]
+    keys: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyMapSet {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "keyMap", default)]
+    key_maps: Vec<KeyMap>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyMap {
+    #[serde(rename = "@index")]
+    index: u32,
+    #[serde(rename = "@baseMapSet")]
+    base_map_set: Option<String>,
+    #[serde(rename = "@baseIndex")]
+    base_index: Option<u32>,
+    #[serde(rename = "key", default)]
+    keys: Vec<Key>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Key {
+    #[serde(rename = "@code")]
+    code: u16,
+    #[serde(rename = "@output")]
+    output: Option<String>,
+    #[serde(rename = "@action")]
+    action: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Actions {
+    #[serde(rename = "action", default)]
+    actions: Vec<Action>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Action {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "when", default)]
+    whens: Vec<When>,
+}
+
+/// What an action does in a dead-key state: type `output`, or enter the
+/// state `next`.
+#[derive(Debug, Deserialize)]
+struct When {
+    #[serde(rename = "@state")]
+    state: String,
+    #[serde(rename = "@output")]
+    output: Option<String>,
+    #[serde(rename = "@next")]
+    next: Option<String>,
+}
+
+/// Whether a `modifier keys="..."` pattern matches the modifiers held for
+/// `level`, with Option standing in for AltGr. Keys marked `?` may be
+/// either up or down; Command, Control and Caps Lock are never held.
+fn modifiers_match(keys: &str, level: ShiftLevel) -> bool {
+    let mut shift_allowed = false;
+    let mut option_allowed = false;
+    for token in keys.split_whitespace() {
+        let (name, optional) = match token.strip_suffix('?') {
+            Some(name) => (name, true),
+            None => (token, false),
+        };
+        let held = match name {
+            "shift" | "anyShift" | "rightShift" => {
+                shift_allowed = true;
+                level.has_shift()
+            }
+            "option" | "anyOption" | "rightOption" => {
+                option_allowed = true;
+                level.has_altgr()
+            }
+            _ => false,
+        };
+        if !held && !optional {
+            return false;
+        }
+    }
+    (shift_allowed || !level.has_shift()) && (option_allowed || !level.has_altgr())
+}
+
+impl ModifierMap {
+    /// The key map used for `level`: the first select matching it, or the
+    /// default one for the base level.
+    fn map_index(&self, level: ShiftLevel) -> Option<u32> {
+        self.selects
+            .iter()
+            .find(|select| {
+                select
+                    .modifiers
+                    .iter()
+                    .any(|m| modifiers_match(&m.keys, level))
+            })
+            .map(|select| select.map_index)
+            .or_else(|| (level == ShiftLevel::Base).then_some(self.default_index))
+    }
+}
+
+impl Keyboard {
+    /// Looks a key up in a key map, falling back to the key map it is
+    /// based on.
+    fn key(&self, map_set: &str, index: u32, code: u16) -> Option<&Key> {
+        let mut map_set = map_set;
+        let mut index = index;
+        for _ in 0..MAX_BASE_DEPTH {
+            let key_map = self
+                .key_map_sets
+                .iter()
+                .find(|set| set.id == map_set)?
+                .key_maps
+                .iter()
+                .find(|key_map| key_map.index == index)?;
+            if let Some(key) = key_map.keys.iter().find(|key| key.code == code) {
+                return Some(key);
+            }
+            map_set = key_map.base_map_set.as_deref()?;
+            index = key_map.base_index?;
+        }
+        None
+    }
+}
+
+impl Action {
+    fn when(&self, state: &str) -> Option<&When> {
+        self.whens.iter().find(|when| when.state == state)
+    }
+}
+
+/// Parses a macOS `.keylayout` file as written by Ukelele. Option is read
+/// as AltGr; layouts only reachable with Command, Control or Caps Lock are
+/// left out. The layout id and name are the keyboard name.
+pub fn parse_keylayout(xml: &str) -> Result<LayoutMap, String> {
+    let keyboard: Keyboard = from_str(xml).map_err(|e| format!("Invalid keylayout XML: {}", e))?;
+    let layouts = &keyboard.layouts.layouts;
+    let layout_ref = layouts
+        .iter()
+        .find(|layout| layout.first == 0)
+        .or_else(|| layouts.first())
+        .ok_or("No layout in the layouts element")?;
+    let modifier_map = keyboard
+        .modifier_maps
+        .iter()
+        .find(|map| map.id == layout_ref.modifiers)
+        .ok_or_else(|| format!("Unknown modifierMap: {}", layout_ref.modifiers))?;
+    if !keyboard
+        .key_map_sets
+        .iter()
+        .any(|set| set.id == layout_ref.map_set)
+    {
+        return Err(format!("Unknown keyMapSet: {}", layout_ref.map_set));
+    }
+    let actions: HashMap<&str, &Action> = keyboard
+        .actions
+        .actions
+        .iter()
+        .map(|action| (action.id.as_str(), action))
+        .collect();
+
+    let mut map: HashMap<KeyStroke, String> = HashMap::new();
+    let mut dead_keys: Vec<(KeyStroke, &str)> = Vec::new();
+    let mut live_actions: Vec<(KeyStroke, &Action)> = Vec::new();
+    for level in ShiftLevel::ALL {
+        let Some(index) = modifier_map.map_index(level) else {
+            continue;
+        };
+        for key in PhysicalKey::all() {
+            let Some(entry) = key
+                .mac_keycode()
+                .and_then(|code| keyboard.key(&layout_ref.map_set, index, code))
+            else {
+                continue;
+            };
+            let stroke = KeyStroke::new(key, level);
+            let action = entry.action.as_deref().and_then(|id| actions.get(id));
+            let output = match (&entry.output, action) {
+                (Some(output), _) => output.clone(),
+                (None, Some(action)) => match action.when("none") {
+                    Some(When {
+                        next: Some(next), ..
+                    }) => {
+                        if level == ShiftLevel::Base
+                            || !dead_keys.contains(&(KeyStroke::base(key), next.as_str()))
+                        {
+                            dead_keys.push((stroke, next));
+                        }
+                        continue;
+                    }
+                    Some(When {
+                        output: Some(output),
+                        ..
+                    }) => output.clone(),
+                    _ => continue,
+                },
+                (None, None) => continue,
+            };
+            if output.is_empty() || output.chars().any(char::is_control) {
+                continue;
+            }
+            if level != ShiftLevel::Base && map.get(&KeyStroke::base(key)) == Some(&output) {
+                continue;
+            }
+            if map.contains_key(&stroke) {
+                continue;
+            }
+            map.insert(stroke, output);
+            if let Some(action) = action {
+                live_actions.push((stroke, action));
+            }
+        }
+    }
+
+    let mut sequences = HashMap::new();
+    for (dead_stroke, state) in &dead_keys {
+        for (live_stroke, action) in &live_actions {
+            if let Some(output) = action
+                .when(state)
+                .and_then(|when| when.output.as_ref())
+                .filter(|output| !output.is_empty())
+            {
+                sequences.insert(vec![*dead_stroke, *live_stroke], output.clone());
+            }
+        }
+    }
+
+    let direction = if map.values().flat_map(|s| s.chars()).any(is_rtl_char) {
+        KeyboardDirection::RTL
+    } else {
+        KeyboardDirection::LTR
+    };
+    let mut layout = KeyboardLayout {
+        id: keyboard.name.clone(),
+        lang_name: keyboard.name,
+        direction,
+        variant: None,
+        language: None,
+        script: None,
+    };
+    // The files carry no language; the script group or the name may tell.
+    layout.language = keyboard
+        .group
+        .as_deref()
+        .and_then(|group| GROUP_LANGUAGES.iter().find(|(g, _)| *g == group.trim()))
+        .map(|(_, language)| *language)
+        .or_else(|| layout.language_code())
+        .map(str::to_string);
+    layout.script = layout
+        .language
+        .as_deref()
+        .and_then(script_for_language)
+        .map(str::to_string);
+    let mut layout_map = LayoutMap::new(layout, map);
+    layout_map.sequences = sequences;
+    Ok(layout_map)
+}
+
+#[cfg(test)]
+mod tests;
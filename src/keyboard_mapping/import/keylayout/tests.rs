@@ -0,0 +1,186 @@
+use super::*;
+
+const SAMPLE: &str = r#"<?xml version="1.1" encoding="UTF-8"?>
+<!DOCTYPE keyboard SYSTEM "file://localhost/System/Library/DTDs/KeyboardLayout.dtd">
+<!--Created by Ukelele version 3.5.7 on 2024-03-01 at 10:00 (GMT+2)-->
+<keyboard group="126" id="-4242" name="Hebrew Test" maxout="2">
+    <layouts>
+        <layout first="0" last="17" mapSet="16c" modifiers="f4"/>
+        <layout first="18" last="18" mapSet="a50" modifiers="f4"/>
+    </layouts>
+    <modifierMap id="f4" defaultIndex="0">
+        <keyMapSelect mapIndex="0">
+            <modifier keys=""/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="1">
+            <modifier keys="anyShift caps?"/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="2">
+            <modifier keys="caps"/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="3">
+            <modifier keys="anyOption"/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="4">
+            <modifier keys="anyShift caps? anyOption"/>
+        </keyMapSelect>
+        <keyMapSelect mapIndex="5">
+            <modifier keys="anyShift? caps? anyOption? command"/>
+        </keyMapSelect>
+    </modifierMap>
+    <keyMapSet id="16c">
+        <keyMap index="0">
+            <key code="0" output="ש"/>
+            <key code="10" output="&#x003B;"/>
+            <key code="12" output="/"/>
+            <key code="14" output="ק"/>
+            <key code="18" output="1"/>
+            <key code="36" output="&#x000D;"/>
+            <key code="39" action="acute"/>
+            <key code="49" output=" "/>
+            <key code="94" output="ろ"/>
+        </keyMap>
+        <keyMap index="1">
+            <key code="0" output="A"/>
+            <key code="12" output="Q"/>
+            <key code="14" action="E"/>
+            <key code="18" output="!"/>
+            <key code="39" output="&quot;"/>
+            <key code="49" output=" "/>
+        </keyMap>
+        <keyMap index="2" baseMapSet="16c" baseIndex="0">
+            <key code="0" output="ש"/>
+        </keyMap>
+        <keyMap index="3" baseMapSet="16c" baseIndex="0">
+            <key code="14" output="€"/>
+        </keyMap>
+        <keyMap index="4">
+            <key code="14" output="ק"/>
+        </keyMap>
+        <keyMap index="5">
+            <key code="0" output="a"/>
+        </keyMap>
+    </keyMapSet>
+    <keyMapSet id="a50">
+        <keyMap index="0">
+            <key code="0" output="x"/>
+        </keyMap>
+    </keyMapSet>
+    <actions>
+        <action id="acute">
+            <when state="none" next="acute"/>
+        </action>
+        <action id="E">
+            <when state="none" output="E"/>
+            <when state="acute" output="É"/>
+        </action>
+    </actions>
+    <terminators>
+        <when state="acute" output="´"/>
+    </terminators>
+</keyboard>
+"#;
+
+#[test]
+fn test_parse_keylayout_metadata() {
+    let layout_map = parse_keylayout(SAMPLE).unwrap();
+    assert_eq!(layout_map.layout.id, "Hebrew Test");
+    assert_eq!(layout_map.layout.lang_name, "Hebrew Test");
+    assert_eq!(layout_map.layout.direction, KeyboardDirection::RTL);
+    assert_eq!(layout_map.layout.language.as_deref(), Some("he"));
+    assert_eq!(layout_map.layout.script.as_deref(), Some("Hebr"));
+}
+
+#[test]
+fn test_parse_keylayout_language_from_group() {
+    let sample = SAMPLE.replace(
+        r#"group="126" id="-4242" name="Hebrew Test""#,
+        r#"group="7" id="-4242" name="Phonetic""#,
+    );
+    let layout_map = parse_keylayout(&sample).unwrap();
+    assert_eq!(layout_map.layout.language.as_deref(), Some("ru"));
+
+    let unknown = SAMPLE.replace("Hebrew Test", "Phonetic");
+    assert_eq!(parse_keylayout(&unknown).unwrap().layout.language, None);
+}
+
+#[test]
+fn test_parse_keylayout_modifier_maps() {
+    let layout_map = parse_keylayout(SAMPLE).unwrap();
+    let output = |key, level| layout_map.output(key, level);
+    assert_eq!(output(PhysicalKey::KeyA, ShiftLevel::Base), Some("ש"));
+    assert_eq!(output(PhysicalKey::KeyA, ShiftLevel::Shift), Some("A"));
+    assert_eq!(output(PhysicalKey::KeyQ, ShiftLevel::Shift), Some("Q"));
+    assert_eq!(output(PhysicalKey::Digit1, ShiftLevel::Shift), Some("!"));
+    assert_eq!(
+        output(PhysicalKey::IntlBackslash, ShiftLevel::Base),
+        Some(";")
+    );
+    assert_eq!(output(PhysicalKey::IntlRo, ShiftLevel::Base), Some("ろ"));
+    assert_eq!(output(PhysicalKey::Quote, ShiftLevel::Shift), Some("\""));
+    // Option maps to AltGr; its key map inherits the rest from the base one,
+    // which is not repeated at the higher level.
+    assert_eq!(output(PhysicalKey::KeyE, ShiftLevel::AltGr), Some("€"));
+    assert_eq!(output(PhysicalKey::KeyA, ShiftLevel::AltGr), None);
+    assert_eq!(output(PhysicalKey::KeyE, ShiftLevel::ShiftAltGr), None);
+    // Control characters and Command-only maps are left out.
+    assert_eq!(output(PhysicalKey::Enter, ShiftLevel::Base), None);
+    assert!(!layout_map.map.values().any(|s| s == "a"));
+}
+
+#[test]
+fn test_parse_keylayout_dead_keys() {
+    let layout_map = parse_keylayout(SAMPLE).unwrap();
+    let dead = KeyStroke::base(PhysicalKey::Quote);
+    assert_eq!(layout_map.map.get(&dead), None);
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyE, ShiftLevel::Shift),
+        Some("E")
+    );
+    assert_eq!(
+        layout_map
+            .sequences
+            .get(&vec![
+                dead,
+                KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Shift)
+            ])
+            .map(String::as_str),
+        Some("É")
+    );
+    // Option+' falls back to the same dead key and is not listed again.
+    assert_eq!(layout_map.sequences.len(), 1);
+}
+
+#[test]
+fn test_modifiers_match() {
+    assert!(modifiers_match("", ShiftLevel::Base));
+    assert!(!modifiers_match("", ShiftLevel::Shift));
+    assert!(modifiers_match("anyShift caps?", ShiftLevel::Shift));
+    assert!(!modifiers_match("anyShift caps?", ShiftLevel::Base));
+    assert!(modifiers_match("anyShift? anyOption", ShiftLevel::AltGr));
+    assert!(modifiers_match(
+        "anyShift? anyOption",
+        ShiftLevel::ShiftAltGr
+    ));
+    assert!(!modifiers_match("caps", ShiftLevel::Base));
+    assert!(!modifiers_match("command anyShift?", ShiftLevel::Shift));
+}
+
+#[test]
+fn test_parse_keylayout_errors() {
+    assert!(
+        parse_keylayout("<keyboard>")
+            .unwrap_err()
+            .starts_with("Invalid keylayout XML")
+    );
+    let missing_map = SAMPLE.replace("mapSet=\"16c\" modifiers", "mapSet=\"zzz\" modifiers");
+    assert_eq!(
+        parse_keylayout(&missing_map).unwrap_err(),
+        "Unknown keyMapSet: zzz"
+    );
+    let missing_modifiers = SAMPLE.replace("modifierMap id=\"f4\"", "modifierMap id=\"f5\"");
+    assert_eq!(
+        parse_keylayout(&missing_modifiers).unwrap_err(),
+        "Unknown modifierMap: f4"
+    );
+}
@@ -0,0 +1,265 @@
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::remap::is_rtl_char;
+use crate::keyboard_mapping::types::{
+    KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, ShiftLevel, script_for_language,
+};
+use std::collections::HashMap;
+
+// Keywords starting a section of a .klc file.
+const SECTIONS: &[&str] = &[
+    "KBD",
+    "COPYRIGHT",
+    "COMPANY",
+    "LOCALENAME",
+    "LOCALEID",
+    "VERSION",
+    "ATTRIBUTES",
+    "SHIFTSTATE",
+    "LAYOUT",
+    "LIGATURE",
+    "DEADKEY",
+    "KEYNAME",
+    "KEYNAME_EXT",
+    "KEYNAME_DEAD",
+    "DESCRIPTIONS",
+    "LANGUAGENAMES",
+    "ENDKBD",
+];
+
+/// What a key types at one shift state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum KlcOutput {
+    Text(String),
+    Dead(char),
+    /// `%%`: the output is listed in the LIGATURE section.
+    Ligature,
+}
+
+/// The shift level of a SHIFTSTATE column. Ctrl-only states type control
+/// characters and are skipped.
+fn shift_level(state: u8) -> Option<ShiftLevel> {
+    match state {
+        0 => Some(ShiftLevel::Base),
+        1 => Some(ShiftLevel::Shift),
+        6 => Some(ShiftLevel::AltGr),
+        7 => Some(ShiftLevel::ShiftAltGr),
+        _ => None,
+    }
+}
+
+/// A character is written either as itself (`q`, `1`) or as four or more
+/// hex digits (`05e9`).
+fn parse_char(token: &str) -> Result<char, String> {
+    let mut chars = token.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(c);
+    }
+    u32::from_str_radix(token, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| format!("Invalid character: {}", token))
+}
+
+fn parse_output(token: &str) -> Result<Option<KlcOutput>, String> {
+    match token {
+        "-1" => Ok(None),
+        "%%" => Ok(Some(KlcOutput::Ligature)),
+        _ => match token.strip_suffix('@') {
+            Some(dead) => parse_char(dead).map(|c| Some(KlcOutput::Dead(c))),
+            None => parse_char(token).map(|c| Some(KlcOutput::Text(c.to_string()))),
+        },
+    }
+}
+
+fn quoted(line: &str) -> Option<&str> {
+    let start = line.find('"')?;
+    let rest = &line[start + 1..];
+    rest.find('"').map(|end| &rest[..end])
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split("//").next().unwrap_or("").trim()
+}
+
+/// Parses a Microsoft Keyboard Layout Creator source file. The layout id is
+/// the KBD name, its name the first DESCRIPTIONS entry.
+pub fn parse_klc(text: &str) -> Result<LayoutMap, String> {
+    let mut section = "";
+    let mut id = None;
+    let mut kbd_description = None;
+    let mut description = None;
+    let mut locale_name = None;
+    let mut columns: Vec<Option<ShiftLevel>> = Vec::new();
+    let mut keys: Vec<(PhysicalKey, String, Vec<Option<KlcOutput>>)> = Vec::new();
+    let mut ligatures: HashMap<(String, usize), String> = HashMap::new();
+    let mut dead_tables: HashMap<char, Vec<(char, char)>> = HashMap::new();
+    let mut dead_key = None;
+
+    for (number, raw) in text.lines().enumerate() {
+        let line = strip_comment(raw);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(first) = tokens.first().copied() else {
+            continue;
+        };
+        let at_line = |e: String| format!("{} (line {})", e, number + 1);
+        if SECTIONS.contains(&first) {
+            section = first;
+            match first {
+                "KBD" => {
+                    id = tokens.get(1).map(|name| name.to_string());
+                    kbd_description = quoted(line).map(str::to_string);
+                }
+                "LOCALENAME" => locale_name = quoted(line).map(str::to_string),
+                "DEADKEY" => {
+                    let c = tokens
+                        .get(1)
+                        .ok_or_else(|| at_line("DEADKEY without a character".to_string()))
+                        .and_then(|t| parse_char(t).map_err(at_line))?;
+                    dead_key = Some(c);
+                    dead_tables.entry(c).or_default();
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match section {
+            "SHIFTSTATE" => {
+                let state: u8 = first
+                    .parse()
+                    .map_err(|_| at_line(format!("Invalid shift state: {}", first)))?;
+                columns.push(shift_level(state));
+            }
+            "LAYOUT" => {
+                // SGCAPS keys continue on a line starting with -1.
+                if first == "-1" {
+                    continue;
+                }
+                if tokens.len() < 3 {
+                    return Err(at_line(format!("Incomplete LAYOUT line: {}", line)));
+                }
+                let scan_code = u16::from_str_radix(first, 16)
+                    .map_err(|_| at_line(format!("Invalid scan code: {}", first)))?;
+                let Some(key) = PhysicalKey::from_scan_code(scan_code) else {
+                    continue;
+                };
+                let outputs = tokens[3..]
+                    .iter()
+                    .map(|token| parse_output(token))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(at_line)?;
+                keys.push((key, tokens[1].to_string(), outputs));
+            }
+            "LIGATURE" => {
+                if tokens.len() < 3 {
+                    return Err(at_line(format!("Incomplete LIGATURE line: {}", line)));
+                }
+                let column: usize = tokens[1]
+                    .parse()
+                    .map_err(|_| at_line(format!("Invalid LIGATURE column: {}", tokens[1])))?;
+                let output = tokens[2..]
+                    .iter()
+                    .map(|token| parse_char(token))
+                    .collect::<Result<String, _>>()
+                    .map_err(at_line)?;
+                ligatures.insert((tokens[0].to_string(), column), output);
+            }
+            "DEADKEY" => {
+                let (Some(dead), Some(result)) = (dead_key, tokens.get(1)) else {
+                    continue;
+                };
+                let base = parse_char(first).map_err(at_line)?;
+                let result = parse_char(result).map_err(at_line)?;
+                dead_tables.entry(dead).or_default().push((base, result));
+            }
+            "DESCRIPTIONS" if description.is_none() => {
+                description = line
+                    .split_once(char::is_whitespace)
+                    .map(|(_, text)| text.trim().to_string())
+                    .filter(|text| !text.is_empty());
+            }
+            _ => {}
+        }
+    }
+
+    let id = id.ok_or("Missing KBD line")?;
+    if keys.is_empty() {
+        return Err("No keys in the LAYOUT section".to_string());
+    }
+    if columns.is_empty() {
+        columns = vec![Some(ShiftLevel::Base), Some(ShiftLevel::Shift)];
+    }
+
+    let mut map: HashMap<KeyStroke, String> = HashMap::new();
+    let mut dead_keys: Vec<(KeyStroke, char)> = Vec::new();
+    for (key, vk, outputs) in &keys {
+        for (column, output) in outputs.iter().enumerate() {
+            let (Some(Some(level)), Some(output)) = (columns.get(column), output) else {
+                continue;
+            };
+            let stroke = KeyStroke::new(*key, *level);
+            let text = match output {
+                KlcOutput::Text(text) => text.clone(),
+                KlcOutput::Dead(c) => {
+                    dead_keys.push((stroke, *c));
+                    continue;
+                }
+                KlcOutput::Ligature => match ligatures.get(&(vk.clone(), column)) {
+                    Some(text) => text.clone(),
+                    None => continue,
+                },
+            };
+            if !text.chars().any(char::is_control) {
+                map.entry(stroke).or_insert(text);
+            }
+        }
+    }
+    // Like the other backends, drop levels repeating the base output, e.g.
+    // Shift+Space, so the base keystroke is the only way to type it.
+    let repeats: Vec<KeyStroke> = map
+        .iter()
+        .filter(|(stroke, output)| {
+            stroke.level != ShiftLevel::Base
+                && map.get(&KeyStroke::base(stroke.key)) == Some(output)
+        })
+        .map(|(stroke, _)| *stroke)
+        .collect();
+    for stroke in repeats {
+        map.remove(&stroke);
+    }
+
+    let mut sequences = HashMap::new();
+    for (dead_stroke, dead) in &dead_keys {
+        for (base, result) in dead_tables.get(dead).into_iter().flatten() {
+            let base = base.to_string();
+            for (live_stroke, _) in map.iter().filter(|(_, output)| **output == base) {
+                sequences.insert(vec![*dead_stroke, *live_stroke], result.to_string());
+            }
+        }
+    }
+
+    let direction = if map.values().flat_map(|s| s.chars()).any(is_rtl_char) {
+        KeyboardDirection::RTL
+    } else {
+        KeyboardDirection::LTR
+    };
+    let language = locale_name.filter(|name| !name.is_empty());
+    let layout = KeyboardLayout {
+        lang_name: description
+            .or(kbd_description)
+            .unwrap_or_else(|| id.clone()),
+        id,
+        direction,
+        variant: None,
+        script: language
+            .as_deref()
+            .and_then(script_for_language)
+            .map(str::to_string),
+        language,
+    };
+    let mut layout_map = LayoutMap::new(layout, map);
+    layout_map.sequences = sequences;
+    Ok(layout_map)
+}
+
+#[cfg(test)]
+mod tests;
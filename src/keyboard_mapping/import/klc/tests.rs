@@ -0,0 +1,205 @@
+use super::*;
+
+const SAMPLE: &str = "\
+KBD\thebtest\t\"Hebrew Test\"
+
+COPYRIGHT\t\"(c) 2024\"
+
+COMPANY\t\"Example\"
+
+LOCALENAME\t\"he-IL\"
+
+LOCALEID\t\"0000040d\"
+
+VERSION\t1.0
+
+SHIFTSTATE
+
+0\t//Column 4
+1\t//Column 5 : Shft
+2\t//Column 6 :       Ctrl
+6\t//Column 7 :       Ctrl Alt
+7\t//Column 8 : Shft  Ctrl Alt
+
+LAYOUT\t\t;an extra '@' at the end is a dead key
+
+//SC\tVK_\t\tCap\t0\t1\t2\t6\t7
+//--\t----\t\t----\t----\t----\t----\t----\t----
+
+02\t1\t\t0\t1\t0021\t-1\t-1\t-1\t\t// DIGIT ONE, EXCLAMATION MARK
+10\tQ\t\t0\t002f\tQ\t-1\t-1\t-1\t\t// SOLIDUS, LATIN CAPITAL LETTER Q
+12\tE\t\t0\t05e7\tE\t-1\t20ac\t-1\t\t// HEBREW LETTER QOF, E, <none>, EURO SIGN
+1e\tA\t\t0\t05e9\tA\t-1\t-1\t-1\t\t// HEBREW LETTER SHIN, A
+1a\tOEM_4\t\t0\t%%\t007b\t001b\t-1\t-1\t\t// <Ligature>, {, ESC
+28\tOEM_7\t\t0\t0027@\t0022\t-1\t-1\t-1\t\t// APOSTROPHE (dead), QUOTATION MARK
+2b\tOEM_5\t\tSGCap\t005c\t007c\t001c\t-1\t-1\t\t// \\, |, FS
+-1\t-1\t\t0\t05b0\t05b1\t-1\t-1\t-1
+39\tSPACE\t\t0\t0020\t0020\t0020\t-1\t-1
+56\tOEM_102\t\t0\t005c\t007c\t001c\t-1\t-1
+53\tDECIMAL\t\t0\t002e\t002e\t-1\t-1\t-1
+
+LIGATURE
+
+//VK_\tMod#\tChar0\tChar1
+//----\t\t----\t----\t----
+
+OEM_4\t0\t05b0\t05b8\t\t// HEBREW POINT SHEVA + QAMATS
+
+DEADKEY\t0027
+
+0045\t00c9\t// E -> É
+0020\t0027\t// ' ' -> '
+0061\t00e1\t// a -> á
+
+KEYNAME
+
+01\tEsc
+0e\tBackspace
+3a\t\"Caps Lock\"
+
+DESCRIPTIONS
+
+0409\tHebrew Test Phonetic
+
+LANGUAGENAMES
+
+0409\tHebrew (Israel)
+
+ENDKBD
+";
+
+#[test]
+fn test_parse_klc_metadata() {
+    let layout_map = parse_klc(SAMPLE).unwrap();
+    let layout = &layout_map.layout;
+    assert_eq!(layout.id, "hebtest");
+    assert_eq!(layout.lang_name, "Hebrew Test Phonetic");
+    assert_eq!(layout.language.as_deref(), Some("he-IL"));
+    assert_eq!(layout.script.as_deref(), Some("Hebr"));
+    assert_eq!(layout.direction, KeyboardDirection::RTL);
+    assert_eq!(layout.language_code(), Some("he"));
+}
+
+#[test]
+fn test_parse_klc_shift_states() {
+    let layout_map = parse_klc(SAMPLE).unwrap();
+    assert_eq!(
+        layout_map.output(PhysicalKey::Digit1, ShiftLevel::Base),
+        Some("1")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::Digit1, ShiftLevel::Shift),
+        Some("!")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyE, ShiftLevel::Base),
+        Some("ק")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyE, ShiftLevel::AltGr),
+        Some("€")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyE, ShiftLevel::ShiftAltGr),
+        None
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::Space, ShiftLevel::Base),
+        Some(" ")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::IntlBackslash, ShiftLevel::Shift),
+        Some("|")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::NumpadDecimal, ShiftLevel::Base),
+        Some(".")
+    );
+    // Levels repeating the base output are left out.
+    assert_eq!(
+        layout_map.output(PhysicalKey::Space, ShiftLevel::Shift),
+        None
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::NumpadDecimal, ShiftLevel::Shift),
+        None
+    );
+    // The Ctrl column only holds control characters.
+    assert!(
+        layout_map
+            .map
+            .values()
+            .all(|output| !output.chars().any(char::is_control))
+    );
+}
+
+#[test]
+fn test_parse_klc_ligatures_and_sgcaps() {
+    let layout_map = parse_klc(SAMPLE).unwrap();
+    assert_eq!(
+        layout_map.output(PhysicalKey::BracketLeft, ShiftLevel::Base),
+        Some("\u{05b0}\u{05b8}")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::Backslash, ShiftLevel::Base),
+        Some("\\")
+    );
+}
+
+#[test]
+fn test_parse_klc_dead_keys() {
+    let layout_map = parse_klc(SAMPLE).unwrap();
+    let dead = KeyStroke::base(PhysicalKey::Quote);
+    assert_eq!(layout_map.map.get(&dead), None);
+    assert_eq!(
+        layout_map
+            .sequences
+            .get(&vec![
+                dead,
+                KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Shift)
+            ])
+            .map(String::as_str),
+        Some("É")
+    );
+    assert_eq!(
+        layout_map
+            .sequences
+            .get(&vec![dead, KeyStroke::base(PhysicalKey::Space)])
+            .map(String::as_str),
+        Some("'")
+    );
+    // No key types "a", so "á" has no sequence.
+    assert!(!layout_map.sequences.values().any(|s| s == "á"));
+}
+
+#[test]
+fn test_parse_klc_errors() {
+    assert_eq!(
+        parse_klc("LAYOUT\n10\tQ\t0\tq\tQ\n").unwrap_err(),
+        "Missing KBD line"
+    );
+    assert_eq!(
+        parse_klc("KBD\tx\t\"X\"\nLAYOUT\nzz\tQ\t0\tq\tQ\n").unwrap_err(),
+        "Invalid scan code: zz (line 3)"
+    );
+    assert_eq!(
+        parse_klc("KBD\tx\t\"X\"\nLAYOUT\n10\tQ\t0\tqq\tQ\n").unwrap_err(),
+        "Invalid character: qq (line 3)"
+    );
+    assert_eq!(
+        parse_klc("KBD\tx\t\"X\"\n").unwrap_err(),
+        "No keys in the LAYOUT section"
+    );
+}
+
+#[test]
+fn test_parse_klc_without_shiftstate_or_descriptions() {
+    let layout_map = parse_klc("KBD\tmini\t\"Mini\"\nLAYOUT\n10\tQ\t1\tq\tQ\n").unwrap();
+    assert_eq!(layout_map.layout.lang_name, "Mini");
+    assert_eq!(layout_map.layout.language, None);
+    assert_eq!(layout_map.layout.direction, KeyboardDirection::LTR);
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyQ, ShiftLevel::Shift),
+        Some("Q")
+    );
+}
@@ -0,0 +1,100 @@
+use super::*;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::types::ShiftLevel;
+use crate::test_utils::{TempDirGuard, temp_dir};
+
+const MINIMAL_KLC: &str = "KBD\thebtest\t\"Hebrew Test\"\nLOCALENAME\t\"he-IL\"\n\
+SHIFTSTATE\n0\n1\nLAYOUT\n1e\tA\t0\t05e9\tA\nDESCRIPTIONS\n0409\tHebrew Test Phonetic\nENDKBD\n";
+
+fn utf16le_with_bom(text: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    bytes
+}
+
+#[test]
+fn test_decode_text_encodings() {
+    assert_eq!(decode_text(&utf16le_with_bom("KBD ש")).unwrap(), "KBD ש");
+    let mut big_endian = vec![0xFE, 0xFF];
+    big_endian.extend("KBD ש".encode_utf16().flat_map(u16::to_be_bytes));
+    assert_eq!(decode_text(&big_endian).unwrap(), "KBD ש");
+    assert_eq!(decode_text(b"\xEF\xBB\xBFKBD").unwrap(), "KBD");
+    assert_eq!(decode_text("KBD ש".as_bytes()).unwrap(), "KBD ש");
+    assert!(decode_text(b"\xFF\xFE\x00\xD8").is_err());
+}
+
+#[test]
+fn test_load_utf16_klc_file() {
+    let dir = temp_dir("import_klc");
+    let _guard = TempDirGuard::new(dir.clone());
+    let path = dir.join("hebtest.KLC");
+    fs::write(&path, utf16le_with_bom(MINIMAL_KLC)).unwrap();
+
+    let maps = load_layout_file(&path).unwrap();
+    assert_eq!(maps.len(), 1);
+    let layout_map = &maps[0];
+    assert_eq!(layout_map.layout.id, "hebtest");
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("ש")
+    );
+}
+
+#[test]
+fn test_load_layout_file_errors_name_the_file() {
+    let dir = temp_dir("import_errors");
+    let _guard = TempDirGuard::new(dir.clone());
+    let unknown = dir.join("layout.txt");
+    fs::write(&unknown, "KBD").unwrap();
+    let broken = dir.join("broken.klc");
+    fs::write(&broken, "LAYOUT\n").unwrap();
+
+    let err = load_layout_file(&unknown).unwrap_err();
+    assert!(err.contains("Unknown layout file format"), "{}", err);
+    let err = load_layout_file(&broken).unwrap_err();
+    assert!(
+        err.contains("broken.klc") && err.contains("Missing KBD line"),
+        "{}",
+        err
+    );
+    let keylayout = dir.join("broken.keylayout");
+    fs::write(&keylayout, "<keyboard>").unwrap();
+    let err = load_layout_file(&keylayout).unwrap_err();
+    assert!(err.contains("Invalid keylayout XML"), "{}", err);
+    let err = load_layout_file(&dir.join("missing.klc")).unwrap_err();
+    assert!(err.starts_with("Failed to read layout file"), "{}", err);
+}
+
+#[test]
+fn test_load_layout_files_skips_failures() {
+    let dir = temp_dir("import_skips");
+    let _guard = TempDirGuard::new(dir.clone());
+    let good = dir.join("good.klc");
+    fs::write(&good, MINIMAL_KLC).unwrap();
+
+    let maps = load_layout_files(&[
+        LayoutFile::Path(dir.join("missing.klc")),
+        LayoutFile::Path(good),
+    ]);
+    assert_eq!(maps.len(), 1);
+    assert_eq!(maps[0].layout.lang_name, "Hebrew Test Phonetic");
+}
+
+#[test]
+fn test_load_layout_files_applies_configured_language() {
+    let dir = temp_dir("import_language");
+    let _guard = TempDirGuard::new(dir.clone());
+    let path = dir.join("phonetic.klc");
+    fs::write(&path, MINIMAL_KLC).unwrap();
+
+    let maps = load_layout_files(&[LayoutFile::Table {
+        path: path.clone(),
+        language: Some("yi".to_string()),
+    }]);
+    assert_eq!(maps[0].layout.language.as_deref(), Some("yi"));
+    let maps = load_layout_files(&[LayoutFile::Table {
+        path,
+        language: None,
+    }]);
+    assert_eq!(maps[0].layout.language.as_deref(), Some("he-IL"));
+}
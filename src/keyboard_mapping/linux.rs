@@ -1,175 +1,730 @@
 #![cfg(target_os = "linux")]
-use xkbcommon::xkb::{CONTEXT_NO_FLAGS, Context, KEYMAP_COMPILE_NO_FLAGS, Keymap, State};
+use xkbcommon::xkb::{
+    CONTEXT_NO_FLAGS, Context, KEYMAP_COMPILE_NO_FLAGS, KEYMAP_FORMAT_TEXT_V1, Keymap, Keysym,
+    ModMask, State, compose, keysym_get_name,
+};
 
-use quick_xml::de::from_str;
-use serde::Deserialize;
 use std::process::Command;
 
+use super::builtin::builtin_layout_maps_for;
+use super::cache::{cache_key, cached_layout_maps};
+use super::custom::with_configured_layouts;
+use super::physical_key::PhysicalKey;
 use super::remap::is_rtl_char;
-use super::types::{KeyboardDirection, KeyboardLayout, LayoutMap};
-use std::collections::HashMap;
+use super::types::{
+    KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, ShiftLevel, script_for_language,
+};
+use crate::config::{Config, LayoutSource};
+use console::console_layout_maps;
+use registry::{XkbConfigRegistry, parse_registry, registry_paths, xkb_config_root};
+use sources::{LayoutNames, layout_names_for_config, read_layout_source};
+use std::cell::{Cell, OnceCell};
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::{Path, PathBuf};
 
-const XML_PATH: &str = "/usr/share/X11/xkb/rules/evdev.xml";
-#[derive(Debug, Deserialize)]
-struct XkbConfigRegistry {
-    #[serde(rename = "layoutList")]
-    layout_list: LayoutList,
+mod console;
+mod registry;
+mod sources;
+
+// Groups a single XKB keymap can hold.
+const MAX_GROUPS: usize = 4;
+
+// Compose data the dead key sequences come from.
+const COMPOSE_DIR_PATH: &str = "/usr/share/X11/locale/compose.dir";
+
+/// XKB and compose data the layout maps of `names` are compiled from; the
+/// layout cache is rebuilt when any of these change. The files read for
+/// `names` are stamped one by one, so editing one in place is noticed; the
+/// data directories catch files added or replaced elsewhere, e.g. included
+/// symbols.
+fn xkb_data_paths(names: &LayoutNames) -> Vec<PathBuf> {
+    let root = xkb_config_root();
+    let rules = Some(names.rules.as_str())
+        .filter(|rules| !rules.is_empty())
+        .unwrap_or("evdev");
+    let mut paths: Vec<PathBuf> = registry_paths(&root).into();
+    paths.push(root.join("rules").join(rules));
+    paths.extend(
+        names
+            .layout
+            .split(',')
+            .filter_map(|layout| layout.split('(').next())
+            .map(str::trim)
+            .filter(|layout| !layout.is_empty())
+            .map(|layout| root.join("symbols").join(layout)),
+    );
+    paths.extend(
+        ["symbols", "keycodes", "types"]
+            .iter()
+            .map(|dir| root.join(dir)),
+    );
+    paths.push(PathBuf::from(COMPOSE_DIR_PATH));
+    paths.extend(compose_files());
+    paths
 }
 
-#[derive(Debug, Deserialize)]
-struct LayoutList {
-    #[serde(rename = "layout", default)]
-    layouts: Vec<Layout>,
+/// The compose files the table of the current locale is read from: the
+/// user's `XCOMPOSEFILE` or `~/.XCompose`, and the locale's file listed in
+/// `compose.dir`.
+fn compose_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    match std::env::var_os("XCOMPOSEFILE").filter(|file| !file.is_empty()) {
+        Some(file) => files.push(PathBuf::from(file)),
+        None => files.extend(
+            std::env::var_os("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| Path::new(&home).join(".XCompose")),
+        ),
+    }
+    let compose_dir = Path::new(COMPOSE_DIR_PATH);
+    if let (Ok(listing), Some(locale_dir)) = (fs::read_to_string(compose_dir), compose_dir.parent())
+    {
+        files.extend(
+            env_locale()
+                .into_iter()
+                .chain(["en_US.UTF-8".to_string()])
+                .find_map(|locale| compose_dir_entry(&listing, &locale))
+                .map(|file| locale_dir.join(file)),
+        );
+    }
+    files
 }
 
-#[derive(Debug, Deserialize)]
-struct Layout {
-    #[serde(rename = "configItem")]
-    config_item: ConfigItem,
+/// The compose file `compose.dir` lists for `locale`, e.g.
+/// `en_US.UTF-8/Compose`. Locales compare ignoring case and dashes, so
+/// `en_US.utf8` finds `en_US.UTF-8`.
+fn compose_dir_entry<'a>(listing: &'a str, locale: &str) -> Option<&'a str> {
+    let normalize = |name: &str| name.trim().to_lowercase().replace('-', "");
+    let locale = normalize(locale);
+    listing
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .find_map(|line| {
+            let (file, name) = line.split_once(':')?;
+            (normalize(name) == locale).then_some(file.trim())
+        })
 }
 
-#[derive(Debug, Deserialize)]
-struct ConfigItem {
-    name: String,
+thread_local! {
+    // Work counters for the expensive steps, per thread so tests running in
+    // parallel don't see each other's work.
+    static CONFIG_LOADS: Cell<usize> = const { Cell::new(0) };
+    static REGISTRY_PARSES: Cell<usize> = const { Cell::new(0) };
+    static KEYMAP_COMPILATIONS: Cell<usize> = const { Cell::new(0) };
 }
 
-fn get_registry_from_xml() -> Result<XkbConfigRegistry, quick_xml::DeError> {
-    let xml_data = fs::read_to_string(XML_PATH).unwrap_or(String::from(""));
+/// The config, or the defaults when it can't be loaded.
+fn load_config() -> Config {
+    CONFIG_LOADS.with(|count| count.set(count.get() + 1));
+    crate::config::load_config().unwrap_or_else(|_| Config::with_defaults())
+}
 
-    let registry: XkbConfigRegistry = from_str(&xml_data)?;
-    Ok(registry)
+/// Parses the XKB registry and merges in its extras. A missing or broken
+/// registry gives an empty one, which only loses layout metadata.
+fn get_registry_from_xml() -> XkbConfigRegistry {
+    REGISTRY_PARSES.with(|count| count.set(count.get() + 1));
+    let read = |path: &PathBuf| {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|xml| parse_registry(&xml).ok())
+    };
+    let [main, extras] = registry_paths(&xkb_config_root());
+    let mut registry = read(&main).unwrap_or_default();
+    if let Some(extras) = read(&extras) {
+        registry.merge(extras);
+    }
+    registry
 }
 
-fn get_locale_layout_and_variant_strs(registry: XkbConfigRegistry) -> String {
-    let output = Command::new("locale")
-        .arg("-a")
-        .output()
-        .expect("Failed to run localectl");
+fn installed_locales() -> Vec<String> {
+    let Ok(output) = Command::new("locale").arg("-a").output() else {
+        return Vec::new();
+    };
     let installed_locales_str: String = String::from_utf8_lossy(&output.stdout).to_string();
-    let installed_locales: Vec<String> = installed_locales_str
+    installed_locales_str
         .split("\n")
         .map(|s| s.to_string())
-        .collect();
-    let active_countries: Vec<String> = installed_locales
-        .iter()
-        .filter_map(|loc| {
-            if loc.starts_with("C") || loc.starts_with("POSIX") {
-                None
-            } else {
-                let parts: Vec<&str> = loc.split(&['_', '.'][..]).collect();
-                if parts.len() >= 2 {
-                    Some(parts[1].to_lowercase())
-                } else {
-                    None
-                }
+        .collect()
+}
+
+/// The configured layouts, from the first of `sources` that reports any;
+/// `None` when none does. The registry is only parsed when the locale guess
+/// is reached.
+fn configured_layout_names(
+    sources: &[LayoutSource],
+    registry: &OnceCell<XkbConfigRegistry>,
+) -> Option<LayoutNames> {
+    for source in sources {
+        let names = match source {
+            LayoutSource::Locale => {
+                let layouts = registry
+                    .get_or_init(get_registry_from_xml)
+                    .layouts_for_locales(&installed_locales());
+                LayoutNames::new(&layouts.join(","), "")
             }
-        })
-        .collect();
-    let filtered_layouts: Vec<&Layout> = registry
-        .layout_list
-        .layouts
-        .iter()
-        .filter(|layout| active_countries.contains(&layout.config_item.name.to_lowercase()))
-        .collect();
+            other => read_layout_source(*other),
+        };
+        if names.is_some() {
+            return names;
+        }
+    }
+    None
+}
 
-    let layout_string: String = filtered_layouts
-        .iter()
-        .map(|layout| layout.config_item.name.clone())
-        .collect::<Vec<_>>()
-        .join(", ");
-    layout_string
+/// The layout `XKB_DEFAULT_LAYOUT` picks, if set.
+fn default_layout_names() -> Option<LayoutNames> {
+    let env = |var: &str| std::env::var(var).ok().filter(|v| !v.is_empty());
+    LayoutNames::new(
+        &env("XKB_DEFAULT_LAYOUT")?,
+        &env("XKB_DEFAULT_VARIANT").unwrap_or_default(),
+    )
+}
+
+/// The layout names to compile, and whether they are the user's layouts:
+/// configured, detected or set by `XKB_DEFAULT_LAYOUT`. Otherwise they are
+/// XKB's default, `us`, spelled out so the layout gets a proper id.
+fn system_layout_names(
+    cfg: &Config,
+    registry: &OnceCell<XkbConfigRegistry>,
+) -> (LayoutNames, bool) {
+    let mut chosen = true;
+    let names = layout_names_for_config(cfg, |sources| {
+        configured_layout_names(sources, registry)
+            .or_else(default_layout_names)
+            .unwrap_or_else(|| {
+                chosen = false;
+                LayoutNames::new("us", "").unwrap_or_default()
+            })
+    });
+    (names, chosen)
 }
 
-fn get_keymap() -> Result<Option<Keymap>, quick_xml::DeError> {
-    let registry: XkbConfigRegistry = get_registry_from_xml()?;
-    let layout_str = get_locale_layout_and_variant_strs(registry);
+/// Compiles one keymap holding the layouts of `names`, one group each.
+fn get_keymap(names: &LayoutNames) -> Option<Keymap> {
+    KEYMAP_COMPILATIONS.with(|count| count.set(count.get() + 1));
     let context = Context::new(CONTEXT_NO_FLAGS);
-    let keymap = Keymap::new_from_names(
+    // No options means XKB's defaults, while an empty string would clear them.
+    let options = Some(names.options.clone()).filter(|o| !o.is_empty());
+    Keymap::new_from_names(
         &context,
-        "",
-        "",
-        &layout_str,
-        "",
-        None::<String>,
+        &names.rules,
+        &names.model,
+        &names.layout,
+        &names.variant,
+        options,
         KEYMAP_COMPILE_NO_FLAGS,
-    );
-    Ok(keymap)
+    )
 }
-pub fn get_layout(index: u32) -> Option<KeyboardLayout> {
-    let keymap = get_keymap().expect("failed to get key map")?;
-    if index >= keymap.num_layouts() {
+
+/// Compiles every configured layout. XKB keymaps hold at most four groups,
+/// so layouts are compiled in batches of four; a batch that fails is retried
+/// one layout at a time so a single broken layout doesn't drop its
+/// neighbours. A layout that fails on its own is kept without a keymap, so
+/// the layouts after it keep their index.
+fn get_keymaps(names: &LayoutNames) -> Vec<(Option<Keymap>, LayoutNames)> {
+    let mut keymaps = Vec::new();
+    for batch in names.batches(MAX_GROUPS) {
+        if let Some(keymap) = get_keymap(&batch) {
+            keymaps.push((Some(keymap), batch));
+            continue;
+        }
+        for single in batch.batches(1) {
+            keymaps.push((get_keymap(&single), single));
+        }
+    }
+    keymaps
+}
+
+/// Whether any layout compiled.
+fn any_compiled(keymaps: &[(Option<Keymap>, LayoutNames)]) -> bool {
+    keymaps.iter().any(|(keymap, _)| keymap.is_some())
+}
+
+/// Every configured layout as `(keymap, names, group index)`, in the order
+/// the layouts were configured; a layout's position here is its layout
+/// index. Layouts that failed to compile have no keymap.
+fn layout_groups(
+    keymaps: &[(Option<Keymap>, LayoutNames)],
+) -> Vec<(Option<&Keymap>, &LayoutNames, u32)> {
+    keymaps
+        .iter()
+        .flat_map(|(keymap, names)| {
+            let groups = keymap.as_ref().map_or(1, Keymap::num_layouts);
+            (0..groups).map(move |i| (keymap.as_ref(), names, i))
+        })
+        .collect()
+}
+
+/// Whether stdin is a virtual terminal (`/dev/tty1`, ...). An SSH session
+/// gets a pseudo-terminal instead, and cron jobs and services none at all.
+fn on_virtual_terminal() -> bool {
+    fs::read_link("/proc/self/fd/0").is_ok_and(|path| is_virtual_terminal(&path))
+}
+
+fn is_virtual_terminal(path: &Path) -> bool {
+    path.to_str()
+        .and_then(|path| path.strip_prefix("/dev/tty"))
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Whether the console keymap stands in for the system layouts: on a
+/// virtual terminal, where no XKB layout is in use, or when `layout_sources`
+/// lists `console` and none of the sources before it reports layouts
+/// (`reports`). Over SSH the server's console keymap has nothing to do with
+/// the keyboard the remote user types on, so it is only used there when
+/// listed. `layouts` naming XKB layouts explicitly always wins.
+fn uses_console_keymap(
+    cfg: &Config,
+    virtual_terminal: bool,
+    reports: impl FnOnce(&[LayoutSource]) -> bool,
+) -> bool {
+    if !cfg.layouts.is_empty() {
+        return false;
+    }
+    let sources = &cfg.layout_sources;
+    match sources.iter().position(|s| *s == LayoutSource::Console) {
+        Some(position) => !reports(&sources[..position]),
+        None => virtual_terminal,
+    }
+}
+
+/// The console keymap's maps when they stand in for the system layouts, see
+/// `uses_console_keymap`.
+fn console_session_maps(
+    cfg: &Config,
+    registry: &OnceCell<XkbConfigRegistry>,
+) -> Option<Vec<LayoutMap>> {
+    let console = uses_console_keymap(cfg, on_virtual_terminal(), |sources| {
+        configured_layout_names(sources, registry).is_some()
+    });
+    if !console {
         return None;
     }
+    Some(console_layout_maps()).filter(|maps| !maps.is_empty())
+}
 
-    let name = keymap.layout_get_name(index).to_string();
-    let lang_name = if name.is_empty() {
-        index.to_string()
-    } else {
-        name.clone()
-    };
+/// Without XKB data no layout compiles, so built-in layouts stand in: the
+/// ones `names` chose, or all of them when the user chose no layouts.
+fn builtin_maps(names: Option<&LayoutNames>) -> Vec<LayoutMap> {
+    let ids: Vec<String> = names
+        .map(|names| {
+            names
+                .layout
+                .split(',')
+                .map(|layout| layout.trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    builtin_layout_maps_for(&ids)
+}
 
-    let mut state = State::new(&keymap);
-    state.update_mask(0, 0, 0, index, 0, 0);
-    let mut dir = KeyboardDirection::LTR;
-    for keycode in 8u16..=255u16 {
-        let s = state.key_get_utf8(keycode.into());
-        if let Some(first) = s.chars().next()
-            && is_rtl_char(first)
-        {
-            dir = KeyboardDirection::RTL;
-            break;
+/// The system layouts: compiled keymaps, or the console or built-in maps
+/// standing in for them.
+enum SystemLayouts {
+    Xkb(Vec<(Option<Keymap>, LayoutNames)>, XkbConfigRegistry),
+    Maps(Vec<LayoutMap>),
+}
+
+impl SystemLayouts {
+    fn detect() -> Self {
+        let cfg = load_config();
+        let registry = OnceCell::new();
+        if let Some(maps) = console_session_maps(&cfg, &registry) {
+            return SystemLayouts::Maps(maps);
         }
+        let (names, chosen) = system_layout_names(&cfg, &registry);
+        SystemLayouts::compile(&names, chosen, registry)
     }
+
+    /// Compiles `names`, parsing the registry unless `registry` already
+    /// holds it. Built-in maps stand in when nothing compiles, see
+    /// `builtin_maps`.
+    fn compile(names: &LayoutNames, chosen: bool, registry: OnceCell<XkbConfigRegistry>) -> Self {
+        let keymaps = get_keymaps(names);
+        if !any_compiled(&keymaps) {
+            return SystemLayouts::Maps(builtin_maps(chosen.then_some(names)));
+        }
+        let registry = registry.into_inner().unwrap_or_else(get_registry_from_xml);
+        SystemLayouts::Xkb(keymaps, registry)
+    }
+
+    fn layouts(&self) -> Vec<KeyboardLayout> {
+        match self {
+            SystemLayouts::Xkb(keymaps, registry) => layout_groups(keymaps)
+                .into_iter()
+                .filter_map(|(keymap, names, group)| {
+                    layout_from_keymap(keymap, names, group, registry)
+                })
+                .collect(),
+            SystemLayouts::Maps(maps) => maps.iter().map(|map| map.layout.clone()).collect(),
+        }
+    }
+
+    fn layout(&self, index: usize) -> Option<KeyboardLayout> {
+        match self {
+            SystemLayouts::Xkb(keymaps, registry) => {
+                let (keymap, names, group) = *layout_groups(keymaps).get(index)?;
+                layout_from_keymap(keymap, names, group, registry)
+            }
+            SystemLayouts::Maps(maps) => maps.get(index).map(|map| map.layout.clone()),
+        }
+    }
+
+    fn layout_map(&self, index: u32) -> Option<LayoutMap> {
+        match self {
+            SystemLayouts::Xkb(keymaps, registry) => {
+                let (keymap, names, group) = *layout_groups(keymaps).get(index as usize)?;
+                let layout = layout_from_keymap(keymap, names, group, registry)
+                    .unwrap_or_else(|| index_layout(index));
+                Some(group_layout_map(
+                    keymap,
+                    group,
+                    layout,
+                    compose_table().as_ref(),
+                ))
+            }
+            SystemLayouts::Maps(maps) => maps.get(index as usize).cloned(),
+        }
+    }
+}
+
+/// Stand-in for a layout that can't be described, named by its index.
+fn index_layout(index: u32) -> KeyboardLayout {
+    KeyboardLayout {
+        id: index.to_string(),
+        lang_name: index.to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
+    }
+}
+
+pub fn get_layout(index: u32) -> Option<KeyboardLayout> {
+    SystemLayouts::detect().layout(index as usize)
+}
+
+/// RTL when any key of group `index` types a right-to-left character
+/// unshifted.
+fn keymap_direction(keymap: &Keymap, index: u32) -> KeyboardDirection {
+    let mut state = State::new(keymap);
+    state.update_mask(0, 0, 0, index, 0, 0);
+    let rtl = PhysicalKey::all().any(|key| {
+        state
+            .key_get_utf8(key.xkb_keycode().into())
+            .chars()
+            .next()
+            .is_some_and(is_rtl_char)
+    });
+    if rtl {
+        KeyboardDirection::RTL
+    } else {
+        KeyboardDirection::LTR
+    }
+}
+
+/// Describes group `index` of a keymap compiled from `names`, named and
+/// tagged with languages from the registry when it lists the layout. A
+/// layout that failed to compile is described from its names alone.
+fn layout_from_keymap(
+    keymap: Option<&Keymap>,
+    names: &LayoutNames,
+    index: u32,
+    registry: &XkbConfigRegistry,
+) -> Option<KeyboardLayout> {
+    if keymap.is_some_and(|keymap| index >= keymap.num_layouts()) {
+        return None;
+    }
+
+    let variant = names.variant_at(index as usize);
+    let info = names
+        .layout_at(index as usize)
+        .and_then(|layout| registry.find(&layout, variant.as_deref()))
+        .unwrap_or_default();
+    let name = keymap
+        .map(|keymap| keymap.layout_get_name(index).to_string())
+        .unwrap_or_default();
+    let id = match (names.layout_at(index as usize), &variant) {
+        (Some(layout), Some(variant)) => format!("{}({})", layout, variant),
+        (Some(layout), None) => layout,
+        (None, _) => index.to_string(),
+    };
+    let lang_name = match info.description {
+        Some(description) => description,
+        None if !name.is_empty() => name,
+        None if keymap.is_none() => id.clone(),
+        None => index.to_string(),
+    };
+
+    let dir = keymap.map_or(KeyboardDirection::LTR, |keymap| {
+        keymap_direction(keymap, index)
+    });
+    let language = info.languages.into_iter().next();
+    let script = language
+        .as_deref()
+        .and_then(script_for_language)
+        .map(str::to_string);
     Some(KeyboardLayout {
+        id,
         lang_name,
         direction: dir,
+        variant,
+        language,
+        script,
     })
 }
 
 pub fn list_layouts() -> Vec<KeyboardLayout> {
-    let Some(keymap) = get_keymap().expect("failed to get key map") else {
-        return vec![KeyboardLayout {
-            lang_name: String::from("current"),
-            direction: KeyboardDirection::LTR,
-        }];
+    SystemLayouts::detect().layouts()
+}
+
+pub fn vk_to_char_map_for_layout(layout_index: u32) -> LayoutMap {
+    SystemLayouts::detect()
+        .layout_map(layout_index)
+        .unwrap_or_else(|| LayoutMap::new(index_layout(layout_index), HashMap::new()))
+}
+
+/// Compiles a single XKB layout by name, e.g. `("us", "intl")`.
+pub fn vk_to_char_map_for_names(layout: &str, variant: &str) -> Option<LayoutMap> {
+    let names = LayoutNames {
+        layout: layout.to_string(),
+        variant: variant.to_string(),
+        ..LayoutNames::default()
     };
-    let mut result = Vec::new();
-    for i in 0..keymap.num_layouts() {
-        if let Some(l) = get_layout(i) {
-            result.push(l);
+    let keymap = get_keymap(&names)?;
+    let keyboard_layout = layout_from_keymap(Some(&keymap), &names, 0, &get_registry_from_xml())?;
+    Some(layout_map_from_keymap(
+        &keymap,
+        0,
+        keyboard_layout,
+        compose_table().as_ref(),
+    ))
+}
+
+/// Wraps the first `xkb_symbols` block of a symbols file into a keymap on
+/// the evdev keycodes, with the `pc` keys and Right Alt as AltGr.
+fn keymap_from_symbols(symbols: &str) -> Option<String> {
+    let text: String = symbols
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let start = text.find("xkb_symbols")?;
+    let open = start + text[start..].find('{')?;
+    let mut depth = 0;
+    let close = text[open..].char_indices().find_map(|(i, c)| {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
         }
+        (depth == 0).then_some(open + i)
+    })?;
+    Some(format!(
+        "xkb_keymap {{\n\
+         \txkb_keycodes {{ include \"evdev+aliases(qwerty)\" }};\n\
+         \txkb_types {{ include \"complete\" }};\n\
+         \txkb_compat {{ include \"complete\" }};\n\
+         \txkb_symbols {{\n\
+         \t\tinclude \"pc\"\n{}\n\
+         \t\tinclude \"level3(ralt_switch)\"\n\
+         \t}};\n\
+         }};\n",
+        &text[open + 1..close]
+    ))
+}
+
+/// Compiles a hand-written XKB keymap (`xkb_keymap`) or symbols file
+/// (`xkb_symbols`) against the system XKB data, one layout per group.
+/// Groups are named by their `name[GroupN]`, falling back to `name`.
+pub fn layout_maps_from_xkb(text: &str, name: &str) -> Result<Vec<LayoutMap>, String> {
+    let source = if text.contains("xkb_keymap") {
+        text.to_string()
+    } else if text.contains("xkb_symbols") {
+        keymap_from_symbols(text).ok_or("Unterminated xkb_symbols block")?
+    } else {
+        return Err("Expected an xkb_keymap or xkb_symbols block".to_string());
+    };
+    KEYMAP_COMPILATIONS.with(|count| count.set(count.get() + 1));
+    let context = Context::new(CONTEXT_NO_FLAGS);
+    let keymap = Keymap::new_from_string(
+        &context,
+        source,
+        KEYMAP_FORMAT_TEXT_V1,
+        KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or("Failed to compile the XKB keymap")?;
+    let compose = compose_table();
+    let groups = keymap.num_layouts();
+    Ok((0..groups)
+        .map(|index| {
+            let group_name = keymap.layout_get_name(index);
+            let mut layout = KeyboardLayout {
+                id: if groups == 1 {
+                    name.to_string()
+                } else {
+                    format!("{}:{}", name, index + 1)
+                },
+                lang_name: if group_name.is_empty() {
+                    name.to_string()
+                } else {
+                    group_name.to_string()
+                },
+                direction: keymap_direction(&keymap, index),
+                variant: None,
+                language: None,
+                script: None,
+            };
+            layout.language = layout.language_code().map(str::to_string);
+            layout.script = layout
+                .language
+                .as_deref()
+                .and_then(script_for_language)
+                .map(str::to_string);
+            layout_map_from_keymap(&keymap, index, layout, compose.as_ref())
+        })
+        .collect())
+}
+
+/// Builds the maps of every configured layout, compiling one keymap per
+/// batch of four layouts.
+fn build_all_layout_maps(names: &LayoutNames, registry: &XkbConfigRegistry) -> Vec<LayoutMap> {
+    let keymaps = get_keymaps(names);
+    let compose = compose_table();
+    layout_groups(&keymaps)
+        .into_iter()
+        .filter_map(|(keymap, names, group)| {
+            let layout = layout_from_keymap(keymap, names, group, registry)?;
+            Some(group_layout_map(keymap, group, layout, compose.as_ref()))
+        })
+        .collect()
+}
+
+/// The map of one layout group; a layout that failed to compile types
+/// nothing.
+fn group_layout_map(
+    keymap: Option<&Keymap>,
+    group: u32,
+    layout: KeyboardLayout,
+    compose: Option<&compose::Table>,
+) -> LayoutMap {
+    match keymap {
+        Some(keymap) => layout_map_from_keymap(keymap, group, layout, compose),
+        None => LayoutMap::new(layout, HashMap::new()),
     }
-    if result.is_empty() {
-        result.push(KeyboardLayout {
-            lang_name: String::from("current"),
-            direction: KeyboardDirection::LTR,
-        });
-    }
-    result
 }
 
-pub fn vk_to_char_map_for_layout(layout_index: u32) -> LayoutMap {
-    let layout = get_layout(layout_index).unwrap_or(KeyboardLayout {
-        lang_name: layout_index.to_string(),
-        direction: KeyboardDirection::LTR,
-    });
-    let Some(keymap) = get_keymap().expect("failed to get key map") else {
-        return LayoutMap {
-            layout,
-            map: HashMap::new(),
+fn layout_map_from_keymap(
+    keymap: &Keymap,
+    layout_index: u32,
+    layout: KeyboardLayout,
+    compose: Option<&compose::Table>,
+) -> LayoutMap {
+    let mut map: HashMap<KeyStroke, String> = HashMap::new();
+    let mut dead_keys: Vec<(KeyStroke, Keysym)> = Vec::new();
+    let mut live_keys: Vec<(KeyStroke, Keysym)> = Vec::new();
+    // Levels a key doesn't define fall back to a lower one; only record each
+    // xkb level once so e.g. AltGr on a two-level key isn't reported.
+    let mut seen: HashSet<(PhysicalKey, u32)> = HashSet::new();
+    for level in ShiftLevel::ALL {
+        let Some(mask) = level_mask(keymap, level) else {
+            continue;
         };
-    };
-    let mut state = State::new(&keymap);
-    state.update_mask(0, 0, 0, layout_index, 0, 0);
-    let mut map: HashMap<u16, String> = HashMap::new();
-    for keycode in 8u16..=255u16 {
-        let s = state.key_get_utf8(keycode.into());
-        if !s.is_empty() {
-            map.entry(keycode).or_insert(s);
+        let mut state = State::new(keymap);
+        state.update_mask(mask, 0, 0, layout_index, 0, 0);
+        for key in PhysicalKey::all() {
+            let keycode = key.xkb_keycode();
+            let key_layout = state.key_get_layout(keycode.into());
+            let xkb_level = state.key_get_level(keycode.into(), key_layout);
+            if !seen.insert((key, xkb_level)) {
+                continue;
+            }
+            let stroke = KeyStroke::new(key, level);
+            let keysym = state.key_get_one_sym(keycode.into());
+            if keysym_get_name(keysym).starts_with("dead_") {
+                dead_keys.push((stroke, keysym));
+                continue;
+            }
+            let s = state.key_get_utf8(keycode.into());
+            if !s.is_empty() {
+                map.entry(stroke).or_insert(s);
+                live_keys.push((stroke, keysym));
+            }
+        }
+    }
+    let mut layout_map = LayoutMap::new(layout, map);
+    if let Some(table) = compose
+        && !dead_keys.is_empty()
+    {
+        layout_map.sequences = dead_key_sequences(table, &dead_keys, &live_keys);
+    }
+    layout_map
+}
+
+fn env_locale() -> Option<String> {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty())
+}
+
+fn compose_table() -> Option<compose::Table> {
+    let context = Context::new(CONTEXT_NO_FLAGS);
+    env_locale()
+        .into_iter()
+        .chain(["en_US.UTF-8".to_string(), "C".to_string()])
+        .find_map(|locale| {
+            compose::Table::new_from_locale(
+                &context,
+                std::ffi::OsStr::new(&locale),
+                compose::COMPILE_NO_FLAGS,
+            )
+            .ok()
+        })
+}
+
+/// Outputs of every dead key followed by a regular key, per the system
+/// compose table.
+fn dead_key_sequences(
+    table: &compose::Table,
+    dead_keys: &[(KeyStroke, Keysym)],
+    live_keys: &[(KeyStroke, Keysym)],
+) -> HashMap<Vec<KeyStroke>, String> {
+    let mut sequences = HashMap::new();
+    let mut state = compose::State::new(table, compose::STATE_NO_FLAGS);
+    for (dead_stroke, dead_sym) in dead_keys {
+        for (live_stroke, live_sym) in live_keys {
+            state.reset();
+            state.feed(*dead_sym);
+            state.feed(*live_sym);
+            if state.status() == compose::Status::Composed
+                && let Some(output) = state.utf8().filter(|s| !s.is_empty())
+            {
+                sequences.insert(vec![*dead_stroke, *live_stroke], output);
+            }
         }
     }
-    LayoutMap { layout, map }
+    sequences
+}
+
+fn modifier_mask(keymap: &Keymap, names: &[&str]) -> Option<ModMask> {
+    names
+        .iter()
+        .map(|name| keymap.mod_get_index(*name))
+        .find(|idx| *idx < keymap.num_mods())
+        .map(|idx| 1 << idx)
+}
+
+fn level_mask(keymap: &Keymap, level: ShiftLevel) -> Option<ModMask> {
+    let mut mask: ModMask = 0;
+    if level.has_shift() {
+        mask |= modifier_mask(keymap, &["Shift"])?;
+    }
+    if level.has_altgr() {
+        mask |= modifier_mask(keymap, &["Mod5", "LevelThree"])?;
+    }
+    Some(mask)
 }
 
 pub fn vk_to_char_map_default() -> LayoutMap {
@@ -177,8 +732,38 @@ pub fn vk_to_char_map_default() -> LayoutMap {
 }
 
 pub fn all_layout_vk_maps() -> Vec<LayoutMap> {
-    let total = list_layouts().len() as u32;
-    (0..total).map(vk_to_char_map_for_layout).collect()
+    all_layout_vk_maps_for(&load_config())
+}
+
+/// `all_layout_vk_maps` for an already loaded config.
+pub fn all_layout_vk_maps_for(cfg: &Config) -> Vec<LayoutMap> {
+    let registry = OnceCell::new();
+    if let Some(maps) = console_session_maps(cfg, &registry) {
+        return with_configured_layouts(maps, cfg);
+    }
+    let (names, chosen) = system_layout_names(cfg, &registry);
+    let mut maps = cached_layout_maps(&layout_cache_key(&names), || {
+        build_all_layout_maps(&names, registry.get_or_init(get_registry_from_xml))
+    });
+    if maps.iter().all(|map| map.map.is_empty()) {
+        maps = builtin_maps(chosen.then_some(&names));
+    }
+    with_configured_layouts(maps, cfg)
+}
+
+/// The layouts come from the configured layout names, and dead key
+/// compositions from the current locale.
+fn layout_cache_key(names: &LayoutNames) -> String {
+    let configuration = format!(
+        "rules={}\nmodel={}\nlayout={}\nvariant={}\noptions={}\ncompose={}",
+        names.rules,
+        names.model,
+        names.layout,
+        names.variant,
+        names.options,
+        env_locale().unwrap_or_default()
+    );
+    cache_key(&configuration, &xkb_data_paths(names))
 }
 
 #[cfg(all(test, target_os = "linux"))]
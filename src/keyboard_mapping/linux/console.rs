@@ -0,0 +1,595 @@
+use super::{compose_table, dead_key_sequences};
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::remap::is_rtl_char;
+use crate::keyboard_mapping::types::{
+    KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, ShiftLevel, script_for_language,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use xkbcommon::xkb::{
+    KEYSYM_NO_FLAGS, Keysym, compose, keysym_from_name, keysym_get_name, keysym_to_utf32,
+    utf32_to_keysym,
+};
+
+// Where kbd installs its keymaps, depending on the distribution.
+const KEYMAP_ROOTS: [&str; 4] = [
+    "/usr/share/kbd/keymaps",
+    "/usr/share/keymaps",
+    "/usr/lib/kbd/keymaps",
+    "/lib/kbd/keymaps",
+];
+
+const VCONSOLE_CONF_PATH: &str = "/etc/vconsole.conf";
+
+// Debian's console-setup compiles the XKB layout into a kbd keymap here.
+const CONSOLE_SETUP_DIR: &str = "/etc/console-setup";
+
+const KEYMAP_SUFFIXES: [&str; 4] = [".map.gz", ".map", ".kmap.gz", ".kmap"];
+const INCLUDE_SUFFIXES: [&str; 6] = ["", ".inc", ".map", ".inc.gz", ".map.gz", ".gz"];
+
+// Includes nest a few levels in practice; the limit stops include loops.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// Modifier bits of a keymap index, as named in `keycode` line prefixes.
+const MODIFIERS: [(&str, u32); 10] = [
+    ("plain", 0),
+    ("shift", 1),
+    ("altgr", 2),
+    ("control", 4),
+    ("alt", 8),
+    ("shiftl", 16),
+    ("shiftr", 32),
+    ("ctrll", 64),
+    ("ctrlr", 128),
+    ("capsshift", 256),
+];
+
+// Characters the kernel passes to the compose table for each dead key, in
+// both the ASCII and the Latin-1 spelling keymaps use.
+const DEAD_DIACRITICS: [(&str, &[char]); 6] = [
+    ("dead_grave", &['`']),
+    ("dead_acute", &['\'', '´']),
+    ("dead_circumflex", &['^']),
+    ("dead_tilde", &['~']),
+    ("dead_diaeresis", &['"', '¨']),
+    ("dead_cedilla", &[',', '¸']),
+];
+
+/// The key definitions of a kbd keymap (`loadkeys` format) with its
+/// includes resolved.
+#[derive(Debug, Default)]
+pub struct ConsoleKeymap {
+    /// Keymap indexes filled by the columns of `keycode` lines, from the
+    /// last `keymaps` line.
+    columns: Vec<u32>,
+    /// Actions by kernel keycode and keymap index.
+    keys: HashMap<u16, HashMap<u32, String>>,
+    /// `compose` definitions as (first, second, result).
+    compose: Vec<(char, char, char)>,
+}
+
+/// What a keymap action types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Text(String),
+    Dead(Keysym),
+}
+
+impl ConsoleKeymap {
+    /// The action of `code` at keymap `index`.
+    fn action(&self, code: u16, index: u32) -> Option<&str> {
+        self.keys.get(&code)?.get(&index).map(String::as_str)
+    }
+}
+
+/// Splits a line into words, `'c'` character literals and `"..."`
+/// strings, with `=` as a word of its own. Comments start with `#` or `!`.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '#' | '!' => break,
+            '=' => {
+                chars.next();
+                tokens.push("=".to_string());
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut token = String::from(c);
+                while let Some(next) = chars.next() {
+                    token.push(next);
+                    if next == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            token.push(escaped);
+                        }
+                    } else if next == c {
+                        break;
+                    }
+                }
+                tokens.push(token);
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut token = String::new();
+                while let Some(next) = chars.next_if(|n| !n.is_whitespace() && *n != '=') {
+                    token.push(next);
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    tokens
+}
+
+/// Parses a number as `loadkeys` does: decimal, `0x` hex or `0` octal.
+fn parse_number(token: &str) -> Option<u32> {
+    if let Some(hex) = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        u32::from_str_radix(hex, 16).ok()
+    } else if token.len() > 1
+        && let Some(octal) = token.strip_prefix('0')
+    {
+        u32::from_str_radix(octal, 8).ok()
+    } else {
+        token.parse().ok()
+    }
+}
+
+/// Parses a `keymaps` list such as `0-2,4-6,8,12`.
+fn parse_keymaps_line(list: &str) -> Option<Vec<u32>> {
+    let mut columns = Vec::new();
+    for range in list.split(',').filter(|r| !r.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => columns.extend(parse_number(start)?..=parse_number(end)?),
+            None => columns.push(parse_number(range)?),
+        }
+    }
+    Some(columns)
+}
+
+/// A `'c'` literal, with `\'`, `\\` and octal `\NNN` escapes.
+fn parse_char_literal(token: &str) -> Option<char> {
+    let inner = token.strip_prefix('\'')?.strip_suffix('\'')?;
+    match inner.strip_prefix('\\') {
+        Some(escaped) if escaped.len() > 1 => char::from_u32(u32::from_str_radix(escaped, 8).ok()?),
+        Some(escaped) => escaped.chars().next(),
+        None => {
+            let mut chars = inner.chars();
+            let c = chars.next()?;
+            chars.next().is_none().then_some(c)
+        }
+    }
+}
+
+/// A character given as a literal, `U+XXXX`, a number or a keysym name.
+fn parse_char(token: &str) -> Option<char> {
+    parse_char_literal(token).or_else(|| match resolve_action(token)? {
+        Action::Text(text) => {
+            let mut chars = text.chars();
+            let c = chars.next()?;
+            chars.next().is_none().then_some(c)
+        }
+        Action::Dead(_) => None,
+    })
+}
+
+/// Resolves a keymap action: a keysym name, `U+XXXX`, or a number naming a
+/// Latin-1 character or kernel letter action. `+` (Caps Lock applies) is
+/// ignored. Function keys, modifiers and console actions give `None`.
+fn resolve_action(token: &str) -> Option<Action> {
+    let token = token.strip_prefix('+').unwrap_or(token);
+    if let Some(hex) = token.strip_prefix("U+") {
+        return char::from_u32(u32::from_str_radix(hex, 16).ok()?).map(|c| Action::Text(c.into()));
+    }
+    if token.starts_with(|c: char| c.is_ascii_digit()) && token.len() > 1 {
+        let value = parse_number(token)?;
+        // Types 0 (Latin) and 0x0b (letter) carry a Latin-1 character.
+        return matches!(value >> 8, 0 | 0x0b)
+            .then(|| Action::Text(char::from(value as u8).into()));
+    }
+    let keysym = [token.to_string(), format!("Greek_{}", token)]
+        .iter()
+        .map(|name| keysym_from_name(name, KEYSYM_NO_FLAGS))
+        .find(|keysym| keysym.raw() != 0)?;
+    if keysym_get_name(keysym).starts_with("dead_") {
+        return Some(Action::Dead(keysym));
+    }
+    char::from_u32(keysym_to_utf32(keysym))
+        .filter(|c| *c != '\0')
+        .map(|c| Action::Text(c.into()))
+}
+
+/// Parses keymap `text` into `keymap`. `include` loads the keymap named by
+/// an `include` line into the same keymap.
+pub fn parse_console_keymap(
+    text: &str,
+    keymap: &mut ConsoleKeymap,
+    include: &mut dyn FnMut(&str, &mut ConsoleKeymap) -> Result<(), String>,
+) -> Result<(), String> {
+    let joined = text.replace("\\\n", " ");
+    for (number, line) in joined.lines().enumerate() {
+        let tokens = tokenize(line);
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+        match first.as_str() {
+            "include" => {
+                let name = tokens
+                    .get(1)
+                    .map(|t| t.trim_matches('"'))
+                    .ok_or_else(|| format!("Missing include name (line {})", number + 1))?;
+                include(name, keymap)?;
+            }
+            "keymaps" => {
+                keymap.columns = tokens
+                    .get(1)
+                    .and_then(|list| parse_keymaps_line(list))
+                    .ok_or_else(|| format!("Invalid keymaps line (line {})", number + 1))?;
+            }
+            "compose" => {
+                if let [_, first, second, to, result, ..] = tokens.as_slice()
+                    && to == "to"
+                    && let (Some(first), Some(second), Some(result)) =
+                        (parse_char(first), parse_char(second), parse_char(result))
+                {
+                    keymap.compose.push((first, second, result));
+                }
+            }
+            _ => {
+                let Some(position) = tokens.iter().position(|t| t == "keycode") else {
+                    continue;
+                };
+                let mut index = None;
+                for modifier in &tokens[..position] {
+                    let (_, bit) = MODIFIERS
+                        .iter()
+                        .find(|(name, _)| name == modifier)
+                        .ok_or_else(|| {
+                            format!("Unknown modifier: {} (line {})", modifier, number + 1)
+                        })?;
+                    index = Some(index.unwrap_or(0) | bit);
+                }
+                let code = tokens
+                    .get(position + 1)
+                    .and_then(|code| parse_number(code))
+                    .and_then(|code| u16::try_from(code).ok())
+                    .ok_or_else(|| format!("Invalid keycode (line {})", number + 1))?;
+                let actions = match tokens.get(position + 2).map(String::as_str) {
+                    Some("=") => &tokens[position + 3..],
+                    _ => return Err(format!("Missing = (line {})", number + 1)),
+                };
+                let entries = keymap.keys.entry(code).or_default();
+                match (index, actions) {
+                    (Some(index), [action, ..]) => {
+                        entries.insert(index, action.clone());
+                    }
+                    // A lone letter is typed in upper case with Shift.
+                    (None, [action]) => {
+                        let letter = action.strip_prefix('+').unwrap_or(action);
+                        entries.insert(0, action.clone());
+                        if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_lowercase()) {
+                            entries.insert(1, letter.to_ascii_uppercase());
+                        }
+                    }
+                    (None, actions) => {
+                        let columns: Vec<u32> = if keymap.columns.is_empty() {
+                            (0..actions.len() as u32).collect()
+                        } else {
+                            keymap.columns.clone()
+                        };
+                        for (index, action) in columns.into_iter().zip(actions) {
+                            entries.insert(index, action.clone());
+                        }
+                    }
+                    (Some(_), []) => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decodes a keymap: UTF-8, or Latin-1 for older keymaps.
+fn decode_keymap(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes)
+        .unwrap_or_else(|e| e.into_bytes().iter().map(|b| char::from(*b)).collect())
+}
+
+/// Reads a keymap file, decompressing `.gz` files with `gzip -dc`.
+fn read_keymap_file(path: &Path) -> Result<String, String> {
+    let bytes = if path.extension().is_some_and(|ext| ext == "gz") {
+        let output = Command::new("gzip")
+            .arg("-dc")
+            .arg(path)
+            .output()
+            .map_err(|e| format!("Failed to run gzip for {}: {}", path.display(), e))?;
+        if !output.status.success() {
+            return Err(format!("Failed to decompress {}", path.display()));
+        }
+        output.stdout
+    } else {
+        fs::read(path).map_err(|e| format!("Failed to read keymap {}: {}", path.display(), e))?
+    };
+    Ok(decode_keymap(bytes))
+}
+
+/// Finds an included keymap next to the including one, in the `include`
+/// directories above it, or in the keymap roots, as `loadkeys` does.
+fn find_include(name: &str, from: &Path, roots: &[PathBuf]) -> Option<PathBuf> {
+    let near = from.parent().into_iter().flat_map(Path::ancestors).take(4);
+    let dirs: Vec<PathBuf> = near
+        .map(Path::to_path_buf)
+        .chain(roots.iter().cloned())
+        .flat_map(|dir| [dir.clone(), dir.join("include"), dir.join("i386/include")])
+        .collect();
+    dirs.iter()
+        .flat_map(|dir| {
+            INCLUDE_SUFFIXES
+                .iter()
+                .map(move |suffix| dir.join(format!("{}{}", name, suffix)))
+        })
+        .find(|path| path.is_file())
+}
+
+/// Loads the keymap at `path` with its includes.
+pub fn load_console_keymap(path: &Path, roots: &[PathBuf]) -> Result<ConsoleKeymap, String> {
+    fn load(
+        path: &Path,
+        roots: &[PathBuf],
+        depth: usize,
+        keymap: &mut ConsoleKeymap,
+    ) -> Result<(), String> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(format!("Includes nested too deeply at {}", path.display()));
+        }
+        let text = read_keymap_file(path)?;
+        parse_console_keymap(&text, keymap, &mut |name, keymap| {
+            let included = find_include(name, path, roots)
+                .ok_or_else(|| format!("{}: include not found: {}", path.display(), name))?;
+            load(&included, roots, depth + 1, keymap)
+        })
+    }
+    let mut keymap = ConsoleKeymap::default();
+    load(path, roots, 0, &mut keymap)?;
+    Ok(keymap)
+}
+
+/// The keymap name of a keymap file, e.g. `ru` for `ru.map.gz`.
+fn keymap_name(path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    KEYMAP_SUFFIXES
+        .iter()
+        .find_map(|suffix| file_name.strip_suffix(suffix))
+        .unwrap_or(&file_name)
+        .to_string()
+}
+
+/// Finds the keymap file for a `KEYMAP` name under `roots`. Paths are
+/// used as they are.
+pub fn find_keymap(name: &str, roots: &[PathBuf]) -> Option<PathBuf> {
+    let path = Path::new(name);
+    if path.is_absolute() {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    fn search(dir: &Path, name: &str) -> Option<PathBuf> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .collect();
+        entries.sort();
+        let (dirs, files): (Vec<PathBuf>, Vec<PathBuf>) =
+            entries.into_iter().partition(|path| path.is_dir());
+        files
+            .into_iter()
+            .find(|path| {
+                let file_name = path.to_string_lossy();
+                KEYMAP_SUFFIXES.iter().any(|s| file_name.ends_with(s)) && keymap_name(path) == name
+            })
+            .or_else(|| {
+                dirs.iter()
+                    .filter(|dir| !dir.ends_with("include"))
+                    .find_map(|dir| search(dir, name))
+            })
+    }
+    roots.iter().find_map(|root| search(root, name))
+}
+
+/// Parses the `KEYMAP` and `KEYMAP_TOGGLE` settings of systemd's
+/// `/etc/vconsole.conf`.
+pub fn parse_vconsole_conf(data: &str) -> Vec<String> {
+    ["KEYMAP", "KEYMAP_TOGGLE"]
+        .iter()
+        .filter_map(|key| {
+            data.lines().find_map(|line| {
+                let (name, value) = line.split_once('=')?;
+                (name.trim() == *key).then(|| value.trim().trim_matches(|c| c == '"' || c == '\''))
+            })
+        })
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses the `VC Keymap` line of `localectl status`, which reads `n/a`
+/// when no console keymap is set.
+pub fn parse_localectl_keymap(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        let value = value.trim();
+        (name.trim() == "VC Keymap" && !value.is_empty() && value != "n/a")
+            .then(|| value.to_string())
+    })
+}
+
+fn keymap_roots() -> Vec<PathBuf> {
+    KEYMAP_ROOTS.iter().map(PathBuf::from).collect()
+}
+
+/// The keymap files of the active console keymap: `/etc/vconsole.conf`,
+/// then `localectl status`, then the keymap console-setup compiled.
+fn active_console_keymaps(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut names = fs::read_to_string(VCONSOLE_CONF_PATH)
+        .map(|data| parse_vconsole_conf(&data))
+        .unwrap_or_default();
+    if names.is_empty() {
+        names.extend(
+            Command::new("localectl")
+                .arg("status")
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| {
+                    parse_localectl_keymap(&String::from_utf8_lossy(&output.stdout))
+                }),
+        );
+    }
+    let paths: Vec<PathBuf> = names
+        .iter()
+        .filter_map(|name| find_keymap(name, roots))
+        .collect();
+    if !paths.is_empty() {
+        return paths;
+    }
+    let mut cached: Vec<PathBuf> = fs::read_dir(CONSOLE_SETUP_DIR)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name = path.to_string_lossy();
+            name.contains("cached_") && name.ends_with(".kmap.gz")
+        })
+        .collect();
+    cached.sort();
+    cached.truncate(1);
+    cached
+}
+
+/// Builds the layout map of a console keymap. Keymaps 0-3 are the base,
+/// Shift, AltGr and Shift+AltGr levels. Dead keys compose with the
+/// keymap's `compose` lines, then with the system compose table.
+pub fn layout_map_from_console_keymap(
+    keymap: &ConsoleKeymap,
+    name: &str,
+    compose: Option<&compose::Table>,
+) -> LayoutMap {
+    let mut map: HashMap<KeyStroke, String> = HashMap::new();
+    let mut dead_keys: Vec<(KeyStroke, Keysym)> = Vec::new();
+    let mut live_keys: Vec<(KeyStroke, Keysym)> = Vec::new();
+    for level in ShiftLevel::ALL {
+        let index = u32::from(level.has_shift()) | (u32::from(level.has_altgr()) << 1);
+        for key in PhysicalKey::all() {
+            let Some(action) = keymap
+                .action(key.evdev_code(), index)
+                .and_then(resolve_action)
+            else {
+                continue;
+            };
+            let stroke = KeyStroke::new(key, level);
+            let base = KeyStroke::base(key);
+            match action {
+                Action::Dead(keysym) => {
+                    if level == ShiftLevel::Base || !dead_keys.contains(&(base, keysym)) {
+                        dead_keys.push((stroke, keysym));
+                    }
+                }
+                Action::Text(text) => {
+                    if text.chars().any(char::is_control)
+                        || (level != ShiftLevel::Base && map.get(&base) == Some(&text))
+                    {
+                        continue;
+                    }
+                    if let Some(c) = text.chars().next() {
+                        live_keys.push((stroke, utf32_to_keysym(c as u32)));
+                    }
+                    map.insert(stroke, text);
+                }
+            }
+        }
+    }
+
+    let mut sequences = HashMap::new();
+    for (dead_stroke, keysym) in &dead_keys {
+        let name = keysym_get_name(*keysym);
+        let Some((_, diacritics)) = DEAD_DIACRITICS.iter().find(|(dead, _)| *dead == name) else {
+            continue;
+        };
+        for (first, second, result) in &keymap.compose {
+            if !diacritics.contains(first) {
+                continue;
+            }
+            for (live_stroke, _) in &live_keys {
+                if map.get(live_stroke) == Some(&second.to_string()) {
+                    sequences
+                        .entry(vec![*dead_stroke, *live_stroke])
+                        .or_insert_with(|| result.to_string());
+                }
+            }
+        }
+    }
+    if let Some(table) = compose
+        && !dead_keys.is_empty()
+    {
+        for (strokes, output) in dead_key_sequences(table, &dead_keys, &live_keys) {
+            sequences.entry(strokes).or_insert(output);
+        }
+    }
+
+    let direction = if map.values().flat_map(|s| s.chars()).any(is_rtl_char) {
+        KeyboardDirection::RTL
+    } else {
+        KeyboardDirection::LTR
+    };
+    let mut layout = KeyboardLayout {
+        id: name.to_string(),
+        // Keymap names start with the layout, e.g. `de-latin1-nodeadkeys`.
+        lang_name: name
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or(name)
+            .to_string(),
+        direction,
+        variant: None,
+        language: None,
+        script: None,
+    };
+    layout.language = layout.language_code().map(str::to_string);
+    layout.script = layout
+        .language
+        .as_deref()
+        .and_then(script_for_language)
+        .map(str::to_string);
+    let mut layout_map = LayoutMap::new(layout, map);
+    layout_map.sequences = sequences;
+    layout_map
+}
+
+/// The layout maps of the active console keymap and its toggle keymap.
+/// Keymaps that fail to load are skipped.
+pub fn console_layout_maps() -> Vec<LayoutMap> {
+    let roots = keymap_roots();
+    let compose = compose_table();
+    active_console_keymaps(&roots)
+        .iter()
+        .filter_map(|path| {
+            let keymap = load_console_keymap(path, &roots).ok()?;
+            Some(layout_map_from_console_keymap(
+                &keymap,
+                &keymap_name(path),
+                compose.as_ref(),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
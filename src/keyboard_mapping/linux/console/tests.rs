@@ -0,0 +1,204 @@
+use super::*;
+use crate::test_utils::{TempDirGuard, temp_dir};
+
+fn parse(text: &str) -> ConsoleKeymap {
+    let mut keymap = ConsoleKeymap::default();
+    parse_console_keymap(text, &mut keymap, &mut |name, _| {
+        Err(format!("unexpected include {}", name))
+    })
+    .unwrap();
+    keymap
+}
+
+const RUSSIAN: &str = r#"# Russian test keymap
+keymaps 0-3
+keycode  16 = +q  +Q  +Cyrillic_shorti  +Cyrillic_SHORTI
+keycode  30 = +a +A \
+    +Cyrillic_ef +Cyrillic_EF
+keycode  40 = apostrophe quotedbl  Cyrillic_e  Cyrillic_E
+keycode   2 = one  exclam  one  exclam
+keycode  28 = Return
+keycode  57 = space
+altgr keycode 18 = U+20AC
+"#;
+
+#[test]
+fn test_tokenize() {
+    assert_eq!(
+        tokenize("keycode 30=a  A # comment"),
+        vec!["keycode", "30", "=", "a", "A"]
+    );
+    assert_eq!(
+        tokenize(r"compose '\'' 'e' to 'é'"),
+        vec!["compose", r"'\''", "'e'", "to", "'é'"]
+    );
+    assert!(tokenize("! only a comment").is_empty());
+}
+
+#[test]
+fn test_parse_numbers_and_keymaps_lines() {
+    assert_eq!(parse_number("30"), Some(30));
+    assert_eq!(parse_number("0x1e"), Some(30));
+    assert_eq!(parse_number("036"), Some(30));
+    assert_eq!(parse_number("0"), Some(0));
+    assert_eq!(
+        parse_keymaps_line("0-2,4,8-9"),
+        Some(vec![0, 1, 2, 4, 8, 9])
+    );
+    assert_eq!(parse_keymaps_line("0-x"), None);
+}
+
+#[test]
+fn test_resolve_action() {
+    let text = |s: &str| Some(Action::Text(s.to_string()));
+    assert_eq!(resolve_action("+Cyrillic_ef"), text("ф"));
+    assert_eq!(resolve_action("hebrew_aleph"), text("א"));
+    assert_eq!(resolve_action("alpha"), text("α"));
+    assert_eq!(resolve_action("U+20AC"), text("€"));
+    assert_eq!(resolve_action("0xe9"), text("é"));
+    assert_eq!(resolve_action("0x0b61"), text("a"));
+    assert!(matches!(
+        resolve_action("dead_acute"),
+        Some(Action::Dead(_))
+    ));
+    assert_eq!(resolve_action("Console_1"), None);
+    assert_eq!(resolve_action("VoidSymbol"), None);
+}
+
+#[test]
+fn test_parse_console_keymap_columns_and_modifiers() {
+    let keymap = parse(RUSSIAN);
+    assert_eq!(keymap.action(16, 2), Some("+Cyrillic_shorti"));
+    assert_eq!(keymap.action(30, 3), Some("+Cyrillic_EF"));
+    assert_eq!(keymap.action(18, 2), Some("U+20AC"));
+    assert_eq!(keymap.action(57, 0), Some("space"));
+    assert_eq!(keymap.action(57, 1), None);
+
+    let letters = parse("keycode 30 = +a\nshift altgr keycode 30 = Cyrillic_EF\n");
+    assert_eq!(letters.action(30, 0), Some("+a"));
+    assert_eq!(letters.action(30, 1), Some("A"));
+    assert_eq!(letters.action(30, 3), Some("Cyrillic_EF"));
+}
+
+#[test]
+fn test_parse_console_keymap_errors() {
+    let mut keymap = ConsoleKeymap::default();
+    let mut include = |_: &str, _: &mut ConsoleKeymap| Ok(());
+    assert_eq!(
+        parse_console_keymap("keycode x = a", &mut keymap, &mut include).unwrap_err(),
+        "Invalid keycode (line 1)"
+    );
+    assert_eq!(
+        parse_console_keymap("\nhyper keycode 30 = a", &mut keymap, &mut include).unwrap_err(),
+        "Unknown modifier: hyper (line 2)"
+    );
+    assert_eq!(
+        parse_console_keymap("keymaps 0-", &mut keymap, &mut include).unwrap_err(),
+        "Invalid keymaps line (line 1)"
+    );
+}
+
+#[test]
+fn test_layout_map_from_console_keymap() {
+    let layout_map = layout_map_from_console_keymap(&parse(RUSSIAN), "ru", None);
+    let output = |key, level| layout_map.output(key, level);
+    assert_eq!(output(PhysicalKey::KeyQ, ShiftLevel::Base), Some("q"));
+    assert_eq!(output(PhysicalKey::KeyA, ShiftLevel::Shift), Some("A"));
+    assert_eq!(output(PhysicalKey::KeyA, ShiftLevel::AltGr), Some("ф"));
+    assert_eq!(
+        output(PhysicalKey::Quote, ShiftLevel::ShiftAltGr),
+        Some("Э")
+    );
+    assert_eq!(output(PhysicalKey::KeyE, ShiftLevel::AltGr), Some("€"));
+    assert_eq!(output(PhysicalKey::Space, ShiftLevel::Base), Some(" "));
+    // Repeats of the base level and control characters are left out.
+    assert_eq!(output(PhysicalKey::Digit1, ShiftLevel::AltGr), None);
+    assert_eq!(output(PhysicalKey::Enter, ShiftLevel::Base), None);
+    assert_eq!(layout_map.layout.id, "ru");
+    assert_eq!(layout_map.layout.language.as_deref(), Some("ru"));
+    assert_eq!(layout_map.layout.script.as_deref(), Some("Cyrl"));
+}
+
+#[test]
+fn test_console_dead_keys_use_compose_lines() {
+    let keymap = parse(
+        "keycode 40 = dead_acute quotedbl\nkeycode 18 = e E\n\
+         compose '\\'' 'e' to 'é'\ncompose '\\'' 'E' to U+00C9\ncompose '`' 'e' to 'è'\n",
+    );
+    let layout_map = layout_map_from_console_keymap(&keymap, "de-latin1", None);
+    let dead = KeyStroke::base(PhysicalKey::Quote);
+    assert_eq!(layout_map.map.get(&dead), None);
+    assert_eq!(
+        layout_map.output(PhysicalKey::Quote, ShiftLevel::Shift),
+        Some("\"")
+    );
+    let sequence = |stroke| {
+        layout_map
+            .sequences
+            .get(&vec![dead, stroke])
+            .map(String::as_str)
+    };
+    assert_eq!(sequence(KeyStroke::base(PhysicalKey::KeyE)), Some("é"));
+    assert_eq!(
+        sequence(KeyStroke::new(PhysicalKey::KeyE, ShiftLevel::Shift)),
+        Some("É")
+    );
+    assert_eq!(layout_map.sequences.len(), 2);
+    assert_eq!(layout_map.layout.language.as_deref(), Some("de"));
+}
+
+#[test]
+fn test_load_console_keymap_follows_includes() {
+    let root = temp_dir("console_include");
+    let _guard = TempDirGuard::new(root.clone());
+    fs::create_dir_all(root.join("i386/qwerty")).unwrap();
+    fs::create_dir_all(root.join("i386/include")).unwrap();
+    fs::write(
+        root.join("i386/include/qwerty-layout.inc"),
+        "keymaps 0-1\nkeycode 16 = q Q\n",
+    )
+    .unwrap();
+    fs::write(root.join("linux-keys.inc"), "keycode 57 = space\n").unwrap();
+    let source = root.join("i386/qwerty/test.map");
+    fs::write(
+        &source,
+        "include \"qwerty-layout\"\ninclude \"linux-keys\"\nkeycode 30 = a A\n",
+    )
+    .unwrap();
+    let status = Command::new("gzip").arg(&source).status().unwrap();
+    assert!(status.success());
+
+    let roots = vec![root.clone()];
+    let path = find_keymap("test", &roots).unwrap();
+    assert_eq!(path, root.join("i386/qwerty/test.map.gz"));
+    assert_eq!(keymap_name(&path), "test");
+    let keymap = load_console_keymap(&path, &roots).unwrap();
+    assert_eq!(keymap.action(16, 1), Some("Q"));
+    assert_eq!(keymap.action(57, 0), Some("space"));
+    assert_eq!(keymap.action(30, 1), Some("A"));
+
+    fs::write(root.join("loop.map"), "include \"loop.map\"\n").unwrap();
+    let err = load_console_keymap(&root.join("loop.map"), &roots).unwrap_err();
+    assert!(err.contains("nested too deeply"), "{}", err);
+    fs::write(root.join("missing.map"), "include \"nowhere\"\n").unwrap();
+    let err = load_console_keymap(&root.join("missing.map"), &roots).unwrap_err();
+    assert!(err.contains("include not found: nowhere"), "{}", err);
+    assert_eq!(find_keymap("nowhere", &roots), None);
+}
+
+#[test]
+fn test_parse_vconsole_conf() {
+    assert_eq!(
+        parse_vconsole_conf("KEYMAP=\"ru\"\nKEYMAP_TOGGLE=us\nFONT=cyr-sun16\n"),
+        vec!["ru", "us"]
+    );
+    assert!(parse_vconsole_conf("FONT=lat2-16\nKEYMAP=\n").is_empty());
+}
+
+#[test]
+fn test_parse_localectl_keymap() {
+    let output =
+        "   System Locale: LANG=en_US.UTF-8\n       VC Keymap: de-latin1\n      X11 Layout: de\n";
+    assert_eq!(parse_localectl_keymap(output).as_deref(), Some("de-latin1"));
+    assert_eq!(parse_localectl_keymap("       VC Keymap: n/a\n"), None);
+}
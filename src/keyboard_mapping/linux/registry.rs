@@ -0,0 +1,282 @@
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+const DEFAULT_XKB_CONFIG_ROOT: &str = "/usr/share/X11/xkb";
+
+// ISO 639-2 codes used by the XKB registry (bibliographic and terminology
+// forms) paired with their ISO 639-1 code.
+const ISO_639_2_CODES: &[(&str, &str)] = &[
+    ("eng", "en"),
+    ("ger", "de"),
+    ("deu", "de"),
+    ("fre", "fr"),
+    ("fra", "fr"),
+    ("spa", "es"),
+    ("ita", "it"),
+    ("por", "pt"),
+    ("dut", "nl"),
+    ("nld", "nl"),
+    ("rus", "ru"),
+    ("ukr", "uk"),
+    ("bel", "be"),
+    ("pol", "pl"),
+    ("cze", "cs"),
+    ("ces", "cs"),
+    ("slo", "sk"),
+    ("slk", "sk"),
+    ("hun", "hu"),
+    ("rum", "ro"),
+    ("ron", "ro"),
+    ("bul", "bg"),
+    ("srp", "sr"),
+    ("hrv", "hr"),
+    ("slv", "sl"),
+    ("swe", "sv"),
+    ("nor", "no"),
+    ("nob", "nb"),
+    ("dan", "da"),
+    ("fin", "fi"),
+    ("est", "et"),
+    ("lav", "lv"),
+    ("lit", "lt"),
+    ("gre", "el"),
+    ("ell", "el"),
+    ("tur", "tr"),
+    ("heb", "he"),
+    ("ara", "ar"),
+    ("per", "fa"),
+    ("fas", "fa"),
+    ("urd", "ur"),
+    ("hin", "hi"),
+    ("ben", "bn"),
+    ("tha", "th"),
+    ("vie", "vi"),
+    ("jpn", "ja"),
+    ("kor", "ko"),
+    ("chi", "zh"),
+    ("zho", "zh"),
+    ("geo", "ka"),
+    ("kat", "ka"),
+    ("arm", "hy"),
+    ("hye", "hy"),
+    ("kaz", "kk"),
+];
+
+/// Converts an ISO 639-2 code to ISO 639-1, keeping codes without a
+/// two-letter form as they are.
+pub fn iso639_1(code: &str) -> String {
+    let code = code.trim().to_lowercase();
+    ISO_639_2_CODES
+        .iter()
+        .find(|(long, _)| *long == code)
+        .map(|(_, short)| short.to_string())
+        .unwrap_or(code)
+}
+
+/// The XKB data directory, `XKB_CONFIG_ROOT` when set.
+pub fn xkb_config_root() -> PathBuf {
+    std::env::var_os("XKB_CONFIG_ROOT")
+        .filter(|root| !root.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_XKB_CONFIG_ROOT))
+}
+
+/// The registry files under `root`: the main list and the extras, which
+/// add less common layouts and variants.
+pub fn registry_paths(root: &std::path::Path) -> [PathBuf; 2] {
+    [
+        root.join("rules").join("evdev.xml"),
+        root.join("rules").join("evdev.extras.xml"),
+    ]
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct XkbConfigRegistry {
+    #[serde(rename = "layoutList", default)]
+    layout_list: LayoutList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LayoutList {
+    #[serde(rename = "layout", default)]
+    layouts: Vec<Layout>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Layout {
+    #[serde(rename = "configItem")]
+    config_item: ConfigItem,
+    #[serde(rename = "variantList", default)]
+    variant_list: VariantList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct VariantList {
+    #[serde(rename = "variant", default)]
+    variants: Vec<Variant>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Variant {
+    #[serde(rename = "configItem")]
+    config_item: ConfigItem,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigItem {
+    name: String,
+    #[serde(rename = "shortDescription")]
+    short_description: Option<String>,
+    description: Option<String>,
+    #[serde(rename = "countryList", default)]
+    country_list: CountryList,
+    #[serde(rename = "languageList", default)]
+    language_list: LanguageList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CountryList {
+    #[serde(rename = "iso3166Id", default)]
+    countries: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LanguageList {
+    #[serde(rename = "iso639Id", default)]
+    languages: Vec<String>,
+}
+
+/// What the registry says about a layout or one of its variants.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutInfo {
+    pub description: Option<String>,
+    pub short_description: Option<String>,
+    /// ISO 639-1 codes where one exists, ISO 639-2 otherwise.
+    pub languages: Vec<String>,
+}
+
+pub fn parse_registry(xml: &str) -> Result<XkbConfigRegistry, quick_xml::DeError> {
+    from_str(xml)
+}
+
+impl ConfigItem {
+    fn has_language(&self, language: &str) -> bool {
+        self.language_list
+            .languages
+            .iter()
+            .any(|code| iso639_1(code) == language)
+    }
+
+    fn has_country(&self, country: &str) -> bool {
+        self.name.eq_ignore_ascii_case(country)
+            || self
+                .country_list
+                .countries
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(country))
+    }
+}
+
+impl XkbConfigRegistry {
+    /// Adds the layouts of `extras`, and its variants of layouts already
+    /// listed, to this registry.
+    pub fn merge(&mut self, extras: XkbConfigRegistry) {
+        for extra in extras.layout_list.layouts {
+            match self
+                .layout_list
+                .layouts
+                .iter_mut()
+                .find(|layout| layout.config_item.name == extra.config_item.name)
+            {
+                Some(layout) => {
+                    for variant in extra.variant_list.variants {
+                        if !layout
+                            .variant_list
+                            .variants
+                            .iter()
+                            .any(|v| v.config_item.name == variant.config_item.name)
+                        {
+                            layout.variant_list.variants.push(variant);
+                        }
+                    }
+                }
+                None => self.layout_list.layouts.push(extra),
+            }
+        }
+    }
+
+    /// Looks up a layout, and its variant when given. A variant inherits the
+    /// short description and languages of its layout unless it lists its own.
+    pub fn find(&self, layout: &str, variant: Option<&str>) -> Option<LayoutInfo> {
+        let layout = self
+            .layout_list
+            .layouts
+            .iter()
+            .find(|l| l.config_item.name == layout)?;
+        let base = &layout.config_item;
+        let item = match variant {
+            Some(variant) => layout
+                .variant_list
+                .variants
+                .iter()
+                .map(|v| &v.config_item)
+                .find(|v| v.name == variant)
+                .unwrap_or(base),
+            None => base,
+        };
+        let languages = if item.language_list.languages.is_empty() {
+            &base.language_list.languages
+        } else {
+            &item.language_list.languages
+        };
+        Some(LayoutInfo {
+            description: item.description.clone(),
+            short_description: item
+                .short_description
+                .clone()
+                .or_else(|| base.short_description.clone()),
+            languages: languages.iter().map(|code| iso639_1(code)).collect(),
+        })
+    }
+
+    /// Picks a layout for each installed locale by its language: a layout
+    /// for that language in the locale's country first, then one whose
+    /// indicator is the language code, then any layout listing the language.
+    pub fn layouts_for_locales(&self, installed_locales: &[String]) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for locale in installed_locales {
+            if locale.is_empty() || locale.starts_with('C') || locale.starts_with("POSIX") {
+                continue;
+            }
+            let mut parts = locale.split(['_', '.', '@']);
+            let language = parts.next().unwrap_or("").to_lowercase();
+            let country = parts.next().unwrap_or("").to_lowercase();
+            let speaking: Vec<&ConfigItem> = self
+                .layout_list
+                .layouts
+                .iter()
+                .map(|l| &l.config_item)
+                .filter(|item| item.has_language(&language))
+                .collect();
+            let chosen = speaking
+                .iter()
+                .find(|item| !country.is_empty() && item.has_country(&country))
+                .or_else(|| {
+                    speaking
+                        .iter()
+                        .find(|item| item.short_description.as_deref() == Some(language.as_str()))
+                })
+                .or_else(|| speaking.first());
+            if let Some(item) = chosen
+                && !names.contains(&item.name)
+            {
+                names.push(item.name.clone());
+            }
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests;
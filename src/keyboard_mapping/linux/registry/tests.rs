@@ -0,0 +1,180 @@
+use super::*;
+
+const MAIN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xkbConfigRegistry version="1.1">
+  <modelList/>
+  <layoutList>
+    <layout>
+      <configItem>
+        <name>us</name>
+        <shortDescription>en</shortDescription>
+        <description>English (US)</description>
+        <countryList><iso3166Id>US</iso3166Id></countryList>
+        <languageList><iso639Id>eng</iso639Id></languageList>
+      </configItem>
+      <variantList>
+        <variant>
+          <configItem>
+            <name>dvorak</name>
+            <description>English (Dvorak)</description>
+          </configItem>
+        </variant>
+        <variant>
+          <configItem>
+            <name>chr</name>
+            <shortDescription>chr</shortDescription>
+            <description>Cherokee</description>
+            <languageList><iso639Id>chr</iso639Id></languageList>
+          </configItem>
+        </variant>
+      </variantList>
+    </layout>
+    <layout>
+      <configItem>
+        <name>gb</name>
+        <shortDescription>en</shortDescription>
+        <description>English (UK)</description>
+        <languageList><iso639Id>eng</iso639Id></languageList>
+      </configItem>
+    </layout>
+    <layout>
+      <configItem>
+        <name>il</name>
+        <shortDescription>he</shortDescription>
+        <description>Hebrew</description>
+        <countryList><iso3166Id>IL</iso3166Id></countryList>
+        <languageList><iso639Id>heb</iso639Id></languageList>
+      </configItem>
+    </layout>
+    <layout>
+      <configItem>
+        <name>at</name>
+        <shortDescription>de</shortDescription>
+        <description>German (Austria)</description>
+        <languageList><iso639Id>ger</iso639Id></languageList>
+      </configItem>
+    </layout>
+  </layoutList>
+</xkbConfigRegistry>
+"#;
+
+const EXTRAS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xkbConfigRegistry version="1.1">
+  <layoutList>
+    <layout>
+      <configItem>
+        <name>il</name>
+      </configItem>
+      <variantList>
+        <variant>
+          <configItem>
+            <name>biblical</name>
+            <description>Hebrew (Biblical, SIL phonetic)</description>
+          </configItem>
+        </variant>
+      </variantList>
+    </layout>
+    <layout>
+      <configItem>
+        <name>apl</name>
+        <shortDescription>apl</shortDescription>
+        <description>APL</description>
+      </configItem>
+    </layout>
+  </layoutList>
+</xkbConfigRegistry>
+"#;
+
+fn registry() -> XkbConfigRegistry {
+    let mut registry = parse_registry(MAIN).unwrap();
+    registry.merge(parse_registry(EXTRAS).unwrap());
+    registry
+}
+
+fn locales(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+#[test]
+fn find_reads_descriptions_and_languages() {
+    let info = registry().find("us", None).unwrap();
+    assert_eq!(info.description.as_deref(), Some("English (US)"));
+    assert_eq!(info.short_description.as_deref(), Some("en"));
+    assert_eq!(info.languages, ["en"]);
+}
+
+#[test]
+fn variants_inherit_unless_they_list_their_own() {
+    let registry = registry();
+    let dvorak = registry.find("us", Some("dvorak")).unwrap();
+    assert_eq!(dvorak.description.as_deref(), Some("English (Dvorak)"));
+    assert_eq!(dvorak.short_description.as_deref(), Some("en"));
+    assert_eq!(dvorak.languages, ["en"]);
+
+    let cherokee = registry.find("us", Some("chr")).unwrap();
+    assert_eq!(cherokee.languages, ["chr"]);
+    assert_eq!(registry.find("xx", None), None);
+}
+
+#[test]
+fn extras_add_layouts_and_variants() {
+    let registry = registry();
+    let biblical = registry.find("il", Some("biblical")).unwrap();
+    assert_eq!(
+        biblical.description.as_deref(),
+        Some("Hebrew (Biblical, SIL phonetic)")
+    );
+    assert_eq!(biblical.languages, ["he"]);
+    assert_eq!(
+        registry.find("apl", None).unwrap().description.as_deref(),
+        Some("APL")
+    );
+    // The base entry keeps its own metadata.
+    assert_eq!(
+        registry.find("il", None).unwrap().description.as_deref(),
+        Some("Hebrew")
+    );
+}
+
+#[test]
+fn locales_match_layouts_by_language() {
+    let registry = registry();
+    assert_eq!(
+        registry.layouts_for_locales(&locales(&[
+            "C.UTF-8",
+            "POSIX",
+            "en_US.UTF-8",
+            "he_IL.UTF-8"
+        ])),
+        ["us", "il"]
+    );
+    // The country alone no longer decides: English in Israel is English.
+    assert_eq!(registry.layouts_for_locales(&locales(&["en_IL"])), ["us"]);
+    // A layout named after the country counts as one for it.
+    assert_eq!(
+        registry.layouts_for_locales(&locales(&["en_GB.UTF-8"])),
+        ["gb"]
+    );
+    assert_eq!(
+        registry.layouts_for_locales(&locales(&["de_DE.UTF-8"])),
+        ["at"]
+    );
+    assert!(
+        registry
+            .layouts_for_locales(&locales(&["xx_YY"]))
+            .is_empty()
+    );
+}
+
+#[test]
+fn iso639_codes_shorten_to_two_letters() {
+    assert_eq!(iso639_1("heb"), "he");
+    assert_eq!(iso639_1("ger"), "de");
+    assert_eq!(iso639_1("deu"), "de");
+    assert_eq!(iso639_1("chr"), "chr");
+}
+
+#[test]
+fn unparsable_registry_is_an_error() {
+    assert!(parse_registry("<xkbConfigRegistry><layoutList><layout>").is_err());
+}
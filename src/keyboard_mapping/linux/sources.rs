@@ -0,0 +1,332 @@
+use crate::config::{Config, LayoutSource};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const DEFAULT_KEYBOARD_PATH: &str = "/etc/default/keyboard";
+
+/// XKB rules, model, layouts, variants and options (RMLVO) as accepted by
+/// `Keymap::new_from_names`. Layouts and variants are comma-separated, one
+/// entry per group; empty strings select XKB's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutNames {
+    pub rules: String,
+    pub model: String,
+    pub layout: String,
+    pub variant: String,
+    pub options: String,
+}
+
+impl LayoutNames {
+    /// `None` when no layout is named.
+    pub fn new(layout: &str, variant: &str) -> Option<Self> {
+        let layout = layout.trim();
+        if layout.split(',').all(|l| l.trim().is_empty()) {
+            return None;
+        }
+        Some(LayoutNames {
+            layout: layout.to_string(),
+            variant: variant.trim().to_string(),
+            ..LayoutNames::default()
+        })
+    }
+
+    fn with_model_and_options(mut self, model: Option<&str>, options: Option<&str>) -> Self {
+        self.model = model.unwrap_or("").trim().to_string();
+        self.options = options.unwrap_or("").trim().to_string();
+        self
+    }
+
+    /// Splits the layouts into runs of at most `size` groups sharing the
+    /// same rules, model and options.
+    pub fn batches(&self, size: usize) -> Vec<LayoutNames> {
+        let layouts: Vec<&str> = self.layout.split(',').map(str::trim).collect();
+        let variants: Vec<&str> = self.variant.split(',').map(str::trim).collect();
+        layouts
+            .chunks(size.max(1))
+            .enumerate()
+            .map(|(chunk, batch)| {
+                let start = chunk * size.max(1);
+                let batch_variants: Vec<&str> = (start..start + batch.len())
+                    .map(|i| variants.get(i).copied().unwrap_or(""))
+                    .collect();
+                LayoutNames {
+                    layout: batch.join(","),
+                    variant: batch_variants.join(","),
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// The layout of the group at `index`.
+    pub fn layout_at(&self, index: usize) -> Option<String> {
+        self.layout
+            .split(',')
+            .nth(index)
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+    }
+
+    /// The variant of the group at `index`, if it has one.
+    pub fn variant_at(&self, index: usize) -> Option<String> {
+        self.variant
+            .split(',')
+            .nth(index)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    }
+
+    fn from_pairs(pairs: &[(String, String)]) -> Option<Self> {
+        let layouts: Vec<&str> = pairs.iter().map(|(l, _)| l.as_str()).collect();
+        let variants: Vec<&str> = pairs.iter().map(|(_, v)| v.as_str()).collect();
+        LayoutNames::new(&layouts.join(","), &variants.join(","))
+    }
+}
+
+/// The layout names for `cfg`: its `layouts` when set, otherwise what
+/// `detect` reads from its layout sources, with the configured XKB rules,
+/// model and options replacing the detected ones.
+pub fn layout_names_for_config(
+    cfg: &Config,
+    detect: impl FnOnce(&[LayoutSource]) -> LayoutNames,
+) -> LayoutNames {
+    let mut names = if cfg.layouts.is_empty() {
+        detect(&cfg.layout_sources)
+    } else {
+        LayoutNames {
+            layout: cfg
+                .layouts
+                .iter()
+                .map(|spec| spec.layout.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+            variant: cfg
+                .layouts
+                .iter()
+                .map(|spec| spec.variant.as_deref().unwrap_or(""))
+                .collect::<Vec<_>>()
+                .join(","),
+            ..LayoutNames::default()
+        }
+    };
+    if let Some(rules) = &cfg.xkb_rules {
+        names.rules = rules.clone();
+    }
+    if let Some(model) = &cfg.xkb_model {
+        names.model = model.clone();
+    }
+    if let Some(options) = &cfg.xkb_options {
+        names.options = options.join(",");
+    }
+    names
+}
+
+/// Reads the layouts reported by `source`, or `None` when it is unavailable
+/// or reports none. The locale guess and the console keymap are handled by
+/// the caller.
+pub fn read_layout_source(source: LayoutSource) -> Option<LayoutNames> {
+    match source {
+        LayoutSource::Gnome => command_output(
+            "gsettings",
+            &["get", "org.gnome.desktop.input-sources", "sources"],
+        )
+        .and_then(|out| parse_gnome_sources(&out))
+        .map(|names| {
+            let options = command_output(
+                "gsettings",
+                &["get", "org.gnome.desktop.input-sources", "xkb-options"],
+            );
+            names.with_model_and_options(
+                None,
+                options.as_deref().map(parse_gnome_options).as_deref(),
+            )
+        }),
+        LayoutSource::Kde => fs::read_to_string(kxkbrc_path()?)
+            .ok()
+            .and_then(|data| parse_kxkbrc(&data)),
+        LayoutSource::X11 => command_output("xprop", &["-root", "_XKB_RULES_NAMES"])
+            .and_then(|out| parse_xkb_rules_names(&out))
+            .or_else(|| {
+                command_output("setxkbmap", &["-query"]).and_then(|out| parse_setxkbmap_query(&out))
+            }),
+        LayoutSource::EtcDefaultKeyboard => fs::read_to_string(DEFAULT_KEYBOARD_PATH)
+            .ok()
+            .and_then(|data| parse_default_keyboard(&data)),
+        LayoutSource::Localectl => {
+            command_output("localectl", &["status"]).and_then(|out| parse_localectl(&out))
+        }
+        LayoutSource::Udev => {
+            command_output("udevadm", &["info", "--export-db"]).and_then(|out| parse_udev_db(&out))
+        }
+        LayoutSource::Locale | LayoutSource::Console => None,
+    }
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn kxkbrc_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("kxkbrc"))
+}
+
+/// Parses `gsettings get org.gnome.desktop.input-sources sources`, e.g.
+/// `[('xkb', 'us'), ('xkb', 'us+dvorak'), ('ibus', 'mozc-jp')]`. Input
+/// method engines are skipped.
+pub fn parse_gnome_sources(output: &str) -> Option<LayoutNames> {
+    let quoted: Vec<&str> = output.split('\'').skip(1).step_by(2).collect();
+    let pairs: Vec<(String, String)> = quoted
+        .chunks(2)
+        .filter(|pair| pair.len() == 2 && pair[0] == "xkb")
+        .map(|pair| match pair[1].split_once('+') {
+            Some((layout, variant)) => (layout.to_string(), variant.to_string()),
+            None => (pair[1].to_string(), String::new()),
+        })
+        .collect();
+    LayoutNames::from_pairs(&pairs)
+}
+
+/// Parses `gsettings get org.gnome.desktop.input-sources xkb-options`,
+/// e.g. `['lv3:ralt_switch', 'compose:rctrl']`, into XKB's comma-separated
+/// form.
+pub fn parse_gnome_options(output: &str) -> String {
+    output
+        .split('\'')
+        .skip(1)
+        .step_by(2)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parses the `[Layout]` group of KDE's `kxkbrc`. Layouts are only read
+/// when KDE manages them (`Use=true`).
+pub fn parse_kxkbrc(data: &str) -> Option<LayoutNames> {
+    let mut in_layout = false;
+    let mut use_layouts = false;
+    let mut layout = "";
+    let mut variant = "";
+    let mut model = None;
+    let mut options = None;
+    for line in data.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_layout = line == "[Layout]";
+            continue;
+        }
+        if !in_layout {
+            continue;
+        }
+        match line.split_once('=') {
+            Some(("Use", value)) => use_layouts = value.trim() == "true",
+            Some(("LayoutList", value)) => layout = value,
+            Some(("VariantList", value)) => variant = value,
+            Some(("Model", value)) => model = Some(value),
+            Some(("Options", value)) => options = Some(value),
+            _ => {}
+        }
+    }
+    if !use_layouts {
+        return None;
+    }
+    Some(LayoutNames::new(layout, variant)?.with_model_and_options(model, options))
+}
+
+/// Parses `xprop -root _XKB_RULES_NAMES`, e.g.
+/// `_XKB_RULES_NAMES(STRING) = "evdev", "pc105", "us,il", ",", "grp:alt_shift_toggle"`.
+pub fn parse_xkb_rules_names(output: &str) -> Option<LayoutNames> {
+    let (_, values) = output.split_once('=')?;
+    let fields: Vec<&str> = values.split('"').skip(1).step_by(2).collect();
+    let mut names = LayoutNames::new(fields.get(2)?, fields.get(3).copied().unwrap_or(""))?
+        .with_model_and_options(fields.get(1).copied(), fields.get(4).copied());
+    names.rules = fields[0].to_string();
+    Some(names)
+}
+
+/// Parses `setxkbmap -query`.
+pub fn parse_setxkbmap_query(output: &str) -> Option<LayoutNames> {
+    let mut names = LayoutNames::new(
+        value_of(output, "layout", ':')?,
+        value_of(output, "variant", ':').unwrap_or(""),
+    )?
+    .with_model_and_options(
+        value_of(output, "model", ':'),
+        value_of(output, "options", ':'),
+    );
+    names.rules = value_of(output, "rules", ':').unwrap_or("").to_string();
+    Some(names)
+}
+
+/// Parses the `XKBLAYOUT`/`XKBVARIANT` assignments of `/etc/default/keyboard`.
+pub fn parse_default_keyboard(data: &str) -> Option<LayoutNames> {
+    Some(
+        LayoutNames::new(
+            unquote(value_of(data, "XKBLAYOUT", '=')?),
+            unquote(value_of(data, "XKBVARIANT", '=').unwrap_or("")),
+        )?
+        .with_model_and_options(
+            value_of(data, "XKBMODEL", '=').map(unquote),
+            value_of(data, "XKBOPTIONS", '=').map(unquote),
+        ),
+    )
+}
+
+/// Parses the `X11 Layout`/`X11 Variant` lines of `localectl status`,
+/// which reads `n/a` when no X11 layout is set.
+pub fn parse_localectl(output: &str) -> Option<LayoutNames> {
+    Some(
+        LayoutNames::new(
+            value_of(output, "X11 Layout", ':').filter(|v| *v != "n/a")?,
+            value_of(output, "X11 Variant", ':').unwrap_or(""),
+        )?
+        .with_model_and_options(
+            value_of(output, "X11 Model", ':'),
+            value_of(output, "X11 Options", ':'),
+        ),
+    )
+}
+
+/// Parses `udevadm info --export-db`, taking the layouts of the first
+/// device with an `XKBLAYOUT` property.
+pub fn parse_udev_db(output: &str) -> Option<LayoutNames> {
+    output.split("\n\n").find_map(|device| {
+        let properties: String = device
+            .lines()
+            .filter_map(|line| line.strip_prefix("E: "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(
+            LayoutNames::new(
+                value_of(&properties, "XKBLAYOUT", '=')?,
+                value_of(&properties, "XKBVARIANT", '=').unwrap_or(""),
+            )?
+            .with_model_and_options(
+                value_of(&properties, "XKBMODEL", '='),
+                value_of(&properties, "XKBOPTIONS", '='),
+            ),
+        )
+    })
+}
+
+fn value_of<'a>(text: &'a str, key: &str, separator: char) -> Option<&'a str> {
+    text.lines().find_map(|line| {
+        let (name, value) = line.split_once(separator)?;
+        (name.trim() == key).then(|| value.trim())
+    })
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+#[cfg(test)]
+mod tests;
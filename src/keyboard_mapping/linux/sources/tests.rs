@@ -0,0 +1,192 @@
+use super::*;
+use crate::config::LayoutSpec;
+
+fn names(layout: &str, variant: &str) -> Option<LayoutNames> {
+    Some(LayoutNames {
+        layout: layout.to_string(),
+        variant: variant.to_string(),
+        ..LayoutNames::default()
+    })
+}
+
+fn with_rmlvo(
+    names: Option<LayoutNames>,
+    rules: &str,
+    model: &str,
+    options: &str,
+) -> Option<LayoutNames> {
+    names.map(|names| LayoutNames {
+        rules: rules.to_string(),
+        model: model.to_string(),
+        options: options.to_string(),
+        ..names
+    })
+}
+
+#[test]
+fn gnome_sources_split_variants_and_skip_input_methods() {
+    let output = "[('xkb', 'us'), ('xkb', 'us+dvorak'), ('ibus', 'mozc-jp'), ('xkb', 'il')]\n";
+    assert_eq!(parse_gnome_sources(output), names("us,us,il", ",dvorak,"));
+}
+
+#[test]
+fn gnome_sources_empty_list_is_none() {
+    assert_eq!(parse_gnome_sources("@a(ss) []\n"), None);
+}
+
+#[test]
+fn gnome_options_join_into_xkb_form() {
+    assert_eq!(
+        parse_gnome_options("['lv3:ralt_switch', 'compose:rctrl']\n"),
+        "lv3:ralt_switch,compose:rctrl"
+    );
+    assert_eq!(parse_gnome_options("@as []\n"), "");
+}
+
+#[test]
+fn kxkbrc_reads_layout_group_when_used() {
+    let data = "[$Version]\nupdate_info=kxkb.upd\n\n[Layout]\nDisplayNames=,\nLayoutList=us,il\nModel=pc104\nOptions=lv3:ralt_switch\nUse=true\nVariantList=,\n";
+    assert_eq!(
+        parse_kxkbrc(data),
+        with_rmlvo(names("us,il", ","), "", "pc104", "lv3:ralt_switch")
+    );
+}
+
+#[test]
+fn kxkbrc_ignored_when_kde_does_not_manage_layouts() {
+    let data = "[Layout]\nLayoutList=us,il\nUse=false\n";
+    assert_eq!(parse_kxkbrc(data), None);
+}
+
+#[test]
+fn xkb_rules_names_property() {
+    let output = "_XKB_RULES_NAMES(STRING) = \"evdev\", \"pc105\", \"us,ru\", \",phonetic\", \"grp:alt_shift_toggle\"\n";
+    assert_eq!(
+        parse_xkb_rules_names(output),
+        with_rmlvo(
+            names("us,ru", ",phonetic"),
+            "evdev",
+            "pc105",
+            "grp:alt_shift_toggle"
+        )
+    );
+}
+
+#[test]
+fn setxkbmap_query_output() {
+    let output = "rules:      evdev\nmodel:      pc105\nlayout:     us,il\nvariant:    ,\noptions:    grp:alt_shift_toggle\n";
+    assert_eq!(
+        parse_setxkbmap_query(output),
+        with_rmlvo(
+            names("us,il", ","),
+            "evdev",
+            "pc105",
+            "grp:alt_shift_toggle"
+        )
+    );
+}
+
+#[test]
+fn default_keyboard_file() {
+    let data = "# KEYBOARD CONFIGURATION FILE\nXKBMODEL=\"pc105\"\nXKBLAYOUT=\"us,il\"\nXKBVARIANT=\",\"\nXKBOPTIONS=\"grp:alt_shift_toggle\"\n";
+    assert_eq!(
+        parse_default_keyboard(data),
+        with_rmlvo(names("us,il", ","), "", "pc105", "grp:alt_shift_toggle")
+    );
+}
+
+#[test]
+fn localectl_status_output() {
+    let output = "   System Locale: LANG=en_US.UTF-8\n       VC Keymap: us\n      X11 Layout: us,de\n       X11 Model: pc105\n     X11 Variant: ,nodeadkeys\n";
+    assert_eq!(
+        parse_localectl(output),
+        with_rmlvo(names("us,de", ",nodeadkeys"), "", "pc105", "")
+    );
+}
+
+#[test]
+fn localectl_without_x11_layout_is_none() {
+    let output = "   System Locale: LANG=C\n       VC Keymap: n/a\n      X11 Layout: n/a\n";
+    assert_eq!(parse_localectl(output), None);
+    assert_eq!(parse_localectl("   System Locale: LANG=C\n"), None);
+}
+
+#[test]
+fn udev_db_takes_first_keyboard_with_layout() {
+    let output = "P: /devices/platform/i8042\nE: DEVPATH=/devices/platform/i8042\n\nP: /devices/platform/i8042/serio0/input/input3\nE: ID_INPUT_KEYBOARD=1\nE: XKBMODEL=pc105\nE: XKBLAYOUT=us,il\nE: XKBVARIANT=,\n\nP: /devices/usb\nE: XKBLAYOUT=de\n";
+    assert_eq!(
+        parse_udev_db(output),
+        with_rmlvo(names("us,il", ","), "", "pc105", "")
+    );
+}
+
+#[test]
+fn locale_source_is_left_to_the_caller() {
+    assert_eq!(read_layout_source(LayoutSource::Locale), None);
+}
+
+#[test]
+fn variant_at_skips_empty_entries() {
+    let names = names("us,il,ru", ",,phonetic").unwrap();
+    assert_eq!(names.variant_at(0), None);
+    assert_eq!(names.variant_at(2), Some("phonetic".to_string()));
+    assert_eq!(names.variant_at(3), None);
+}
+
+#[test]
+fn configured_layouts_replace_detected_ones() {
+    let mut cfg = Config::with_defaults();
+    cfg.layouts = vec![
+        "us(dvorak)".parse::<LayoutSpec>().unwrap(),
+        "il".parse().unwrap(),
+    ];
+    cfg.xkb_options = Some(vec!["lv3:ralt_switch".to_string()]);
+    let configured = layout_names_for_config(&cfg, |_| panic!("sources must not be read"));
+    assert_eq!(
+        Some(configured),
+        with_rmlvo(names("us,il", "dvorak,"), "", "", "lv3:ralt_switch")
+    );
+}
+
+#[test]
+fn configured_options_override_detected_ones() {
+    let mut cfg = Config::with_defaults();
+    cfg.xkb_model = Some("pc104".to_string());
+    let detected = with_rmlvo(
+        names("us,il", ","),
+        "evdev",
+        "pc105",
+        "grp:alt_shift_toggle",
+    );
+    let configured = layout_names_for_config(&cfg, |sources| {
+        assert_eq!(sources, LayoutSource::DEFAULT_ORDER);
+        detected.clone().unwrap()
+    });
+    assert_eq!(configured.model, "pc104");
+    assert_eq!(configured.options, "grp:alt_shift_toggle");
+    assert_eq!(configured.layout, "us,il");
+}
+
+#[test]
+fn batches_keep_variants_aligned_with_layouts() {
+    let names = LayoutNames {
+        options: "lv3:ralt_switch".to_string(),
+        ..names("us,il,ru,de,fr,gr", ",,phonetic,,,polytonic").unwrap()
+    };
+    let batches = names.batches(4);
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0].layout, "us,il,ru,de");
+    assert_eq!(batches[0].variant, ",,phonetic,");
+    assert_eq!(batches[1].layout, "fr,gr");
+    assert_eq!(batches[1].variant, ",polytonic");
+    assert!(batches.iter().all(|b| b.options == "lv3:ralt_switch"));
+}
+
+#[test]
+fn batches_pad_missing_variants() {
+    let batches = names("us,il", "").unwrap().batches(1);
+    assert_eq!(
+        batches,
+        vec![names("us", "").unwrap(), names("il", "").unwrap()]
+    );
+}
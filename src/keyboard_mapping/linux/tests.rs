@@ -1,4 +1,11 @@
 use super::*;
+use crate::keyboard_mapping::cache::set_cache_enabled;
+use crate::keyboard_mapping::remap::shift_text_language;
+use crate::test_utils::use_temp_cache_dir;
+
+fn names_or_skip(layout: &str) -> Option<LayoutMap> {
+    vk_to_char_map_for_names(layout, "").filter(|lm| !lm.map.is_empty())
+}
 
 #[test]
 fn test_list_layouts_returns_non_empty() {
@@ -57,21 +64,16 @@ fn test_vk_to_char_map_default_contains_common_keys() {
     let layout_map = vk_to_char_map_default();
 
     let common_keys = vec![
-        0x26, // KEY_A
-        0x38, // KEY_B
-        0x36, // KEY_C
-        0x39, // KEY_SPACE
-        0x1C, // KEY_ENTER
+        PhysicalKey::KeyA,
+        PhysicalKey::KeyB,
+        PhysicalKey::KeyC,
+        PhysicalKey::Space,
+        PhysicalKey::Enter,
     ];
 
     for key in common_keys {
-        if layout_map.map.contains_key(&key) {
-            let output = &layout_map.map[&key];
-            assert!(
-                !output.is_empty(),
-                "KEY_{:02X} should have non-empty output",
-                key
-            );
+        if let Some(output) = layout_map.map.get(&KeyStroke::base(key)) {
+            assert!(!output.is_empty(), "{} should have non-empty output", key);
         }
     }
 }
@@ -95,6 +97,7 @@ fn test_vk_to_char_map_for_layout_structure() {
 
 #[test]
 fn test_all_layout_vk_maps_structure() {
+    use_temp_cache_dir();
     let layout_maps = all_layout_vk_maps();
 
     assert!(
@@ -116,6 +119,7 @@ fn test_all_layout_vk_maps_structure() {
 
 #[test]
 fn test_all_layout_vk_maps_consistency() {
+    use_temp_cache_dir();
     let layouts = list_layouts();
     let layout_maps = all_layout_vk_maps();
 
@@ -138,3 +142,485 @@ fn test_all_layout_vk_maps_consistency() {
         }
     }
 }
+
+#[test]
+fn test_vk_to_char_map_default_records_shift_level() {
+    let layout_map = vk_to_char_map_default();
+    let base = layout_map.map.get(&KeyStroke::base(PhysicalKey::KeyA));
+    let shifted = layout_map
+        .map
+        .get(&KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Shift));
+
+    if let (Some(base), Some(shifted)) = (base, shifted) {
+        assert_ne!(base, shifted, "Shift level should differ from base level");
+    }
+}
+
+#[test]
+fn test_level_mask_shift_altgr_combines_modifiers() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    let context = Context::new(CONTEXT_NO_FLAGS);
+    let keymap = Keymap::new_from_names(
+        &context,
+        "",
+        "",
+        "us",
+        "",
+        None::<String>,
+        KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .expect("us keymap should compile");
+
+    let shift = level_mask(&keymap, ShiftLevel::Shift).unwrap();
+    let altgr = level_mask(&keymap, ShiftLevel::AltGr).unwrap();
+    assert_eq!(level_mask(&keymap, ShiftLevel::Base), Some(0));
+    assert_eq!(
+        level_mask(&keymap, ShiftLevel::ShiftAltGr),
+        Some(shift | altgr)
+    );
+}
+
+#[test]
+fn test_vk_to_char_map_for_names_records_dead_key_sequences() {
+    let Some(layout_map) = vk_to_char_map_for_names("us", "intl") else {
+        return;
+    };
+    if compose_table().is_none() {
+        return;
+    }
+    assert!(
+        layout_map.sequences.values().any(|s| s == "é"),
+        "us(intl) should compose é from a dead key"
+    );
+    assert!(
+        layout_map
+            .sequences
+            .keys()
+            .all(|strokes| strokes.len() == 2)
+    );
+}
+
+#[test]
+fn test_shift_text_language_pinned_us_to_il() {
+    let (Some(us), Some(il)) = (names_or_skip("us"), names_or_skip("il")) else {
+        return;
+    };
+    for _ in 0..5 {
+        assert_eq!(shift_text_language("akuo", &us, &il), "שלום");
+        // '/' and '.' also live on the keypad; the main block keys win.
+        assert_eq!(shift_text_language("a/b.", &us, &il), "ש.נץ");
+        assert_eq!(shift_text_language("123", &us, &il), "123");
+    }
+}
+
+#[test]
+fn test_shift_text_language_pinned_us_to_ru() {
+    let (Some(us), Some(ru)) = (names_or_skip("us"), names_or_skip("ru")) else {
+        return;
+    };
+    for _ in 0..5 {
+        assert_eq!(shift_text_language("ghbdtn", &us, &ru), "привет");
+        assert_eq!(shift_text_language("Ghbdtn!", &us, &ru), "Привет!");
+        assert_eq!(shift_text_language("руддщ", &ru, &us), "hello");
+    }
+}
+
+fn reset_work_counters() {
+    CONFIG_LOADS.with(|count| count.set(0));
+    REGISTRY_PARSES.with(|count| count.set(0));
+    KEYMAP_COMPILATIONS.with(|count| count.set(0));
+}
+
+fn work_counters() -> (usize, usize) {
+    (
+        REGISTRY_PARSES.with(Cell::get),
+        KEYMAP_COMPILATIONS.with(Cell::get),
+    )
+}
+
+#[test]
+fn test_build_all_layout_maps_compiles_keymap_once() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    let names = LayoutNames::new("us,il,ru", "").unwrap();
+    let registry = get_registry_from_xml();
+    reset_work_counters();
+    let maps = build_all_layout_maps(&names, &registry);
+
+    assert_eq!(maps.len(), 3);
+    assert_eq!(work_counters(), (0, 1));
+}
+
+// The layout names are given rather than detected, so the XKB path runs
+// whatever the session (console keymap, layout sources) looks like.
+fn compile_system_layouts() -> SystemLayouts {
+    let names = LayoutNames::new("us,il,ru", "").unwrap();
+    SystemLayouts::compile(&names, true, OnceCell::new())
+}
+
+#[test]
+fn test_list_layouts_compiles_keymap_once() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    reset_work_counters();
+    let layouts = compile_system_layouts().layouts();
+    assert_eq!(layouts.len(), 3);
+    assert_eq!(work_counters(), (1, 1));
+}
+
+#[test]
+fn test_vk_to_char_map_for_layout_compiles_keymap_once() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    reset_work_counters();
+    let layout_map = compile_system_layouts().layout_map(2).unwrap();
+    assert_eq!(layout_map.layout.id, "ru");
+    assert_eq!(work_counters(), (1, 1));
+}
+
+#[test]
+fn test_all_layout_vk_maps_loads_config_and_registry_once() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    set_cache_enabled(false);
+    reset_work_counters();
+    let maps = all_layout_vk_maps();
+    set_cache_enabled(true);
+
+    let (registry_parses, compilations) = work_counters();
+    assert_eq!(CONFIG_LOADS.with(Cell::get), 1);
+    assert!(registry_parses <= 1, "{} registry parses", registry_parses);
+    // One keymap per batch of four layouts; the console keymap and the
+    // built-in layouts compile none.
+    assert!(
+        compilations <= maps.len().div_ceil(MAX_GROUPS),
+        "{} keymaps for {} layouts",
+        compilations,
+        maps.len()
+    );
+}
+
+#[test]
+fn test_configured_layout_names_takes_first_reporting_source() {
+    reset_work_counters();
+    let registry = OnceCell::new();
+    let _ = configured_layout_names(&[LayoutSource::Locale], &registry);
+    assert_eq!(work_counters(), (1, 0));
+    assert!(registry.get().is_some());
+    // A source list without the locale guess never parses the registry.
+    reset_work_counters();
+    let registry = OnceCell::new();
+    let _ = configured_layout_names(&[LayoutSource::Kde, LayoutSource::Udev], &registry);
+    assert_eq!(work_counters().0, 0);
+    assert!(registry.get().is_none());
+    assert_eq!(configured_layout_names(&[], &OnceCell::new()), None);
+}
+
+#[test]
+fn test_layout_cache_key_tracks_layout_names() {
+    let us = LayoutNames::new("us", "").unwrap();
+    let dvorak = LayoutNames::new("us", "dvorak").unwrap();
+    assert_ne!(layout_cache_key(&us), layout_cache_key(&dvorak));
+}
+
+#[test]
+fn test_build_all_layout_maps_honors_variants() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    let names = LayoutNames {
+        layout: "us,us".to_string(),
+        variant: ",dvorak".to_string(),
+        ..LayoutNames::default()
+    };
+    let maps = build_all_layout_maps(&names, &XkbConfigRegistry::default());
+    assert_eq!(maps.len(), 2);
+    assert_eq!(maps[0].layout.variant, None);
+    assert_eq!(maps[1].layout.variant.as_deref(), Some("dvorak"));
+    // The key right of Tab types "q" on QWERTY and "'" on Dvorak.
+    assert_eq!(
+        maps[0].output(PhysicalKey::KeyQ, ShiftLevel::Base),
+        Some("q")
+    );
+    assert_eq!(
+        maps[1].output(PhysicalKey::KeyQ, ShiftLevel::Base),
+        Some("'")
+    );
+}
+
+#[test]
+fn test_compose_dir_entry() {
+    let listing = "\
+# comment: C
+iso8859-1/Compose:\t\ten_US.ISO8859-1
+en_US.UTF-8/Compose:\t\ten_US.UTF-8
+en_US.UTF-8/Compose:\t\tde_DE.UTF-8
+";
+    assert_eq!(
+        compose_dir_entry(listing, "en_US.utf8"),
+        Some("en_US.UTF-8/Compose")
+    );
+    assert_eq!(
+        compose_dir_entry(listing, "de_DE.UTF-8"),
+        Some("en_US.UTF-8/Compose")
+    );
+    assert_eq!(compose_dir_entry(listing, "C"), None);
+}
+
+#[test]
+fn test_layout_cache_key_tracks_options() {
+    let plain = LayoutNames::new("us", "").unwrap();
+    let ralt = LayoutNames {
+        options: "lv3:ralt_switch".to_string(),
+        ..plain.clone()
+    };
+    assert_ne!(layout_cache_key(&plain), layout_cache_key(&ralt));
+}
+
+#[test]
+fn test_build_all_layout_maps_beyond_four_groups() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    let names = LayoutNames::new("us,il,ru,de,fr,gr", "").unwrap();
+    reset_work_counters();
+    let maps = build_all_layout_maps(&names, &XkbConfigRegistry::default());
+    assert_eq!(work_counters(), (0, 2), "six layouts take two keymaps");
+
+    let codes: Vec<Option<&str>> = maps.iter().map(|m| m.layout.language_code()).collect();
+    assert_eq!(
+        codes,
+        [
+            Some("en"),
+            Some("he"),
+            Some("ru"),
+            Some("de"),
+            Some("fr"),
+            Some("el")
+        ]
+    );
+    // The fifth and sixth layouts type their own scripts, not the first batch's.
+    assert_eq!(
+        maps[5].output(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("α")
+    );
+}
+
+#[test]
+fn test_broken_layout_does_not_drop_its_batch() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    let names = LayoutNames::new("us,no_such_layout,ru", "").unwrap();
+    reset_work_counters();
+    let maps = build_all_layout_maps(&names, &XkbConfigRegistry::default());
+    let ids: Vec<&str> = maps.iter().map(|m| m.layout.id.as_str()).collect();
+    // The broken layout keeps its place, so `ru` stays at index 2.
+    assert_eq!(ids, ["us", "no_such_layout", "ru"]);
+    assert!(maps[1].map.is_empty());
+    assert_eq!(
+        maps[2].output(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("ф")
+    );
+    // The failed batch, then each of its layouts on its own.
+    assert_eq!(work_counters(), (0, 4));
+}
+
+#[test]
+fn test_broken_layout_keeps_layout_indices() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    let names = LayoutNames::new("us,no_such_layout,ru", "").unwrap();
+    let system = SystemLayouts::compile(&names, true, OnceCell::new());
+    let ids: Vec<String> = system.layouts().into_iter().map(|l| l.id).collect();
+    assert_eq!(ids, ["us", "no_such_layout", "ru"]);
+    assert_eq!(system.layout(2).unwrap().id, "ru");
+    assert_eq!(system.layout_map(2).unwrap().layout.id, "ru");
+    assert!(system.layout_map(1).unwrap().map.is_empty());
+}
+
+#[test]
+fn test_system_layouts_without_keymaps_use_builtin_layouts() {
+    // Nothing compiles and the chosen layout isn't built in either, so no
+    // built-in layout the user never chose stands in.
+    let names = LayoutNames::new("no_such_layout", "").unwrap();
+    let layouts = SystemLayouts::compile(&names, true, OnceCell::new()).layouts();
+    assert!(layouts.is_empty(), "{:?}", layouts);
+    let maps = build_all_layout_maps(&names, &XkbConfigRegistry::default());
+    assert!(maps.iter().all(|map| map.map.is_empty()));
+    let all = builtin_layout_maps_for(&[]).len();
+    let unchosen = SystemLayouts::compile(&names, false, OnceCell::new()).layouts();
+    assert_eq!(unchosen.len(), all);
+}
+
+#[test]
+fn test_builtin_maps_follow_chosen_layout_names() {
+    let names = LayoutNames::new("il, ru", "").unwrap();
+    let ids: Vec<String> = builtin_maps(Some(&names))
+        .into_iter()
+        .map(|m| m.layout.id)
+        .collect();
+    assert_eq!(ids, ["il", "ru"]);
+}
+
+#[test]
+fn test_builtin_maps_without_chosen_layouts_are_all_builtin_layouts() {
+    // Nothing configured or detected: the names are the spelled-out `us`
+    // default, which must not narrow the built-in layouts down to one.
+    let all = builtin_layout_maps_for(&[]);
+    assert!(all.len() > 1);
+    assert_eq!(builtin_maps(None), all);
+    let us = LayoutNames::new("us", "").unwrap();
+    assert_eq!(builtin_maps(Some(&us)).len(), 1);
+}
+
+#[test]
+fn test_layouts_carry_registry_names_and_languages() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    let names = LayoutNames {
+        layout: "il,us".to_string(),
+        variant: "phonetic,".to_string(),
+        ..LayoutNames::default()
+    };
+    let maps = build_all_layout_maps(&names, &get_registry_from_xml());
+    assert_eq!(maps[0].layout.id, "il(phonetic)");
+    assert_eq!(maps[0].layout.lang_name, "Hebrew (phonetic)");
+    assert_eq!(maps[0].layout.language.as_deref(), Some("he"));
+    assert_eq!(maps[0].layout.script.as_deref(), Some("Hebr"));
+    assert_eq!(maps[0].layout.language_code(), Some("he"));
+    assert_eq!(maps[1].layout.id, "us");
+    assert_eq!(maps[1].layout.lang_name, "English (US)");
+    assert_eq!(maps[1].layout.language.as_deref(), Some("en"));
+    assert_eq!(maps[1].layout.script.as_deref(), Some("Latn"));
+}
+
+#[test]
+fn test_xkb_data_paths_follow_config_root() {
+    let names = LayoutNames::new("us, il(phonetic)", "").unwrap();
+    let paths = xkb_data_paths(&names);
+    let root = xkb_config_root();
+    assert!(paths.contains(&root.join("rules").join("evdev.extras.xml")));
+    assert!(paths.contains(&root.join("symbols")));
+    // The files read for the layouts are stamped themselves.
+    for file in ["rules/evdev", "rules/evdev.xml", "symbols/us", "symbols/il"] {
+        assert!(paths.contains(&root.join(file)), "{} in {:?}", file, paths);
+    }
+    assert!(paths.contains(&PathBuf::from(COMPOSE_DIR_PATH)));
+}
+
+const SYMBOLS_SNIPPET: &str = r#"// Hand-written layout
+default partial alphanumeric_keys
+xkb_symbols "test" {
+    include "us(basic)"
+    name[Group1]= "Hebrew (test)";
+    key <AC01> { [ hebrew_shin, A ] }; // { braces in comments }
+    key <AD03> { [ e, E, EuroSign ] };
+};
+"#;
+
+#[test]
+fn test_layout_maps_from_xkb_symbols_snippet() {
+    if names_or_skip("us").is_none() {
+        return;
+    }
+    let maps = layout_maps_from_xkb(SYMBOLS_SNIPPET, "hebtest").unwrap();
+    assert_eq!(maps.len(), 1);
+    let layout_map = &maps[0];
+    assert_eq!(layout_map.layout.id, "hebtest");
+    assert_eq!(layout_map.layout.lang_name, "Hebrew (test)");
+    assert_eq!(layout_map.layout.language.as_deref(), Some("he"));
+    assert_eq!(layout_map.layout.direction, KeyboardDirection::RTL);
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("ש")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyQ, ShiftLevel::Base),
+        Some("q")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyE, ShiftLevel::AltGr),
+        Some("€")
+    );
+}
+
+#[test]
+fn test_layout_maps_from_xkb_keymap_has_one_layout_per_group() {
+    let names = LayoutNames::new("us,il", "").unwrap();
+    let Some(keymap) = get_keymap(&names) else {
+        return;
+    };
+    let text = keymap.get_as_string(KEYMAP_FORMAT_TEXT_V1);
+    let maps = layout_maps_from_xkb(&text, "custom").unwrap();
+    let ids: Vec<&str> = maps.iter().map(|m| m.layout.id.as_str()).collect();
+    assert_eq!(ids, ["custom:1", "custom:2"]);
+    assert_eq!(
+        maps[1].output(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("ש")
+    );
+}
+
+#[test]
+fn test_layout_maps_from_xkb_errors() {
+    assert_eq!(
+        layout_maps_from_xkb("key <AC01> { [ a ] };", "x").unwrap_err(),
+        "Expected an xkb_keymap or xkb_symbols block"
+    );
+    assert_eq!(
+        layout_maps_from_xkb("xkb_symbols \"x\" { key", "x").unwrap_err(),
+        "Unterminated xkb_symbols block"
+    );
+    assert_eq!(
+        layout_maps_from_xkb("xkb_keymap { nonsense };", "x").unwrap_err(),
+        "Failed to compile the XKB keymap"
+    );
+}
+
+#[test]
+fn test_is_virtual_terminal() {
+    assert!(is_virtual_terminal(Path::new("/dev/tty1")));
+    assert!(is_virtual_terminal(Path::new("/dev/tty12")));
+    assert!(!is_virtual_terminal(Path::new("/dev/tty")));
+    assert!(!is_virtual_terminal(Path::new("/dev/ttyS0")));
+    assert!(!is_virtual_terminal(Path::new("/dev/pts/3")));
+    assert!(!is_virtual_terminal(Path::new("/dev/null")));
+}
+
+#[test]
+fn test_uses_console_keymap_only_on_a_virtual_terminal_by_default() {
+    let cfg = Config::with_defaults();
+    assert!(uses_console_keymap(&cfg, true, |_| true));
+    // No graphical session is not enough: SSH, cron and services keep XKB.
+    assert!(!uses_console_keymap(&cfg, false, |_| false));
+
+    let explicit = Config {
+        layouts: vec!["us".parse().unwrap()],
+        ..Config::with_defaults()
+    };
+    assert!(!uses_console_keymap(&explicit, true, |_| false));
+}
+
+#[test]
+fn test_uses_console_keymap_when_listed_as_a_source() {
+    let cfg = Config {
+        layout_sources: vec![LayoutSource::Gnome, LayoutSource::Console],
+        ..Config::with_defaults()
+    };
+    let mut asked = Vec::new();
+    assert!(uses_console_keymap(&cfg, false, |sources| {
+        asked = sources.to_vec();
+        false
+    }));
+    assert_eq!(asked, [LayoutSource::Gnome]);
+    // A source listed before it that reports layouts wins.
+    assert!(!uses_console_keymap(&cfg, true, |_| true));
+}
@@ -1,24 +1,48 @@
 #![allow(unused)]
+pub mod builtin;
+pub mod cache;
+pub mod caps;
+pub mod custom;
+pub mod dictionary;
 pub mod fallback;
+pub mod import;
 pub mod linux;
+pub mod ngram;
+pub mod physical_key;
 pub mod remap;
+pub mod transliterate;
 pub mod types;
 pub mod windows;
-pub use types::{KeyboardDirection, KeyboardLayout, LayoutMap};
+pub use types::{
+    KeyClass, KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, LayoutSelector, ShiftLevel,
+};
 
 #[cfg(target_os = "windows")]
 pub use windows::{
-    all_layout_vk_maps, get_layout, list_layouts, vk_to_char_map_default, vk_to_char_map_for_layout,
+    all_layout_vk_maps, all_layout_vk_maps_for, get_layout, list_layouts, vk_to_char_map_default,
+    vk_to_char_map_for_layout,
 };
 
 #[cfg(target_os = "linux")]
 pub use linux::{
-    all_layout_vk_maps, get_layout, list_layouts, vk_to_char_map_default, vk_to_char_map_for_layout,
+    all_layout_vk_maps, all_layout_vk_maps_for, get_layout, list_layouts, vk_to_char_map_default,
+    vk_to_char_map_for_layout,
 };
 
 #[cfg(not(any(target_os = "windows", target_os = "linux")))]
 pub use fallback::{
-    all_layout_vk_maps, get_layout, list_layouts, vk_to_char_map_default, vk_to_char_map_for_layout,
+    all_layout_vk_maps, all_layout_vk_maps_for, get_layout, list_layouts, vk_to_char_map_default,
+    vk_to_char_map_for_layout,
 };
 
-pub use remap::{get_text_leyaout_map, shift_text_language};
+pub use caps::{invert_case, is_caps_inverted, toggle_caps_levels};
+pub use dictionary::{Dictionaries, Dictionary};
+pub use ngram::{LanguageModel, LanguageModels};
+pub use physical_key::PhysicalKey;
+pub use remap::{
+    best_target_layout, get_text_leyaout_map, get_text_leyaout_map_with_models, select_layout,
+    shift_text_between, shift_text_between_layouts, shift_text_language,
+};
+pub use transliterate::{
+    TransliterationScheme, source_language, transliterate, transliterate_between,
+};
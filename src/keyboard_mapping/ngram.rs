@@ -0,0 +1,205 @@
+use super::types::KeyboardLayout;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const BOUNDARY: char = '_';
+
+const BUILTIN_TABLES: &[(&str, &str)] = &[
+    ("en", include_str!("ngram/data/en.txt")),
+    ("de", include_str!("ngram/data/de.txt")),
+    ("fr", include_str!("ngram/data/fr.txt")),
+    ("es", include_str!("ngram/data/es.txt")),
+    ("ru", include_str!("ngram/data/ru.txt")),
+    ("uk", include_str!("ngram/data/uk.txt")),
+    ("be", include_str!("ngram/data/be.txt")),
+    ("he", include_str!("ngram/data/he.txt")),
+    ("ar", include_str!("ngram/data/ar.txt")),
+];
+
+/// Character bigram/trigram log-probabilities for one language.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageModel {
+    bigrams: HashMap<String, f64>,
+    trigrams: HashMap<String, f64>,
+    bigram_floor: f64,
+    trigram_floor: f64,
+}
+
+impl LanguageModel {
+    /// Parses a table of `<ngram> <count>` lines. `_` stands for a word
+    /// boundary and lines starting with `#` are comments.
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let mut bigram_counts: HashMap<String, f64> = HashMap::new();
+        let mut trigram_counts: HashMap<String, f64> = HashMap::new();
+        for (line_no, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let (Some(ngram), Some(count), None) = (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(format!("Invalid n-gram entry on line {}", line_no + 1));
+            };
+            let count: f64 = count
+                .parse()
+                .ok()
+                .filter(|c: &f64| *c > 0.0)
+                .ok_or_else(|| format!("Invalid n-gram count on line {}", line_no + 1))?;
+            let ngram = ngram.to_lowercase();
+            let table = match ngram.chars().count() {
+                2 => &mut bigram_counts,
+                3 => &mut trigram_counts,
+                _ => {
+                    return Err(format!(
+                        "Only bigrams and trigrams are supported (line {})",
+                        line_no + 1
+                    ));
+                }
+            };
+            *table.entry(ngram).or_insert(0.0) += count;
+        }
+        if bigram_counts.is_empty() && trigram_counts.is_empty() {
+            return Err("N-gram table is empty".to_string());
+        }
+        let (bigrams, bigram_floor) = to_log_probabilities(bigram_counts);
+        let (trigrams, trigram_floor) = to_log_probabilities(trigram_counts);
+        Ok(LanguageModel {
+            bigrams,
+            trigrams,
+            bigram_floor,
+            trigram_floor,
+        })
+    }
+
+    /// Average log-probability per n-gram of `text`, or `None` when the text
+    /// has no letters to score.
+    pub fn score(&self, text: &str) -> Option<f64> {
+        let normalized = normalize(text)?;
+        let mut total = 0.0;
+        let mut count = 0usize;
+        for window in normalized.windows(2) {
+            let key: String = window.iter().collect();
+            total += self.bigrams.get(&key).copied().unwrap_or(self.bigram_floor);
+            count += 1;
+        }
+        for window in normalized.windows(3) {
+            let key: String = window.iter().collect();
+            total += self
+                .trigrams
+                .get(&key)
+                .copied()
+                .unwrap_or(self.trigram_floor);
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some(total / count as f64)
+    }
+}
+
+fn to_log_probabilities(counts: HashMap<String, f64>) -> (HashMap<String, f64>, f64) {
+    let total: f64 = counts.values().sum::<f64>().max(1.0);
+    let floor = (0.5 / total).ln();
+    let probs = counts
+        .into_iter()
+        .map(|(ngram, count)| (ngram, (count / total).ln()))
+        .collect();
+    (probs, floor)
+}
+
+fn normalize(text: &str) -> Option<Vec<char>> {
+    let mut out = vec![BOUNDARY];
+    let mut letters = 0usize;
+    for ch in text.chars().flat_map(char::to_lowercase) {
+        if ch.is_alphabetic() {
+            out.push(ch);
+            letters += 1;
+        } else if out.last() != Some(&BOUNDARY) {
+            out.push(BOUNDARY);
+        }
+    }
+    if letters == 0 {
+        return None;
+    }
+    if out.last() != Some(&BOUNDARY) {
+        out.push(BOUNDARY);
+    }
+    Some(out)
+}
+
+/// Language models keyed by ISO 639-1 code.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LanguageModels {
+    models: HashMap<String, LanguageModel>,
+}
+
+impl LanguageModels {
+    /// The compiled-in tables. Every table parses, which the tests check;
+    /// one that didn't would be left out.
+    pub fn builtin() -> Self {
+        let models = BUILTIN_TABLES
+            .iter()
+            .filter_map(|(code, data)| {
+                let model = LanguageModel::parse(data).ok()?;
+                Some((code.to_string(), model))
+            })
+            .collect();
+        LanguageModels { models }
+    }
+
+    /// Built-in tables extended (or overridden) by `<data dir>/ngrams/<code>.txt`.
+    pub fn shared() -> &'static LanguageModels {
+        static MODELS: OnceLock<LanguageModels> = OnceLock::new();
+        MODELS.get_or_init(|| {
+            let mut models = LanguageModels::builtin();
+            if let Ok(dir) = crate::config::data_dir() {
+                let _ = models.load_dir(&dir.join("ngrams"));
+            }
+            models
+        })
+    }
+
+    /// Loads every `<code>.txt` table in `dir`, replacing models for the
+    /// same language. A missing directory is not an error.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<(), String> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+                continue;
+            }
+            let Some(code) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let data = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let model =
+                LanguageModel::parse(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+            self.insert(code, model);
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, code: &str, model: LanguageModel) {
+        self.models.insert(code.to_lowercase(), model);
+    }
+
+    pub fn get(&self, code: &str) -> Option<&LanguageModel> {
+        self.models.get(&code.to_lowercase())
+    }
+
+    /// How plausible `text` is in the language typed by `layout`.
+    pub fn plausibility(&self, text: &str, layout: &KeyboardLayout) -> Option<f64> {
+        self.get(layout.language_code()?)?.score(text)
+    }
+}
+
+#[cfg(test)]
+mod tests;
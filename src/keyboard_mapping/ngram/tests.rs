@@ -0,0 +1,107 @@
+use super::*;
+use crate::keyboard_mapping::types::KeyboardDirection;
+
+fn layout(name: &str) -> KeyboardLayout {
+    KeyboardLayout {
+        id: name.to_string(),
+        lang_name: name.to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
+    }
+}
+
+#[test]
+fn test_builtin_tables_parse() {
+    for (code, data) in BUILTIN_TABLES {
+        if let Err(e) = LanguageModel::parse(data) {
+            panic!("built-in n-gram table {}: {}", code, e);
+        }
+    }
+    let models = LanguageModels::builtin();
+    for (code, _) in BUILTIN_TABLES {
+        assert!(models.get(code).is_some(), "missing model for {}", code);
+    }
+}
+
+#[test]
+fn test_parse_rejects_invalid_lines() {
+    assert!(LanguageModel::parse("th").is_err());
+    assert!(LanguageModel::parse("th abc").is_err());
+    assert!(LanguageModel::parse("thea 3").is_err());
+    assert!(LanguageModel::parse("# only a comment").is_err());
+}
+
+#[test]
+fn test_parse_ignores_comments_and_blank_lines() {
+    let model = LanguageModel::parse("# header\n\nth 10\nthe 5\n").unwrap();
+    assert_eq!(model.bigrams.len(), 1);
+    assert_eq!(model.trigrams.len(), 1);
+}
+
+#[test]
+fn test_score_none_without_letters() {
+    let models = LanguageModels::builtin();
+    let en = models.get("en").unwrap();
+    assert_eq!(en.score("123 !?"), None);
+    assert_eq!(en.score(""), None);
+}
+
+#[test]
+fn test_score_prefers_matching_language() {
+    let models = LanguageModels::builtin();
+    let en = models.get("en").unwrap();
+    let de = models.get("de").unwrap();
+
+    let english = "the weather is nice and the house is there";
+    let german = "ich denke dass die schule und der zug nicht gehen";
+    assert!(en.score(english).unwrap() > de.score(english).unwrap());
+    assert!(de.score(german).unwrap() > en.score(german).unwrap());
+}
+
+#[test]
+fn test_score_separates_cyrillic_languages() {
+    let models = LanguageModels::builtin();
+    let ru = models.get("ru").unwrap();
+    let uk = models.get("uk").unwrap();
+
+    let russian = "это не то что было на столе";
+    let ukrainian = "він пішов до своєї країни";
+    assert!(ru.score(russian).unwrap() > uk.score(russian).unwrap());
+    assert!(uk.score(ukrainian).unwrap() > ru.score(ukrainian).unwrap());
+}
+
+#[test]
+fn test_plausibility_uses_layout_language() {
+    let models = LanguageModels::builtin();
+    assert!(
+        models
+            .plausibility("hello", &layout("English (US)"))
+            .is_some()
+    );
+    assert!(models.plausibility("hello", &layout("Klingon")).is_none());
+}
+
+#[test]
+fn test_load_dir_overrides_builtin() {
+    let dir = crate::test_utils::temp_dir("ngrams");
+    let _guard = crate::test_utils::TempDirGuard::new(dir.clone());
+    fs::write(dir.join("tlh.txt"), "qa 10\n_q 5\n").unwrap();
+    fs::write(dir.join("notes.md"), "ignored").unwrap();
+
+    let mut models = LanguageModels::builtin();
+    models.load_dir(&dir).unwrap();
+    assert!(models.get("tlh").is_some());
+    assert!(models.get("en").is_some());
+}
+
+#[test]
+fn test_load_dir_missing_is_ok() {
+    let mut models = LanguageModels::default();
+    assert!(
+        models
+            .load_dir(Path::new("/nonexistent/kms/ngrams"))
+            .is_ok()
+    );
+}
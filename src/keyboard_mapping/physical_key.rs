@@ -0,0 +1,423 @@
+use super::types::KeyClass;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A key by its position on the keyboard, named as in the W3C UI Events
+/// `KeyboardEvent.code` table. `KeyQ` is the key right of Tab whatever the
+/// layout types with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PhysicalKey {
+    Backquote,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    Digit0,
+    Minus,
+    Equal,
+    /// The key left of Backspace on JIS keyboards (¥).
+    IntlYen,
+    KeyQ,
+    KeyW,
+    KeyE,
+    KeyR,
+    KeyT,
+    KeyY,
+    KeyU,
+    KeyI,
+    KeyO,
+    KeyP,
+    BracketLeft,
+    BracketRight,
+    KeyA,
+    KeyS,
+    KeyD,
+    KeyF,
+    KeyG,
+    KeyH,
+    KeyJ,
+    KeyK,
+    KeyL,
+    Semicolon,
+    Quote,
+    /// Above Enter on ANSI keyboards, left of Enter on ISO ones.
+    Backslash,
+    /// The key right of Left Shift on ISO keyboards.
+    IntlBackslash,
+    KeyZ,
+    KeyX,
+    KeyC,
+    KeyV,
+    KeyB,
+    KeyN,
+    KeyM,
+    Comma,
+    Period,
+    Slash,
+    /// The key left of Right Shift on JIS keyboards (ろ).
+    IntlRo,
+    Space,
+    Backspace,
+    Tab,
+    Enter,
+    Escape,
+    Delete,
+    CapsLock,
+    ShiftLeft,
+    ShiftRight,
+    ControlLeft,
+    ControlRight,
+    AltLeft,
+    AltRight,
+    MetaLeft,
+    MetaRight,
+    ContextMenu,
+    /// 無変換 on JIS keyboards.
+    NonConvert,
+    /// 変換 on JIS keyboards.
+    Convert,
+    /// カタカナ/ひらがな on JIS keyboards.
+    KanaMode,
+    /// Hangul/English toggle on Korean keyboards.
+    Lang1,
+    /// Hanja conversion on Korean keyboards.
+    Lang2,
+    NumLock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadComma,
+    NumpadEqual,
+    NumpadEnter,
+}
+
+use PhysicalKey::*;
+
+// Every key with its code name, Linux evdev code and PC scan code (set 1,
+// extended keys prefixed with 0xE0).
+const KEYS: &[(PhysicalKey, &str, u16, u16)] = &[
+    (Backquote, "Backquote", 41, 0x29),
+    (Digit1, "Digit1", 2, 0x02),
+    (Digit2, "Digit2", 3, 0x03),
+    (Digit3, "Digit3", 4, 0x04),
+    (Digit4, "Digit4", 5, 0x05),
+    (Digit5, "Digit5", 6, 0x06),
+    (Digit6, "Digit6", 7, 0x07),
+    (Digit7, "Digit7", 8, 0x08),
+    (Digit8, "Digit8", 9, 0x09),
+    (Digit9, "Digit9", 10, 0x0A),
+    (Digit0, "Digit0", 11, 0x0B),
+    (Minus, "Minus", 12, 0x0C),
+    (Equal, "Equal", 13, 0x0D),
+    (IntlYen, "IntlYen", 124, 0x7D),
+    (KeyQ, "KeyQ", 16, 0x10),
+    (KeyW, "KeyW", 17, 0x11),
+    (KeyE, "KeyE", 18, 0x12),
+    (KeyR, "KeyR", 19, 0x13),
+    (KeyT, "KeyT", 20, 0x14),
+    (KeyY, "KeyY", 21, 0x15),
+    (KeyU, "KeyU", 22, 0x16),
+    (KeyI, "KeyI", 23, 0x17),
+    (KeyO, "KeyO", 24, 0x18),
+    (KeyP, "KeyP", 25, 0x19),
+    (BracketLeft, "BracketLeft", 26, 0x1A),
+    (BracketRight, "BracketRight", 27, 0x1B),
+    (KeyA, "KeyA", 30, 0x1E),
+    (KeyS, "KeyS", 31, 0x1F),
+    (KeyD, "KeyD", 32, 0x20),
+    (KeyF, "KeyF", 33, 0x21),
+    (KeyG, "KeyG", 34, 0x22),
+    (KeyH, "KeyH", 35, 0x23),
+    (KeyJ, "KeyJ", 36, 0x24),
+    (KeyK, "KeyK", 37, 0x25),
+    (KeyL, "KeyL", 38, 0x26),
+    (Semicolon, "Semicolon", 39, 0x27),
+    (Quote, "Quote", 40, 0x28),
+    (Backslash, "Backslash", 43, 0x2B),
+    (IntlBackslash, "IntlBackslash", 86, 0x56),
+    (KeyZ, "KeyZ", 44, 0x2C),
+    (KeyX, "KeyX", 45, 0x2D),
+    (KeyC, "KeyC", 46, 0x2E),
+    (KeyV, "KeyV", 47, 0x2F),
+    (KeyB, "KeyB", 48, 0x30),
+    (KeyN, "KeyN", 49, 0x31),
+    (KeyM, "KeyM", 50, 0x32),
+    (Comma, "Comma", 51, 0x33),
+    (Period, "Period", 52, 0x34),
+    (Slash, "Slash", 53, 0x35),
+    (IntlRo, "IntlRo", 89, 0x73),
+    (Space, "Space", 57, 0x39),
+    (Backspace, "Backspace", 14, 0x0E),
+    (Tab, "Tab", 15, 0x0F),
+    (Enter, "Enter", 28, 0x1C),
+    (Escape, "Escape", 1, 0x01),
+    (Delete, "Delete", 111, 0xE053),
+    (CapsLock, "CapsLock", 58, 0x3A),
+    (ShiftLeft, "ShiftLeft", 42, 0x2A),
+    (ShiftRight, "ShiftRight", 54, 0x36),
+    (ControlLeft, "ControlLeft", 29, 0x1D),
+    (ControlRight, "ControlRight", 97, 0xE01D),
+    (AltLeft, "AltLeft", 56, 0x38),
+    (AltRight, "AltRight", 100, 0xE038),
+    (MetaLeft, "MetaLeft", 125, 0xE05B),
+    (MetaRight, "MetaRight", 126, 0xE05C),
+    (ContextMenu, "ContextMenu", 127, 0xE05D),
+    (NonConvert, "NonConvert", 94, 0x7B),
+    (Convert, "Convert", 92, 0x79),
+    (KanaMode, "KanaMode", 93, 0x70),
+    (Lang1, "Lang1", 122, 0x72),
+    (Lang2, "Lang2", 123, 0x71),
+    (NumLock, "NumLock", 69, 0xE045),
+    (Numpad0, "Numpad0", 82, 0x52),
+    (Numpad1, "Numpad1", 79, 0x4F),
+    (Numpad2, "Numpad2", 80, 0x50),
+    (Numpad3, "Numpad3", 81, 0x51),
+    (Numpad4, "Numpad4", 75, 0x4B),
+    (Numpad5, "Numpad5", 76, 0x4C),
+    (Numpad6, "Numpad6", 77, 0x4D),
+    (Numpad7, "Numpad7", 71, 0x47),
+    (Numpad8, "Numpad8", 72, 0x48),
+    (Numpad9, "Numpad9", 73, 0x49),
+    (NumpadAdd, "NumpadAdd", 78, 0x4E),
+    (NumpadSubtract, "NumpadSubtract", 74, 0x4A),
+    (NumpadMultiply, "NumpadMultiply", 55, 0x37),
+    (NumpadDivide, "NumpadDivide", 98, 0xE035),
+    (NumpadDecimal, "NumpadDecimal", 83, 0x53),
+    (NumpadComma, "NumpadComma", 121, 0x7E),
+    (NumpadEqual, "NumpadEqual", 117, 0x59),
+    (NumpadEnter, "NumpadEnter", 96, 0xE01C),
+];
+
+// macOS virtual key codes (`kVK_*`). Keys missing on Mac keyboards, such as
+// Convert, are not listed.
+const MAC_KEYCODES: &[(PhysicalKey, u16)] = &[
+    (KeyA, 0x00),
+    (KeyS, 0x01),
+    (KeyD, 0x02),
+    (KeyF, 0x03),
+    (KeyH, 0x04),
+    (KeyG, 0x05),
+    (KeyZ, 0x06),
+    (KeyX, 0x07),
+    (KeyC, 0x08),
+    (KeyV, 0x09),
+    (IntlBackslash, 0x0A),
+    (KeyB, 0x0B),
+    (KeyQ, 0x0C),
+    (KeyW, 0x0D),
+    (KeyE, 0x0E),
+    (KeyR, 0x0F),
+    (KeyY, 0x10),
+    (KeyT, 0x11),
+    (Digit1, 0x12),
+    (Digit2, 0x13),
+    (Digit3, 0x14),
+    (Digit4, 0x15),
+    (Digit6, 0x16),
+    (Digit5, 0x17),
+    (Equal, 0x18),
+    (Digit9, 0x19),
+    (Digit7, 0x1A),
+    (Minus, 0x1B),
+    (Digit8, 0x1C),
+    (Digit0, 0x1D),
+    (BracketRight, 0x1E),
+    (KeyO, 0x1F),
+    (KeyU, 0x20),
+    (BracketLeft, 0x21),
+    (KeyI, 0x22),
+    (KeyP, 0x23),
+    (Enter, 0x24),
+    (KeyL, 0x25),
+    (KeyJ, 0x26),
+    (Quote, 0x27),
+    (KeyK, 0x28),
+    (Semicolon, 0x29),
+    (Backslash, 0x2A),
+    (Comma, 0x2B),
+    (Slash, 0x2C),
+    (KeyN, 0x2D),
+    (KeyM, 0x2E),
+    (Period, 0x2F),
+    (Tab, 0x30),
+    (Space, 0x31),
+    (Backquote, 0x32),
+    (Backspace, 0x33),
+    (Escape, 0x35),
+    (MetaRight, 0x36),
+    (MetaLeft, 0x37),
+    (ShiftLeft, 0x38),
+    (CapsLock, 0x39),
+    (AltLeft, 0x3A),
+    (ControlLeft, 0x3B),
+    (ShiftRight, 0x3C),
+    (AltRight, 0x3D),
+    (ControlRight, 0x3E),
+    (NumpadDecimal, 0x41),
+    (NumpadMultiply, 0x43),
+    (NumpadAdd, 0x45),
+    (NumLock, 0x47),
+    (NumpadDivide, 0x4B),
+    (NumpadEnter, 0x4C),
+    (NumpadSubtract, 0x4E),
+    (NumpadEqual, 0x51),
+    (Numpad0, 0x52),
+    (Numpad1, 0x53),
+    (Numpad2, 0x54),
+    (Numpad3, 0x55),
+    (Numpad4, 0x56),
+    (Numpad5, 0x57),
+    (Numpad6, 0x58),
+    (Numpad7, 0x59),
+    (Numpad8, 0x5B),
+    (Numpad9, 0x5C),
+    (IntlYen, 0x5D),
+    (IntlRo, 0x5E),
+    (NumpadComma, 0x5F),
+    (Lang2, 0x66),
+    (Lang1, 0x68),
+    (ContextMenu, 0x6E),
+    (Delete, 0x75),
+];
+
+// XKB keycodes are evdev codes shifted by 8.
+const XKB_KEYCODE_OFFSET: u16 = 8;
+
+impl PhysicalKey {
+    pub fn all() -> impl Iterator<Item = PhysicalKey> {
+        KEYS.iter().map(|(key, ..)| *key)
+    }
+
+    fn entry(self) -> &'static (PhysicalKey, &'static str, u16, u16) {
+        KEYS.iter()
+            .find(|(key, ..)| *key == self)
+            .expect("every physical key is listed in KEYS")
+    }
+
+    /// The `KeyboardEvent.code` name, e.g. `KeyQ`.
+    pub fn code(self) -> &'static str {
+        self.entry().1
+    }
+
+    /// The Linux evdev code (`KEY_Q` is 16).
+    pub fn evdev_code(self) -> u16 {
+        self.entry().2
+    }
+
+    pub fn from_evdev_code(code: u16) -> Option<Self> {
+        KEYS.iter()
+            .find(|(_, _, evdev, _)| *evdev == code)
+            .map(|(key, ..)| *key)
+    }
+
+    pub fn xkb_keycode(self) -> u16 {
+        self.evdev_code() + XKB_KEYCODE_OFFSET
+    }
+
+    pub fn from_xkb_keycode(keycode: u16) -> Option<Self> {
+        keycode
+            .checked_sub(XKB_KEYCODE_OFFSET)
+            .and_then(Self::from_evdev_code)
+    }
+
+    /// The PC scan code (set 1), with `0xE0` in the high byte for extended
+    /// keys, as Windows reports it.
+    pub fn scan_code(self) -> u16 {
+        self.entry().3
+    }
+
+    pub fn from_scan_code(scan_code: u16) -> Option<Self> {
+        KEYS.iter()
+            .find(|(_, _, _, scan)| *scan == scan_code)
+            .map(|(key, ..)| *key)
+    }
+
+    /// The macOS virtual key code (`kVK_ANSI_Q` is 12), as used in
+    /// `.keylayout` files.
+    pub fn mac_keycode(self) -> Option<u16> {
+        MAC_KEYCODES
+            .iter()
+            .find(|(key, _)| *key == self)
+            .map(|(_, code)| *code)
+    }
+
+    pub fn from_mac_keycode(code: u16) -> Option<Self> {
+        MAC_KEYCODES
+            .iter()
+            .find(|(_, mac)| *mac == code)
+            .map(|(key, _)| *key)
+    }
+
+    /// Where the key sits: the alphanumeric block (including the ISO and
+    /// JIS extra keys and Space), the keypad, or elsewhere.
+    pub fn class(self) -> KeyClass {
+        match self {
+            Backquote | Digit1 | Digit2 | Digit3 | Digit4 | Digit5 | Digit6 | Digit7 | Digit8
+            | Digit9 | Digit0 | Minus | Equal | IntlYen | KeyQ | KeyW | KeyE | KeyR | KeyT
+            | KeyY | KeyU | KeyI | KeyO | KeyP | BracketLeft | BracketRight | KeyA | KeyS
+            | KeyD | KeyF | KeyG | KeyH | KeyJ | KeyK | KeyL | Semicolon | Quote | Backslash
+            | IntlBackslash | KeyZ | KeyX | KeyC | KeyV | KeyB | KeyN | KeyM | Comma | Period
+            | Slash | IntlRo | Space => KeyClass::Main,
+            NumLock | Numpad0 | Numpad1 | Numpad2 | Numpad3 | Numpad4 | Numpad5 | Numpad6
+            | Numpad7 | Numpad8 | Numpad9 | NumpadAdd | NumpadSubtract | NumpadMultiply
+            | NumpadDivide | NumpadDecimal | NumpadComma | NumpadEqual | NumpadEnter => {
+                KeyClass::Keypad
+            }
+            _ => KeyClass::Other,
+        }
+    }
+}
+
+impl fmt::Display for PhysicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for PhysicalKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim();
+        KEYS.iter()
+            .find(|(_, name, ..)| *name == code)
+            .map(|(key, ..)| *key)
+            .ok_or_else(|| format!("Unknown key code: {}", code))
+    }
+}
+
+impl TryFrom<String> for PhysicalKey {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<PhysicalKey> for String {
+    fn from(key: PhysicalKey) -> Self {
+        key.code().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests;
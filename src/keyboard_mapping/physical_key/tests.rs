@@ -0,0 +1,118 @@
+use super::*;
+use std::collections::HashSet;
+
+#[test]
+fn test_codes_are_unique_and_round_trip() {
+    let mut codes = HashSet::new();
+    for key in PhysicalKey::all() {
+        assert!(codes.insert(key.code()), "duplicate code {}", key);
+        assert_eq!(key.code().parse::<PhysicalKey>(), Ok(key));
+    }
+}
+
+#[test]
+fn test_evdev_and_scan_codes_are_unique() {
+    let evdev: HashSet<u16> = PhysicalKey::all().map(PhysicalKey::evdev_code).collect();
+    let scan: HashSet<u16> = PhysicalKey::all().map(PhysicalKey::scan_code).collect();
+    assert_eq!(evdev.len(), KEYS.len());
+    assert_eq!(scan.len(), KEYS.len());
+}
+
+#[test]
+fn test_every_variant_is_listed() {
+    // The table lists each key once, in declaration order.
+    let keys: Vec<PhysicalKey> = PhysicalKey::all().collect();
+    let mut sorted = keys.clone();
+    sorted.sort();
+    assert_eq!(keys, sorted);
+    assert_eq!(keys.first(), Some(&PhysicalKey::Backquote));
+    assert_eq!(keys.last(), Some(&PhysicalKey::NumpadEnter));
+}
+
+#[test]
+fn test_xkb_keycodes() {
+    assert_eq!(PhysicalKey::KeyQ.xkb_keycode(), 24);
+    assert_eq!(PhysicalKey::KeyA.xkb_keycode(), 38);
+    assert_eq!(PhysicalKey::from_xkb_keycode(65), Some(PhysicalKey::Space));
+    assert_eq!(
+        PhysicalKey::from_xkb_keycode(94),
+        Some(PhysicalKey::IntlBackslash)
+    );
+    assert_eq!(PhysicalKey::from_xkb_keycode(97), Some(PhysicalKey::IntlRo));
+    assert_eq!(
+        PhysicalKey::from_xkb_keycode(132),
+        Some(PhysicalKey::IntlYen)
+    );
+    assert_eq!(PhysicalKey::from_xkb_keycode(3), None);
+}
+
+#[test]
+fn test_scan_codes() {
+    assert_eq!(PhysicalKey::KeyQ.scan_code(), 0x10);
+    assert_eq!(
+        PhysicalKey::from_scan_code(0x56),
+        Some(PhysicalKey::IntlBackslash)
+    );
+    assert_eq!(
+        PhysicalKey::from_scan_code(0xE01C),
+        Some(PhysicalKey::NumpadEnter)
+    );
+    assert_eq!(PhysicalKey::from_scan_code(0x1C), Some(PhysicalKey::Enter));
+    assert_eq!(PhysicalKey::from_scan_code(0xFF), None);
+}
+
+#[test]
+fn test_mac_keycodes() {
+    assert_eq!(PhysicalKey::KeyA.mac_keycode(), Some(0));
+    assert_eq!(PhysicalKey::from_mac_keycode(12), Some(PhysicalKey::KeyQ));
+    assert_eq!(
+        PhysicalKey::from_mac_keycode(10),
+        Some(PhysicalKey::IntlBackslash)
+    );
+    assert_eq!(
+        PhysicalKey::from_mac_keycode(50),
+        Some(PhysicalKey::Backquote)
+    );
+    assert_eq!(PhysicalKey::from_mac_keycode(94), Some(PhysicalKey::IntlRo));
+    assert_eq!(PhysicalKey::Convert.mac_keycode(), None);
+    let codes: HashSet<u16> = MAC_KEYCODES.iter().map(|(_, code)| *code).collect();
+    assert_eq!(codes.len(), MAC_KEYCODES.len());
+}
+
+#[test]
+fn test_class() {
+    assert_eq!(PhysicalKey::KeyA.class(), KeyClass::Main);
+    assert_eq!(PhysicalKey::Slash.class(), KeyClass::Main);
+    assert_eq!(PhysicalKey::Space.class(), KeyClass::Main);
+    assert_eq!(PhysicalKey::IntlBackslash.class(), KeyClass::Main);
+    assert_eq!(PhysicalKey::IntlRo.class(), KeyClass::Main);
+    assert_eq!(PhysicalKey::NumpadDivide.class(), KeyClass::Keypad);
+    assert_eq!(PhysicalKey::Numpad7.class(), KeyClass::Keypad);
+    assert_eq!(PhysicalKey::Escape.class(), KeyClass::Other);
+    assert_eq!(PhysicalKey::Enter.class(), KeyClass::Other);
+}
+
+#[test]
+fn test_parse_unknown_code() {
+    assert_eq!(
+        "KeyÄ".parse::<PhysicalKey>(),
+        Err("Unknown key code: KeyÄ".to_string())
+    );
+    assert_eq!(" KeyZ ".parse::<PhysicalKey>(), Ok(PhysicalKey::KeyZ));
+}
+
+#[test]
+fn test_serializes_as_code() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Wrapper {
+        key: PhysicalKey,
+    }
+    let data = toml::to_string(&Wrapper {
+        key: PhysicalKey::BracketLeft,
+    })
+    .unwrap();
+    assert_eq!(data.trim(), "key = \"BracketLeft\"");
+    let back: Wrapper = toml::from_str(&data).unwrap();
+    assert_eq!(back.key, PhysicalKey::BracketLeft);
+    assert!(toml::from_str::<Wrapper>("key = \"Nope\"").is_err());
+}
@@ -1,4 +1,9 @@
-use super::types::{KeyboardDirection, KeyboardLayout, LayoutMap};
+use super::all_layout_vk_maps;
+use super::dictionary::Dictionaries;
+use super::ngram::LanguageModels;
+use super::types::{
+    KeyClass, KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, LayoutSelector, ShiftLevel,
+};
 use std::collections::HashMap;
 use unicode_bidi::{BidiClass, bidi_class};
 
@@ -26,26 +31,34 @@ fn direction_bonus(text_dir_is_rtl: Option<bool>, layout_dir: KeyboardDirection)
     }
 }
 
-fn coverage_score(text: &str, inverse: &HashMap<char, Vec<u16>>) -> (i32, usize) {
+fn coverage_score(text: &str, inverse: &OutputTrie) -> (i32, usize) {
     let mut score: i32 = 0;
     let mut matches: usize = 0;
-    for ch in text.chars() {
-        if ch.is_control() {
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        if let Some((len, _)) = inverse.longest_match(rest) {
+            let chars = rest[..len].chars().count();
+            score += 2 * chars as i32;
+            matches += chars;
+            rest = &rest[len..];
             continue;
         }
-        if inverse.contains_key(&ch) {
-            score += 2;
-            matches += 1;
-        } else {
+        if !ch.is_control() {
             score -= 1;
         }
+        rest = &rest[ch.len_utf8()..];
     }
     (score, matches)
 }
 
-pub fn get_text_leyaout_map<'a>(
-    text: &'a str,
+pub fn get_text_leyaout_map<'a>(text: &str, layout_maps: &'a [LayoutMap]) -> Option<&'a LayoutMap> {
+    get_text_leyaout_map_with_models(text, layout_maps, LanguageModels::shared())
+}
+
+pub fn get_text_leyaout_map_with_models<'a>(
+    text: &str,
     layout_maps: &'a [LayoutMap],
+    models: &LanguageModels,
 ) -> Option<&'a LayoutMap> {
     let text_dir = text_starts_rtl(text);
 
@@ -55,16 +68,35 @@ pub fn get_text_leyaout_map<'a>(
     let mut best_idx: Option<usize> = None;
     let mut best_score: i32 = i32::MIN;
     let mut best_matches: usize = 0;
+    let mut best_plausibility: Option<f64> = None;
 
     for (idx, lm) in layout_maps.iter().enumerate() {
         let inverse = &inverses[idx];
         let (mut score, matches) = coverage_score(text, inverse);
         score += direction_bonus(text_dir, lm.layout.direction);
 
-        if score > best_score || (score == best_score && matches > best_matches) {
+        // Coverage decides between layouts of different scripts; among layouts
+        // that cover the text equally well (us/de, ru/uk), the one whose
+        // language makes the text most plausible wins.
+        let plausibility = if score >= best_score {
+            models.plausibility(text, &lm.layout)
+        } else {
+            None
+        };
+        let better = score > best_score
+            || (score == best_score
+                && match (plausibility, best_plausibility) {
+                    (Some(p), Some(best)) => p > best,
+                    (Some(_), None) => true,
+                    (None, Some(_)) => false,
+                    (None, None) => matches > best_matches,
+                });
+
+        if better {
             best_score = score;
             best_idx = Some(idx);
             best_matches = matches;
+            best_plausibility = plausibility;
         }
     }
 
@@ -80,35 +112,247 @@ pub fn shift_text_language(
     target_layout: &LayoutMap,
 ) -> String {
     let inverse_current = invert_layout_map(curent_layout);
-    text.chars()
-        .map(|ch| {
-            inverse_current
-                .get(&ch)
-                .and_then(|vks| vks.first())
-                .and_then(|vk| target_layout.map.get(vk))
+    let mut tokens: Vec<(&str, Option<&[KeyStroke]>)> = Vec::new();
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        let (len, strokes) = match inverse_current.longest_match(rest) {
+            Some((len, sequences)) => (len, sequences.first().map(Vec::as_slice)),
+            None => (ch.len_utf8(), None),
+        };
+        tokens.push((&rest[..len], strokes));
+        rest = &rest[len..];
+    }
+
+    let mut shifted = String::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        // Keystrokes of consecutive characters may form a dead key sequence
+        // on the target layout, e.g. ',' + 'ק' on Hebrew is ' + e, i.e. 'é'
+        // on us(intl).
+        if let Some((consumed, output)) = match_target_sequence(&tokens[i..], target_layout) {
+            shifted.push_str(output);
+            i += consumed;
+            continue;
+        }
+        let (source, strokes) = tokens[i];
+        match strokes.and_then(|strokes| type_strokes(strokes, target_layout)) {
+            Some(output) => shifted.push_str(&output),
+            None => shifted.push_str(source),
+        }
+        i += 1;
+    }
+    shifted
+}
+
+/// Converts `text` from one configured layout to another, both picked
+/// explicitly rather than detected.
+pub fn shift_text_between(
+    text: &str,
+    from: &LayoutSelector,
+    to: &LayoutSelector,
+) -> Result<String, String> {
+    shift_text_between_layouts(text, from, to, &all_layout_vk_maps())
+}
+
+pub fn shift_text_between_layouts(
+    text: &str,
+    from: &LayoutSelector,
+    to: &LayoutSelector,
+    layout_maps: &[LayoutMap],
+) -> Result<String, String> {
+    let from_layout = select_layout(from, layout_maps)?;
+    let to_layout = select_layout(to, layout_maps)?;
+    Ok(shift_text_language(text, from_layout, to_layout))
+}
+
+pub fn select_layout<'a>(
+    selector: &LayoutSelector,
+    layout_maps: &'a [LayoutMap],
+) -> Result<&'a LayoutMap, String> {
+    selector
+        .select(layout_maps)
+        .ok_or_else(|| format!("No configured layout matches \"{}\"", selector))
+}
+
+/// Longest run of leading tokens whose keystrokes form one of the target's
+/// multi-keystroke sequences.
+fn match_target_sequence<'t>(
+    tokens: &[(&str, Option<&[KeyStroke]>)],
+    target_layout: &'t LayoutMap,
+) -> Option<(usize, &'t str)> {
+    let longest = target_layout.sequences.keys().map(Vec::len).max()?;
+    let mut strokes: Vec<KeyStroke> = Vec::new();
+    let mut matched = None;
+    for (consumed, (_, token_strokes)) in tokens.iter().enumerate() {
+        let Some(token_strokes) = token_strokes else {
+            break;
+        };
+        strokes.extend_from_slice(token_strokes);
+        if strokes.len() > longest {
+            break;
+        }
+        if let Some(output) = target_layout.sequences.get(&strokes) {
+            matched = Some((consumed + 1, output.as_str()));
+        }
+    }
+    matched
+}
+
+fn type_strokes(strokes: &[KeyStroke], target_layout: &LayoutMap) -> Option<String> {
+    strokes
+        .iter()
+        .map(|stroke| {
+            target_layout
+                .map
+                .get(stroke)
                 .filter(|s| !s.is_empty())
-                .cloned()
-                .unwrap_or_else(|| ch.to_string())
+                .map(String::as_str)
         })
         .collect()
 }
 
-fn invert_layout_map(layout_map: &LayoutMap) -> HashMap<char, Vec<u16>> {
-    let mut inverse: HashMap<char, Vec<u16>> = HashMap::new();
-    for (vk, output) in &layout_map.map {
-        let mut chars = output.chars();
-        let first = match chars.next() {
-            Some(c) => c,
-            None => continue,
+/// How plausible a conversion is: its share of dictionary words, when its
+/// language has a dictionary, and its n-gram score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Plausibility {
+    known_words: Option<f64>,
+    ngrams: Option<f64>,
+}
+
+impl Plausibility {
+    /// Dictionary ratios decide when both sides have a dictionary, or when
+    /// one side has known words and the other no dictionary; a dictionary
+    /// matching nothing says nothing against a language without one.
+    /// Otherwise the n-gram scores decide.
+    fn beats(&self, other: &Plausibility) -> bool {
+        match (self.known_words, other.known_words) {
+            (Some(ours), Some(theirs)) if ours != theirs => return ours > theirs,
+            (Some(ours), None) if ours > 0.0 => return true,
+            (None, Some(theirs)) if theirs > 0.0 => return false,
+            _ => {}
+        }
+        self.ngrams > other.ngrams
+    }
+}
+
+/// Converts `text` into every layout other than `current_layout` and returns
+/// the most plausible result, see `Plausibility::beats`. `None` when no
+/// candidate can be scored.
+pub fn best_target_layout<'a>(
+    text: &str,
+    current_layout: &LayoutMap,
+    layout_maps: &'a [LayoutMap],
+    dictionaries: &Dictionaries,
+    models: &LanguageModels,
+) -> Option<(&'a LayoutMap, String)> {
+    let mut best: Option<(&'a LayoutMap, String, Plausibility)> = None;
+
+    // A layout that types nothing, e.g. one that failed to compile, can't
+    // be a target.
+    for candidate in layout_maps
+        .iter()
+        .filter(|lm| lm.layout.id != current_layout.layout.id && !lm.map.is_empty())
+    {
+        let shifted = shift_text_language(text, current_layout, candidate);
+        let known_words = candidate
+            .layout
+            .language_code()
+            .and_then(|code| dictionaries.get(code))
+            .and_then(|d| d.known_word_ratio(&shifted));
+        let rank = Plausibility {
+            known_words,
+            ngrams: models.plausibility(&shifted, &candidate.layout),
         };
-        if chars.next().is_none() {
-            inverse.entry(first).or_default().push(*vk);
+        if rank.known_words.is_none() && rank.ngrams.is_none() {
+            continue;
+        }
+        if best
+            .as_ref()
+            .is_none_or(|(_, _, best_rank)| rank.beats(best_rank))
+        {
+            best = Some((candidate, shifted, rank));
         }
     }
+    best.map(|(layout_map, shifted, _)| (layout_map, shifted))
+}
+
+/// Keystroke sequences keyed by the text they produce, stored as a trie over
+/// characters so multi-character outputs such as the Arabic lam-alef "لا"
+/// can be matched.
+#[derive(Debug, Default)]
+struct OutputTrie {
+    children: HashMap<char, OutputTrie>,
+    strokes: Vec<Vec<KeyStroke>>,
+}
+
+impl OutputTrie {
+    fn insert(&mut self, output: &str, strokes: Vec<KeyStroke>) {
+        let node = output
+            .chars()
+            .fold(self, |node, ch| node.children.entry(ch).or_default());
+        node.strokes.push(strokes);
+    }
+
+    /// The longest output that `text` starts with, as its length in bytes
+    /// and the keystroke sequences producing it.
+    fn longest_match(&self, text: &str) -> Option<(usize, &Vec<Vec<KeyStroke>>)> {
+        let mut node = self;
+        let mut longest = None;
+        for (idx, ch) in text.char_indices() {
+            let Some(next) = node.children.get(&ch) else {
+                break;
+            };
+            node = next;
+            if !node.strokes.is_empty() {
+                longest = Some((idx + ch.len_utf8(), &node.strokes));
+            }
+        }
+        longest
+    }
+}
+
+/// Sort key deciding which keystrokes to use when several type the same
+/// output: main block before other keys and the keypad, lower levels first,
+/// then fewer keystrokes, then physical key.
+fn stroke_priority(strokes: &[KeyStroke]) -> (KeyClass, ShiftLevel, usize, Vec<KeyStroke>) {
+    let class = strokes
+        .iter()
+        .map(|stroke| stroke.key.class())
+        .max()
+        .unwrap_or(KeyClass::Main);
+    let level = strokes
+        .iter()
+        .map(|stroke| stroke.level)
+        .max()
+        .unwrap_or(ShiftLevel::Base);
+    (class, level, strokes.len(), strokes.to_vec())
+}
+
+/// Indexes every output of `layout_map` by the keystrokes producing it, in
+/// [`stroke_priority`] order.
+fn invert_layout_map(layout_map: &LayoutMap) -> OutputTrie {
+    let singles = layout_map
+        .map
+        .iter()
+        .map(|(stroke, output)| (vec![*stroke], output));
+    let sequences = layout_map
+        .sequences
+        .iter()
+        .map(|(strokes, output)| (strokes.clone(), output));
+    let mut entries: Vec<(Vec<KeyStroke>, &String)> = singles
+        .chain(sequences)
+        .filter(|(_, output)| !output.is_empty())
+        .collect();
+    entries.sort_by_cached_key(|(strokes, _)| stroke_priority(strokes));
+
+    let mut inverse = OutputTrie::default();
+    for (strokes, output) in entries {
+        inverse.insert(output, strokes);
+    }
     inverse
 }
 
-fn invert_layout_maps(layout_maps: &[LayoutMap]) -> Vec<HashMap<char, Vec<u16>>> {
+fn invert_layout_maps(layout_maps: &[LayoutMap]) -> Vec<OutputTrie> {
     layout_maps.iter().map(invert_layout_map).collect()
 }
 
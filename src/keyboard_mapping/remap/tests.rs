@@ -1,22 +1,30 @@
 use super::*;
+use crate::keyboard_mapping::dictionary::Dictionary;
+use crate::keyboard_mapping::physical_key::PhysicalKey;
+use crate::keyboard_mapping::types::ShiftLevel;
 use std::collections::HashMap;
 
 fn create_test_layout(
     name: &str,
     direction: KeyboardDirection,
-    pairs: Vec<(u16, &str)>,
+    pairs: Vec<(PhysicalKey, &str)>,
 ) -> LayoutMap {
     let mut map = HashMap::new();
     for (vk, output) in pairs {
-        map.insert(vk, output.to_string());
+        map.insert(KeyStroke::base(vk), output.to_string());
     }
 
     LayoutMap {
         layout: KeyboardLayout {
+            id: name.to_string(),
             lang_name: name.to_string(),
             direction,
+            variant: None,
+            language: None,
+            script: None,
         },
         map,
+        sequences: HashMap::new(),
     }
 }
 
@@ -25,32 +33,32 @@ fn create_en_layout() -> LayoutMap {
         "English",
         KeyboardDirection::LTR,
         vec![
-            (0x41, "A"),
-            (0x42, "B"),
-            (0x43, "C"),
-            (0x44, "D"),
-            (0x45, "E"),
-            (0x46, "F"),
-            (0x47, "G"),
-            (0x48, "H"),
-            (0x49, "I"),
-            (0x4A, "J"),
-            (0x4B, "K"),
-            (0x4C, "L"),
-            (0x4D, "M"),
-            (0x4E, "N"),
-            (0x4F, "O"),
-            (0x50, "P"),
-            (0x51, "Q"),
-            (0x52, "R"),
-            (0x53, "S"),
-            (0x54, "T"),
-            (0x55, "U"),
-            (0x56, "V"),
-            (0x57, "W"),
-            (0x58, "X"),
-            (0x59, "Y"),
-            (0x5A, "Z"),
+            (PhysicalKey::KeyA, "A"),
+            (PhysicalKey::KeyB, "B"),
+            (PhysicalKey::KeyC, "C"),
+            (PhysicalKey::KeyD, "D"),
+            (PhysicalKey::KeyE, "E"),
+            (PhysicalKey::KeyF, "F"),
+            (PhysicalKey::KeyG, "G"),
+            (PhysicalKey::KeyH, "H"),
+            (PhysicalKey::KeyI, "I"),
+            (PhysicalKey::KeyJ, "J"),
+            (PhysicalKey::KeyK, "K"),
+            (PhysicalKey::KeyL, "L"),
+            (PhysicalKey::KeyM, "M"),
+            (PhysicalKey::KeyN, "N"),
+            (PhysicalKey::KeyO, "O"),
+            (PhysicalKey::KeyP, "P"),
+            (PhysicalKey::KeyQ, "Q"),
+            (PhysicalKey::KeyR, "R"),
+            (PhysicalKey::KeyS, "S"),
+            (PhysicalKey::KeyT, "T"),
+            (PhysicalKey::KeyU, "U"),
+            (PhysicalKey::KeyV, "V"),
+            (PhysicalKey::KeyW, "W"),
+            (PhysicalKey::KeyX, "X"),
+            (PhysicalKey::KeyY, "Y"),
+            (PhysicalKey::KeyZ, "Z"),
         ],
     )
 }
@@ -60,32 +68,32 @@ fn create_he_layout() -> LayoutMap {
         "Hebrew",
         KeyboardDirection::RTL,
         vec![
-            (0x41, "ש"),
-            (0x42, "נ"),
-            (0x43, "ב"),
-            (0x44, "ג"),
-            (0x45, "כ"),
-            (0x46, "ע"),
-            (0x47, "י"),
-            (0x48, "ח"),
-            (0x49, "ל"),
-            (0x4A, "ך"),
-            (0x4B, "פ"),
-            (0x4C, "ם"),
-            (0x4D, "צ"),
-            (0x4E, "ת"),
-            (0x4F, "ק"),
-            (0x50, "ר"),
-            (0x51, "א"),
-            (0x52, "ט"),
-            (0x53, "ו"),
-            (0x54, "ן"),
-            (0x55, "מ"),
-            (0x56, "ה"),
-            (0x57, "ז"),
-            (0x58, "ס"),
-            (0x59, "ד"),
-            (0x5A, "ג"),
+            (PhysicalKey::KeyA, "ש"),
+            (PhysicalKey::KeyB, "נ"),
+            (PhysicalKey::KeyC, "ב"),
+            (PhysicalKey::KeyD, "ג"),
+            (PhysicalKey::KeyE, "כ"),
+            (PhysicalKey::KeyF, "ע"),
+            (PhysicalKey::KeyG, "י"),
+            (PhysicalKey::KeyH, "ח"),
+            (PhysicalKey::KeyI, "ל"),
+            (PhysicalKey::KeyJ, "ך"),
+            (PhysicalKey::KeyK, "פ"),
+            (PhysicalKey::KeyL, "ם"),
+            (PhysicalKey::KeyM, "צ"),
+            (PhysicalKey::KeyN, "ת"),
+            (PhysicalKey::KeyO, "ק"),
+            (PhysicalKey::KeyP, "ר"),
+            (PhysicalKey::KeyQ, "א"),
+            (PhysicalKey::KeyR, "ט"),
+            (PhysicalKey::KeyS, "ו"),
+            (PhysicalKey::KeyT, "ן"),
+            (PhysicalKey::KeyU, "מ"),
+            (PhysicalKey::KeyV, "ה"),
+            (PhysicalKey::KeyW, "ז"),
+            (PhysicalKey::KeyX, "ס"),
+            (PhysicalKey::KeyY, "ד"),
+            (PhysicalKey::KeyZ, "ג"),
         ],
     )
 }
@@ -95,32 +103,32 @@ fn create_ar_layout() -> LayoutMap {
         "Arabic",
         KeyboardDirection::RTL,
         vec![
-            (0x41, "ش"),
-            (0x42, "س"),
-            (0x43, "ي"),
-            (0x44, "ب"),
-            (0x45, "ل"),
-            (0x46, "ا"),
-            (0x47, "ت"),
-            (0x48, "ن"),
-            (0x49, "م"),
-            (0x4A, "ك"),
-            (0x4B, "ط"),
-            (0x4C, "ئ"),
-            (0x4D, "ء"),
-            (0x4E, "ؤ"),
-            (0x4F, "ر"),
-            (0x50, "لا"),
-            (0x51, "ى"),
-            (0x52, "ة"),
-            (0x53, "و"),
-            (0x54, "ز"),
-            (0x55, "ظ"),
-            (0x56, "د"),
-            (0x57, "ج"),
-            (0x58, "ح"),
-            (0x59, "خ"),
-            (0x5A, "ذ"),
+            (PhysicalKey::KeyA, "ش"),
+            (PhysicalKey::KeyB, "س"),
+            (PhysicalKey::KeyC, "ي"),
+            (PhysicalKey::KeyD, "ب"),
+            (PhysicalKey::KeyE, "ل"),
+            (PhysicalKey::KeyF, "ا"),
+            (PhysicalKey::KeyG, "ت"),
+            (PhysicalKey::KeyH, "ن"),
+            (PhysicalKey::KeyI, "م"),
+            (PhysicalKey::KeyJ, "ك"),
+            (PhysicalKey::KeyK, "ط"),
+            (PhysicalKey::KeyL, "ئ"),
+            (PhysicalKey::KeyM, "ء"),
+            (PhysicalKey::KeyN, "ؤ"),
+            (PhysicalKey::KeyO, "ر"),
+            (PhysicalKey::KeyP, "لا"),
+            (PhysicalKey::KeyQ, "ى"),
+            (PhysicalKey::KeyR, "ة"),
+            (PhysicalKey::KeyS, "و"),
+            (PhysicalKey::KeyT, "ز"),
+            (PhysicalKey::KeyU, "ظ"),
+            (PhysicalKey::KeyV, "د"),
+            (PhysicalKey::KeyW, "ج"),
+            (PhysicalKey::KeyX, "ح"),
+            (PhysicalKey::KeyY, "خ"),
+            (PhysicalKey::KeyZ, "ذ"),
         ],
     )
 }
@@ -223,9 +231,9 @@ fn test_direction_bonus_no_text_direction() {
 
 #[test]
 fn test_coverage_score_perfect_match() {
-    let mut inverse = HashMap::new();
-    inverse.insert('A', vec![0x41]);
-    inverse.insert('B', vec![0x42]);
+    let mut inverse = OutputTrie::default();
+    inverse.insert("A", vec![KeyStroke::base(PhysicalKey::KeyA)]);
+    inverse.insert("B", vec![KeyStroke::base(PhysicalKey::KeyB)]);
 
     let (score, matches) = coverage_score("AB", &inverse);
     assert_eq!(matches, 2);
@@ -234,8 +242,8 @@ fn test_coverage_score_perfect_match() {
 
 #[test]
 fn test_coverage_score_partial_match() {
-    let mut inverse = HashMap::new();
-    inverse.insert('A', vec![0x41]);
+    let mut inverse = OutputTrie::default();
+    inverse.insert("A", vec![KeyStroke::base(PhysicalKey::KeyA)]);
 
     let (score, matches) = coverage_score("ABC", &inverse);
     assert_eq!(matches, 1);
@@ -244,7 +252,7 @@ fn test_coverage_score_partial_match() {
 
 #[test]
 fn test_coverage_score_no_match() {
-    let inverse = HashMap::new();
+    let inverse = OutputTrie::default();
 
     let (score, matches) = coverage_score("ABC", &inverse);
     assert_eq!(matches, 0);
@@ -253,8 +261,8 @@ fn test_coverage_score_no_match() {
 
 #[test]
 fn test_coverage_score_ignores_control_chars() {
-    let mut inverse = HashMap::new();
-    inverse.insert('A', vec![0x41]);
+    let mut inverse = OutputTrie::default();
+    inverse.insert("A", vec![KeyStroke::base(PhysicalKey::KeyA)]);
 
     let (score, matches) = coverage_score("A\n\t", &inverse);
     assert_eq!(matches, 1);
@@ -334,29 +342,47 @@ fn test_shift_text_language_no_match() {
 #[test]
 fn test_shift_text_language_multi_char_sequences() {
     let mut current_map = HashMap::new();
-    current_map.insert(0x50, "لا".to_string());
+    current_map.insert(KeyStroke::base(PhysicalKey::KeyP), "لا".to_string());
 
     let mut target_map = HashMap::new();
-    target_map.insert(0x50, "LA".to_string());
+    target_map.insert(KeyStroke::base(PhysicalKey::KeyP), "LA".to_string());
 
     let current = LayoutMap {
         layout: KeyboardLayout {
+            id: "Arabic".to_string(),
             lang_name: "Arabic".to_string(),
             direction: KeyboardDirection::RTL,
+            variant: None,
+            language: None,
+            script: None,
         },
         map: current_map,
+        sequences: HashMap::new(),
     };
 
     let target = LayoutMap {
         layout: KeyboardLayout {
+            id: "English".to_string(),
             lang_name: "English".to_string(),
             direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
         },
         map: target_map,
+        sequences: HashMap::new(),
     };
 
-    let result = shift_text_language("لا", &current, &target);
-    assert_eq!(result, "لا");
+    assert_eq!(shift_text_language("لا", &current, &target), "LA");
+    assert_eq!(shift_text_language("LA", &target, &current), "لا");
+}
+
+/// Keystroke sequences producing exactly `output`.
+fn strokes_for<'a>(inverse: &'a OutputTrie, output: &str) -> Option<&'a Vec<Vec<KeyStroke>>> {
+    inverse
+        .longest_match(output)
+        .filter(|(len, _)| *len == output.len())
+        .map(|(_, strokes)| strokes)
 }
 
 #[test]
@@ -364,45 +390,71 @@ fn test_invert_layout_map_single_chars() {
     let layout = create_en_layout();
     let inverse = invert_layout_map(&layout);
 
-    assert_eq!(inverse.get(&'A'), Some(&vec![0x41]));
-    assert_eq!(inverse.get(&'B'), Some(&vec![0x42]));
+    assert_eq!(
+        strokes_for(&inverse, "A"),
+        Some(&vec![vec![KeyStroke::base(PhysicalKey::KeyA)]])
+    );
+    assert_eq!(
+        strokes_for(&inverse, "B"),
+        Some(&vec![vec![KeyStroke::base(PhysicalKey::KeyB)]])
+    );
 }
 
 #[test]
-fn test_invert_layout_map_ignores_multi_char() {
+fn test_invert_layout_map_indexes_multi_char() {
     let mut map = HashMap::new();
-    map.insert(0x41, "AB".to_string());
-    map.insert(0x42, "C".to_string());
+    map.insert(KeyStroke::base(PhysicalKey::KeyA), "AB".to_string());
+    map.insert(KeyStroke::base(PhysicalKey::KeyB), "C".to_string());
 
     let layout = LayoutMap {
         layout: KeyboardLayout {
+            id: "Test".to_string(),
             lang_name: "Test".to_string(),
             direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
         },
         map,
+        sequences: HashMap::new(),
     };
 
     let inverse = invert_layout_map(&layout);
-    assert!(!inverse.contains_key(&'A'));
-    assert_eq!(inverse.get(&'C'), Some(&vec![0x42]));
+    assert!(strokes_for(&inverse, "A").is_none());
+    assert_eq!(
+        strokes_for(&inverse, "AB"),
+        Some(&vec![vec![KeyStroke::base(PhysicalKey::KeyA)]])
+    );
+    assert_eq!(
+        strokes_for(&inverse, "C"),
+        Some(&vec![vec![KeyStroke::base(PhysicalKey::KeyB)]])
+    );
 }
 
 #[test]
 fn test_invert_layout_map_empty_strings() {
     let mut map = HashMap::new();
-    map.insert(0x41, "".to_string());
-    map.insert(0x42, "A".to_string());
+    map.insert(KeyStroke::base(PhysicalKey::KeyA), "".to_string());
+    map.insert(KeyStroke::base(PhysicalKey::KeyB), "A".to_string());
 
     let layout = LayoutMap {
         layout: KeyboardLayout {
+            id: "Test".to_string(),
             lang_name: "Test".to_string(),
             direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
         },
         map,
+        sequences: HashMap::new(),
     };
 
     let inverse = invert_layout_map(&layout);
-    assert_eq!(inverse.get(&'A'), Some(&vec![0x42]));
+    assert_eq!(
+        strokes_for(&inverse, "A"),
+        Some(&vec![vec![KeyStroke::base(PhysicalKey::KeyB)]])
+    );
 }
 
 #[test]
@@ -411,6 +463,434 @@ fn test_invert_layout_maps_batch() {
     let inverses = invert_layout_maps(&layouts);
 
     assert_eq!(inverses.len(), 2);
-    assert_eq!(inverses[0].get(&'A'), Some(&vec![0x41]));
-    assert_eq!(inverses[1].get(&'ש'), Some(&vec![0x41]));
+    assert_eq!(
+        strokes_for(&inverses[0], "A"),
+        Some(&vec![vec![KeyStroke::base(PhysicalKey::KeyA)]])
+    );
+    assert_eq!(
+        strokes_for(&inverses[1], "ש"),
+        Some(&vec![vec![KeyStroke::base(PhysicalKey::KeyA)]])
+    );
+}
+
+fn create_latin_layout(name: &str) -> LayoutMap {
+    let pairs: Vec<(PhysicalKey, String)> = ('a'..='z')
+        .map(|ch| {
+            let key = format!("Key{}", ch.to_ascii_uppercase()).parse().unwrap();
+            (key, ch.to_string())
+        })
+        .collect();
+    create_test_layout(
+        name,
+        KeyboardDirection::LTR,
+        pairs.iter().map(|(vk, s)| (*vk, s.as_str())).collect(),
+    )
+}
+
+#[test]
+fn test_get_text_layout_map_ngram_breaks_coverage_tie() {
+    let layouts = vec![
+        create_latin_layout("English"),
+        create_latin_layout("German"),
+    ];
+    let models = LanguageModels::builtin();
+
+    let result = get_text_leyaout_map_with_models("und die schule ist nicht", &layouts, &models);
+    assert_eq!(result.unwrap().layout.lang_name, "German");
+
+    let result = get_text_leyaout_map_with_models("the house is there", &layouts, &models);
+    assert_eq!(result.unwrap().layout.lang_name, "English");
+}
+
+#[test]
+fn test_get_text_layout_map_ngram_is_order_independent() {
+    let layouts = vec![
+        create_latin_layout("German"),
+        create_latin_layout("English"),
+    ];
+    let models = LanguageModels::builtin();
+
+    let result = get_text_leyaout_map_with_models("the house is there", &layouts, &models);
+    assert_eq!(result.unwrap().layout.lang_name, "English");
+}
+
+#[test]
+fn test_get_text_layout_map_coverage_beats_ngram() {
+    let layouts = vec![create_en_layout(), create_he_layout()];
+    let models = LanguageModels::builtin();
+
+    let result = get_text_leyaout_map_with_models("שלום", &layouts, &models);
+    assert_eq!(result.unwrap().layout.lang_name, "Hebrew");
+}
+
+#[test]
+fn test_get_text_layout_map_without_models_keeps_first_best() {
+    let layouts = vec![create_latin_layout("Foo"), create_latin_layout("Bar")];
+    let models = LanguageModels::default();
+
+    let result = get_text_leyaout_map_with_models("the house", &layouts, &models);
+    assert_eq!(result.unwrap().layout.lang_name, "Foo");
+}
+
+#[test]
+fn test_best_target_layout_prefers_dictionary_words() {
+    let layouts = vec![create_he_layout(), create_ar_layout(), create_en_layout()];
+    let mut dictionaries = Dictionaries::default();
+    dictionaries.insert("en", Dictionary::from_word_list("hello"));
+
+    // "HELLO" typed on the Hebrew layout; cycling would pick Arabic.
+    let typed = shift_text_language("HELLO", &layouts[2], &layouts[0]);
+    let (target, shifted) = best_target_layout(
+        &typed,
+        &layouts[0],
+        &layouts,
+        &dictionaries,
+        &LanguageModels::default(),
+    )
+    .unwrap();
+    assert_eq!(target.layout.lang_name, "English");
+    assert_eq!(shifted, "HELLO");
+}
+
+#[test]
+fn test_best_target_layout_falls_back_to_ngrams() {
+    let layouts = vec![create_en_layout(), create_ar_layout(), create_he_layout()];
+    let models = LanguageModels::builtin();
+
+    // "שלום" typed on the English layout.
+    let (target, shifted) = best_target_layout(
+        "AISL",
+        &layouts[0],
+        &layouts,
+        &Dictionaries::default(),
+        &models,
+    )
+    .unwrap();
+    assert_eq!(target.layout.lang_name, "Hebrew");
+    assert_eq!(shifted, "שלום");
+}
+
+#[test]
+fn test_best_target_layout_empty_dictionary_match_defers_to_ngrams() {
+    let layouts = vec![create_ar_layout(), create_en_layout(), create_he_layout()];
+    let mut dictionaries = Dictionaries::default();
+    dictionaries.insert("en", Dictionary::from_word_list("hello\nworld"));
+
+    // "שלום" typed on the Arabic layout: English has a dictionary but none
+    // of its words, Hebrew has no dictionary.
+    let typed = shift_text_language("שלום", &layouts[2], &layouts[0]);
+    let (target, shifted) = best_target_layout(
+        &typed,
+        &layouts[0],
+        &layouts,
+        &dictionaries,
+        &LanguageModels::builtin(),
+    )
+    .unwrap();
+    assert_eq!(target.layout.lang_name, "Hebrew");
+    assert_eq!(shifted, "שלום");
+}
+
+#[test]
+fn test_best_target_layout_none_without_scores() {
+    let layouts = vec![create_latin_layout("Foo"), create_latin_layout("Bar")];
+    let result = best_target_layout(
+        "abc",
+        &layouts[0],
+        &layouts,
+        &Dictionaries::default(),
+        &LanguageModels::default(),
+    );
+    assert!(result.is_none());
+}
+
+fn create_leveled_layout(
+    name: &str,
+    direction: KeyboardDirection,
+    keys: Vec<(PhysicalKey, &str, &str)>,
+) -> LayoutMap {
+    let mut map = HashMap::new();
+    for (vk, base, shifted) in keys {
+        map.insert(KeyStroke::base(vk), base.to_string());
+        map.insert(KeyStroke::new(vk, ShiftLevel::Shift), shifted.to_string());
+    }
+    LayoutMap {
+        layout: KeyboardLayout {
+            id: name.to_string(),
+            lang_name: name.to_string(),
+            direction,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        map,
+        sequences: HashMap::new(),
+    }
+}
+
+#[test]
+fn test_shift_text_language_keeps_shift_level() {
+    let en = create_leveled_layout(
+        "English",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::KeyH, "h", "H"),
+            (PhysicalKey::KeyE, "e", "E"),
+            (PhysicalKey::KeyL, "l", "L"),
+            (PhysicalKey::KeyO, "o", "O"),
+            (PhysicalKey::Digit1, "1", "!"),
+        ],
+    );
+    let ru = create_leveled_layout(
+        "Russian",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::KeyH, "р", "Р"),
+            (PhysicalKey::KeyE, "у", "У"),
+            (PhysicalKey::KeyL, "д", "Д"),
+            (PhysicalKey::KeyO, "щ", "Щ"),
+            (PhysicalKey::Digit1, "1", "!"),
+        ],
+    );
+
+    assert_eq!(shift_text_language("Hello!", &en, &ru), "Руддщ!");
+    assert_eq!(shift_text_language("Руддщ!", &ru, &en), "Hello!");
+}
+
+#[test]
+fn test_shift_text_language_altgr_level() {
+    let mut de_map = HashMap::new();
+    de_map.insert(KeyStroke::base(PhysicalKey::KeyQ), "q".to_string());
+    de_map.insert(
+        KeyStroke::new(PhysicalKey::KeyQ, ShiftLevel::AltGr),
+        "@".to_string(),
+    );
+    let de = LayoutMap {
+        layout: KeyboardLayout {
+            id: "German".to_string(),
+            lang_name: "German".to_string(),
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        map: de_map,
+        sequences: HashMap::new(),
+    };
+    let mut he_map = HashMap::new();
+    he_map.insert(KeyStroke::base(PhysicalKey::KeyQ), "/".to_string());
+    he_map.insert(
+        KeyStroke::new(PhysicalKey::KeyQ, ShiftLevel::AltGr),
+        "ׂ".to_string(),
+    );
+    let he = LayoutMap {
+        layout: KeyboardLayout {
+            id: "Hebrew".to_string(),
+            lang_name: "Hebrew".to_string(),
+            direction: KeyboardDirection::RTL,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        map: he_map,
+        sequences: HashMap::new(),
+    };
+
+    assert_eq!(shift_text_language("q@", &de, &he), "/ׂ");
+}
+
+#[test]
+fn test_shift_text_language_missing_target_level_keeps_char() {
+    let en = create_leveled_layout(
+        "English",
+        KeyboardDirection::LTR,
+        vec![(PhysicalKey::KeyA, "a", "A")],
+    );
+    let he = create_test_layout(
+        "Hebrew",
+        KeyboardDirection::RTL,
+        vec![(PhysicalKey::KeyA, "ש")],
+    );
+
+    assert_eq!(shift_text_language("aA", &en, &he), "שA");
+}
+
+fn create_dead_key_layouts() -> (LayoutMap, LayoutMap) {
+    let mut intl_map = HashMap::new();
+    intl_map.insert(KeyStroke::base(PhysicalKey::KeyE), "e".to_string());
+    intl_map.insert(KeyStroke::base(PhysicalKey::Space), " ".to_string());
+    let mut intl = LayoutMap::new(
+        KeyboardLayout {
+            id: "English (US, intl.)".to_string(),
+            lang_name: "English (US, intl.)".to_string(),
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        intl_map,
+    );
+    intl.sequences.insert(
+        vec![
+            KeyStroke::base(PhysicalKey::Quote),
+            KeyStroke::base(PhysicalKey::KeyE),
+        ],
+        "é".to_string(),
+    );
+    intl.sequences.insert(
+        vec![
+            KeyStroke::base(PhysicalKey::Quote),
+            KeyStroke::base(PhysicalKey::Space),
+        ],
+        "'".to_string(),
+    );
+
+    let mut he_map = HashMap::new();
+    he_map.insert(KeyStroke::base(PhysicalKey::Quote), ",".to_string());
+    he_map.insert(KeyStroke::base(PhysicalKey::KeyE), "ק".to_string());
+    he_map.insert(KeyStroke::base(PhysicalKey::Space), " ".to_string());
+    let he = LayoutMap::new(
+        KeyboardLayout {
+            id: "Hebrew".to_string(),
+            lang_name: "Hebrew".to_string(),
+            direction: KeyboardDirection::RTL,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        he_map,
+    );
+    (intl, he)
+}
+
+#[test]
+fn test_shift_text_language_expands_dead_key_sequence() {
+    let (intl, he) = create_dead_key_layouts();
+    assert_eq!(shift_text_language("é e'", &intl, &he), ",ק ק, ");
+}
+
+#[test]
+fn test_shift_text_language_composes_dead_key_sequence() {
+    let (intl, he) = create_dead_key_layouts();
+    assert_eq!(shift_text_language(",ק ק", &he, &intl), "é e");
+}
+
+#[test]
+fn test_invert_layout_map_includes_sequences() {
+    let (intl, _) = create_dead_key_layouts();
+    let inverse = invert_layout_map(&intl);
+    assert_eq!(
+        strokes_for(&inverse, "é"),
+        Some(&vec![vec![
+            KeyStroke::base(PhysicalKey::Quote),
+            KeyStroke::base(PhysicalKey::KeyE)
+        ]])
+    );
+}
+
+#[test]
+fn test_shift_text_language_prefers_longest_output() {
+    let ar = create_test_layout(
+        "Arabic",
+        KeyboardDirection::RTL,
+        vec![
+            (PhysicalKey::KeyB, "لا"),
+            (PhysicalKey::KeyG, "ل"),
+            (PhysicalKey::KeyH, "ا"),
+        ],
+    );
+    let en = create_test_layout(
+        "English",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::KeyB, "b"),
+            (PhysicalKey::KeyG, "g"),
+            (PhysicalKey::KeyH, "h"),
+        ],
+    );
+
+    assert_eq!(shift_text_language("لا ل", &ar, &en), "b g");
+    assert_eq!(shift_text_language("bgh", &en, &ar), "لالا");
+}
+
+#[test]
+fn test_coverage_score_counts_multi_char_outputs() {
+    let mut inverse = OutputTrie::default();
+    inverse.insert("لا", vec![KeyStroke::base(PhysicalKey::KeyB)]);
+
+    let (score, matches) = coverage_score("لال", &inverse);
+    assert_eq!(matches, 2);
+    assert_eq!(score, 3);
+}
+
+#[test]
+fn test_shift_text_language_prefers_lower_level() {
+    // Digit0 and Digit1 are both on the main block.
+    let mut current_map = HashMap::new();
+    current_map.insert(
+        KeyStroke::new(PhysicalKey::Digit0, ShiftLevel::Shift),
+        "x".to_string(),
+    );
+    current_map.insert(KeyStroke::base(PhysicalKey::Digit1), "x".to_string());
+    let current = LayoutMap::new(
+        KeyboardLayout {
+            id: "Current".to_string(),
+            lang_name: "Current".to_string(),
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        current_map,
+    );
+    let target = create_leveled_layout(
+        "Target",
+        KeyboardDirection::LTR,
+        vec![
+            (PhysicalKey::Digit0, "a", "A"),
+            (PhysicalKey::Digit1, "b", "B"),
+        ],
+    );
+
+    for _ in 0..20 {
+        assert_eq!(shift_text_language("x", &current, &target), "b");
+    }
+}
+
+#[test]
+fn test_stroke_priority_orders_level_then_length() {
+    let base = vec![KeyStroke::base(PhysicalKey::KeyA)];
+    let shifted = vec![KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Shift)];
+    let sequence = vec![
+        KeyStroke::base(PhysicalKey::KeyA),
+        KeyStroke::base(PhysicalKey::KeyB),
+    ];
+    assert!(stroke_priority(&base) < stroke_priority(&shifted));
+    assert!(stroke_priority(&base) < stroke_priority(&sequence));
+}
+
+#[test]
+fn test_shift_text_between_layouts() {
+    let layouts = vec![create_en_layout(), create_he_layout()];
+    let he = LayoutSelector::Name("he".to_string());
+    let en = LayoutSelector::Index(0);
+    assert_eq!(
+        shift_text_between_layouts("ABC", &en, &he, &layouts),
+        Ok("שנב".to_string())
+    );
+    assert_eq!(
+        shift_text_between_layouts("שנב", &he, &en, &layouts),
+        Ok("ABC".to_string())
+    );
+}
+
+#[test]
+fn test_shift_text_between_layouts_unknown_layout() {
+    let layouts = vec![create_en_layout(), create_he_layout()];
+    let missing = LayoutSelector::Name("Greek".to_string());
+    let result = shift_text_between_layouts("ABC", &LayoutSelector::Index(0), &missing, &layouts);
+    assert_eq!(
+        result,
+        Err("No configured layout matches \"Greek\"".to_string())
+    );
 }
@@ -0,0 +1,587 @@
+use super::types::{KeyboardLayout, script_for_language};
+use std::str::FromStr;
+
+// Languages written in Latin script; any of them can be a romanization target.
+const LATIN_LANGUAGES: &[&str] = &["en", "de", "fr", "es"];
+
+// Languages with a romanization table, each with the letters that tell it
+// apart from the others of its script. A language without such letters is
+// assumed for text of its script that has none of the others' letters.
+const SOURCE_LANGUAGES: &[(&str, &str)] =
+    &[("be", "ўЎ"), ("uk", "іїєґІЇЄҐ"), ("ru", ""), ("he", "")];
+
+/// Which romanization table to use for a language.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransliterationScheme {
+    /// BGN/PCGN for Cyrillic, simplified Israeli romanization for Hebrew,
+    /// both for English readers.
+    #[default]
+    Default,
+    /// BGN/PCGN (Cyrillic into English only).
+    Bgn,
+    /// ISO 9:1995, one Latin letter per Cyrillic letter (Cyrillic into any
+    /// Latin-script language).
+    Iso9,
+}
+
+impl FromStr for TransliterationScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "default" => Ok(TransliterationScheme::Default),
+            "bgn" => Ok(TransliterationScheme::Bgn),
+            "iso9" => Ok(TransliterationScheme::Iso9),
+            other => Err(format!(
+                "Unknown transliteration scheme: {} (expected default, bgn or iso9)",
+                other
+            )),
+        }
+    }
+}
+
+/// One Cyrillic letter. `initial` replaces `out` at the start of a word
+/// and, for tables with `initial_after_vowel`, after a vowel or a hard or
+/// soft sign.
+struct Rule {
+    ch: char,
+    out: &'static str,
+    initial: Option<&'static str>,
+}
+
+const fn r(ch: char, out: &'static str) -> Rule {
+    Rule {
+        ch,
+        out,
+        initial: None,
+    }
+}
+
+const fn ri(ch: char, out: &'static str, initial: &'static str) -> Rule {
+    Rule {
+        ch,
+        out,
+        initial: Some(initial),
+    }
+}
+
+struct CyrillicTable {
+    rules: &'static [Rule],
+    initial_after_vowel: bool,
+}
+
+const ISO9: CyrillicTable = CyrillicTable {
+    rules: &[
+        r('а', "a"),
+        r('б', "b"),
+        r('в', "v"),
+        r('г', "g"),
+        r('ґ', "g̀"),
+        r('д', "d"),
+        r('е', "e"),
+        r('ё', "ë"),
+        r('є', "ê"),
+        r('ж', "ž"),
+        r('з', "z"),
+        r('и', "i"),
+        r('і', "ì"),
+        r('ї', "ï"),
+        r('й', "j"),
+        r('к', "k"),
+        r('л', "l"),
+        r('м', "m"),
+        r('н', "n"),
+        r('о', "o"),
+        r('п', "p"),
+        r('р', "r"),
+        r('с', "s"),
+        r('т', "t"),
+        r('у', "u"),
+        r('ў', "ŭ"),
+        r('ф', "f"),
+        r('х', "h"),
+        r('ц', "c"),
+        r('ч', "č"),
+        r('ш', "š"),
+        r('щ', "ŝ"),
+        r('ъ', "ʺ"),
+        r('ы', "y"),
+        r('ь', "ʹ"),
+        r('э', "è"),
+        r('ю', "û"),
+        r('я', "â"),
+        r('\'', "ʼ"),
+    ],
+    initial_after_vowel: false,
+};
+
+const BGN_RUSSIAN: CyrillicTable = CyrillicTable {
+    rules: &[
+        r('а', "a"),
+        r('б', "b"),
+        r('в', "v"),
+        r('г', "g"),
+        r('д', "d"),
+        ri('е', "e", "ye"),
+        ri('ё', "ë", "yë"),
+        r('ж', "zh"),
+        r('з', "z"),
+        r('и', "i"),
+        r('й', "y"),
+        r('к', "k"),
+        r('л', "l"),
+        r('м', "m"),
+        r('н', "n"),
+        r('о', "o"),
+        r('п', "p"),
+        r('р', "r"),
+        r('с', "s"),
+        r('т', "t"),
+        r('у', "u"),
+        r('ф', "f"),
+        r('х', "kh"),
+        r('ц', "ts"),
+        r('ч', "ch"),
+        r('ш', "sh"),
+        r('щ', "shch"),
+        r('ъ', "\""),
+        r('ы', "y"),
+        r('ь', "'"),
+        r('э', "e"),
+        r('ю', "yu"),
+        r('я', "ya"),
+    ],
+    initial_after_vowel: true,
+};
+
+// The Ukrainian BGN/PCGN table follows the 2010 national system, which
+// drops the apostrophe and soft sign.
+const BGN_UKRAINIAN: CyrillicTable = CyrillicTable {
+    rules: &[
+        r('а', "a"),
+        r('б', "b"),
+        r('в', "v"),
+        r('г', "h"),
+        r('ґ', "g"),
+        r('д', "d"),
+        r('е', "e"),
+        ri('є', "ie", "ye"),
+        r('ж', "zh"),
+        r('з', "z"),
+        r('и', "y"),
+        r('і', "i"),
+        ri('ї', "i", "yi"),
+        ri('й', "i", "y"),
+        r('к', "k"),
+        r('л', "l"),
+        r('м', "m"),
+        r('н', "n"),
+        r('о', "o"),
+        r('п', "p"),
+        r('р', "r"),
+        r('с', "s"),
+        r('т', "t"),
+        r('у', "u"),
+        r('ф', "f"),
+        r('х', "kh"),
+        r('ц', "ts"),
+        r('ч', "ch"),
+        r('ш', "sh"),
+        r('щ', "shch"),
+        r('ь', ""),
+        ri('ю', "iu", "yu"),
+        ri('я', "ia", "ya"),
+        r('\'', ""),
+        r('’', ""),
+    ],
+    initial_after_vowel: false,
+};
+
+const BGN_BELARUSIAN: CyrillicTable = CyrillicTable {
+    rules: &[
+        r('а', "a"),
+        r('б', "b"),
+        r('в', "v"),
+        r('г', "h"),
+        r('д', "d"),
+        ri('е', "e", "ye"),
+        ri('ё', "yo", "yo"),
+        r('ж', "zh"),
+        r('з', "z"),
+        r('і', "i"),
+        r('й', "y"),
+        r('к', "k"),
+        r('л', "l"),
+        r('м', "m"),
+        r('н', "n"),
+        r('о', "o"),
+        r('п', "p"),
+        r('р', "r"),
+        r('с', "s"),
+        r('т', "t"),
+        r('у', "u"),
+        r('ў', "w"),
+        r('ф', "f"),
+        r('х', "kh"),
+        r('ц', "ts"),
+        r('ч', "ch"),
+        r('ш', "sh"),
+        r('ы', "y"),
+        r('ь', "'"),
+        r('э', "e"),
+        r('ю', "yu"),
+        r('я', "ya"),
+        r('\'', "\""),
+    ],
+    initial_after_vowel: true,
+};
+
+/// A romanization table, picked by source and target language.
+enum Table {
+    Cyrillic(&'static CyrillicTable),
+    Hebrew,
+}
+
+/// The table romanizing `from` for readers of `to`. ISO 9 spells the same
+/// for every Latin-script language; BGN/PCGN and the Hebrew table spell
+/// sounds the English way, so they only romanize into English.
+fn table(from: &str, to: &str, scheme: TransliterationScheme) -> Option<Table> {
+    use TransliterationScheme::{Bgn, Default, Iso9};
+    match (from, to, scheme) {
+        ("ru" | "uk" | "be", _, Iso9) if LATIN_LANGUAGES.contains(&to) => {
+            Some(Table::Cyrillic(&ISO9))
+        }
+        ("ru", "en", Default | Bgn) => Some(Table::Cyrillic(&BGN_RUSSIAN)),
+        ("uk", "en", Default | Bgn) => Some(Table::Cyrillic(&BGN_UKRAINIAN)),
+        ("be", "en", Default | Bgn) => Some(Table::Cyrillic(&BGN_BELARUSIAN)),
+        ("he", "en", Default) => Some(Table::Hebrew),
+        _ => None,
+    }
+}
+
+/// The script of a letter, for the scripts there are tables for.
+fn letter_script(ch: char) -> Option<&'static str> {
+    match ch {
+        '\u{0400}'..='\u{04FF}' => Some("Cyrl"),
+        '\u{0590}'..='\u{05FF}' => Some("Hebr"),
+        _ if ch.is_alphabetic() => Some("Latn"),
+        _ => None,
+    }
+}
+
+/// The language `text` is written in, for romanizing it: `layout`'s when
+/// that is written in the script of the text, otherwise the language with a
+/// table whose letters the text uses. `None` for text in a script without
+/// a table.
+pub fn source_language(text: &str, layout: Option<&KeyboardLayout>) -> Option<&'static str> {
+    let script = text.chars().find_map(letter_script)?;
+    if let Some(code) = layout.and_then(KeyboardLayout::language_code)
+        && script_for_language(code) == Some(script)
+    {
+        return Some(code);
+    }
+    SOURCE_LANGUAGES
+        .iter()
+        .filter(|(code, _)| script_for_language(code) == Some(script))
+        .find(|(_, letters)| letters.is_empty() || text.chars().any(|c| letters.contains(c)))
+        .map(|(code, _)| *code)
+}
+
+fn is_cyrillic_vowel(ch: char) -> bool {
+    "аеёєиіїоуыэюяъь".contains(ch)
+}
+
+/// Upper-cases the romanized letter: the whole output when the word is in
+/// capitals ("ЩИ" -> "SHCHI"), only its first letter otherwise ("Щи" -> "Shchi").
+fn apply_case(out: &str, all_caps: bool) -> String {
+    if all_caps {
+        return out.to_uppercase();
+    }
+    let mut chars = out.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn transliterate_cyrillic(text: &str, table: &CyrillicTable) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    for (i, &ch) in chars.iter().enumerate() {
+        let lower = ch.to_lowercase().next().unwrap_or(ch);
+        let Some(rule) = table.rules.iter().find(|rule| rule.ch == lower) else {
+            out.push(ch);
+            continue;
+        };
+        let previous = i
+            .checked_sub(1)
+            .map(|p| chars[p].to_lowercase().next().unwrap_or(chars[p]));
+        let initial = match previous {
+            None => true,
+            Some(p) if !p.is_alphabetic() => true,
+            Some(p) => table.initial_after_vowel && is_cyrillic_vowel(p),
+        };
+        let romanized = match rule.initial {
+            Some(initial_out) if initial => initial_out,
+            _ => rule.out,
+        };
+        if ch.is_uppercase() {
+            let neighbour_upper = |idx: Option<usize>| {
+                idx.and_then(|idx| chars.get(idx))
+                    .is_some_and(|c| c.is_uppercase())
+            };
+            let all_caps = neighbour_upper(Some(i + 1)) || neighbour_upper(i.checked_sub(1));
+            out.push_str(&apply_case(romanized, all_caps));
+        } else {
+            out.push_str(romanized);
+        }
+    }
+    out
+}
+
+// Hebrew romanization. Vowel points are used when present. Unpointed text
+// doesn't spell most vowels, so common words and names come from
+// `HEBREW_WORDS` and other words get "a" between two consonants.
+
+/// Unpointed spellings of common words and names, romanized the way they
+/// are usually written.
+const HEBREW_WORDS: &[(&str, &str)] = &[
+    ("שלום", "shalom"),
+    ("תודה", "toda"),
+    ("בוקר", "boker"),
+    ("טוב", "tov"),
+    ("לילה", "layla"),
+    ("כן", "ken"),
+    ("לא", "lo"),
+    ("ישראל", "yisrael"),
+    ("ירושלים", "yerushalayim"),
+    ("אביב", "aviv"),
+    ("אבי", "avi"),
+    ("אברהם", "avraham"),
+    ("אהרון", "aharon"),
+    ("אורי", "uri"),
+    ("אליהו", "eliyahu"),
+    ("אסתר", "ester"),
+    ("בנימין", "binyamin"),
+    ("ביטון", "biton"),
+    ("גולן", "golan"),
+    ("דוד", "david"),
+    ("דניאל", "daniel"),
+    ("חיים", "chaim"),
+    ("חן", "chen"),
+    ("יוסף", "yosef"),
+    ("יונתן", "yonatan"),
+    ("יעל", "yael"),
+    ("יעקב", "yaakov"),
+    ("יצחק", "yitzhak"),
+    ("כהן", "cohen"),
+    ("לאה", "lea"),
+    ("לוי", "levi"),
+    ("מזרחי", "mizrahi"),
+    ("מיכאל", "michael"),
+    ("מרים", "miriam"),
+    ("משה", "moshe"),
+    ("נועה", "noa"),
+    ("נתן", "natan"),
+    ("עמית", "amit"),
+    ("פרץ", "peretz"),
+    ("רבקה", "rivka"),
+    ("רות", "rut"),
+    ("רחל", "rachel"),
+    ("שי", "shai"),
+    ("שמואל", "shmuel"),
+    ("שרה", "sara"),
+    ("תמר", "tamar"),
+];
+
+const DAGESH: char = '\u{05BC}';
+const SHIN_DOT: char = '\u{05C1}';
+const SIN_DOT: char = '\u{05C2}';
+const HOLAM: char = '\u{05B9}';
+
+fn hebrew_vowel(mark: char) -> Option<&'static str> {
+    match mark {
+        '\u{05B0}' => Some(""),
+        '\u{05B1}' | '\u{05B5}' | '\u{05B6}' => Some("e"),
+        '\u{05B2}' | '\u{05B3}' | '\u{05B7}' | '\u{05B8}' => Some("a"),
+        '\u{05B4}' => Some("i"),
+        '\u{05B9}' | '\u{05BA}' => Some("o"),
+        '\u{05BB}' => Some("u"),
+        _ => None,
+    }
+}
+
+fn is_hebrew_mark(ch: char) -> bool {
+    ('\u{0591}'..='\u{05C7}').contains(&ch) && !matches!(ch, '\u{05BE}' | '\u{05C0}' | '\u{05C3}')
+}
+
+fn is_hebrew_letter(ch: char) -> bool {
+    ('\u{05D0}'..='\u{05EA}').contains(&ch)
+}
+
+/// A letter and the points written on it.
+struct HebrewLetter {
+    letter: char,
+    marks: Vec<char>,
+}
+
+impl HebrewLetter {
+    fn has(&self, mark: char) -> bool {
+        self.marks.contains(&mark)
+    }
+
+    fn vowel(&self) -> Option<&'static str> {
+        self.marks.iter().find_map(|m| hebrew_vowel(*m))
+    }
+}
+
+enum Sound {
+    Consonant(&'static str),
+    Vowel(&'static str),
+}
+
+fn hebrew_sound(letters: &[HebrewLetter], i: usize, pointed: bool) -> Sound {
+    let current = &letters[i];
+    let initial = i == 0;
+    let last = i + 1 == letters.len();
+    let hard = initial || current.has(DAGESH);
+    match current.letter {
+        'א' | 'ע' if pointed => Sound::Consonant(""),
+        'א' | 'ע' if initial || last => Sound::Vowel("a"),
+        'א' | 'ע' => Sound::Vowel(""),
+        'ב' if hard => Sound::Consonant("b"),
+        'ב' => Sound::Consonant("v"),
+        'ג' => Sound::Consonant("g"),
+        'ד' => Sound::Consonant("d"),
+        'ה' if last => Sound::Vowel(if pointed { "" } else { "a" }),
+        'ה' => Sound::Consonant("h"),
+        'ו' if current.has(DAGESH) && current.vowel().is_none() && !initial => Sound::Vowel("u"),
+        'ו' if current.has(HOLAM) => Sound::Vowel("o"),
+        'ו' if initial || pointed || letters.get(i + 1).is_some_and(|n| n.letter == 'ו') => {
+            Sound::Consonant("v")
+        }
+        'ו' if i > 0 && letters[i - 1].letter == 'ו' => Sound::Consonant(""),
+        'ו' => Sound::Vowel("o"),
+        'ז' => Sound::Consonant("z"),
+        'ח' => Sound::Consonant("ch"),
+        'ט' => Sound::Consonant("t"),
+        'י' if initial || pointed && current.vowel().is_some() => Sound::Consonant("y"),
+        'י' if pointed => Sound::Vowel(""),
+        'י' => Sound::Vowel("i"),
+        'כ' if hard => Sound::Consonant("k"),
+        'כ' | 'ך' => Sound::Consonant("kh"),
+        'ל' => Sound::Consonant("l"),
+        'מ' | 'ם' => Sound::Consonant("m"),
+        'נ' | 'ן' => Sound::Consonant("n"),
+        'ס' => Sound::Consonant("s"),
+        'פ' if hard => Sound::Consonant("p"),
+        'פ' | 'ף' => Sound::Consonant("f"),
+        'צ' | 'ץ' => Sound::Consonant("ts"),
+        'ק' => Sound::Consonant("k"),
+        'ר' => Sound::Consonant("r"),
+        'ש' if current.has(SIN_DOT) => Sound::Consonant("s"),
+        'ש' => Sound::Consonant("sh"),
+        'ת' => Sound::Consonant("t"),
+        _ => Sound::Consonant(""),
+    }
+}
+
+fn transliterate_hebrew_word(word: &[HebrewLetter]) -> String {
+    let pointed = word.iter().any(|l| l.vowel().is_some() || l.has(SHIN_DOT));
+    if !pointed {
+        let spelling: String = word.iter().map(|l| l.letter).collect();
+        if let Some((_, known)) = HEBREW_WORDS.iter().find(|(w, _)| *w == spelling) {
+            return known.to_string();
+        }
+    }
+    let mut out = String::new();
+    let mut previous_consonant = false;
+    for (i, letter) in word.iter().enumerate() {
+        match hebrew_sound(word, i, pointed) {
+            Sound::Consonant(sound) => {
+                if !pointed && previous_consonant {
+                    out.push('a');
+                }
+                out.push_str(sound);
+                previous_consonant = !sound.is_empty();
+                if pointed && let Some(vowel) = letter.vowel() {
+                    out.push_str(vowel);
+                }
+            }
+            // A vowel letter's own point (holam on vav) is already its sound.
+            Sound::Vowel(sound) => {
+                out.push_str(sound);
+                previous_consonant = false;
+            }
+        }
+    }
+    out
+}
+
+fn transliterate_hebrew(text: &str) -> String {
+    let mut out = String::new();
+    let mut word: Vec<HebrewLetter> = Vec::new();
+    for ch in text.chars() {
+        if is_hebrew_letter(ch) {
+            word.push(HebrewLetter {
+                letter: ch,
+                marks: Vec::new(),
+            });
+        } else if is_hebrew_mark(ch) {
+            if let Some(letter) = word.last_mut() {
+                letter.marks.push(ch);
+            }
+        } else {
+            out.push_str(&transliterate_hebrew_word(&word));
+            word.clear();
+            out.push(match ch {
+                '\u{05BE}' => '-',
+                '\u{05F3}' => '\'',
+                '\u{05F4}' => '"',
+                other => other,
+            });
+        }
+    }
+    out.push_str(&transliterate_hebrew_word(&word));
+    out
+}
+
+/// Romanizes `text` written in language `from` (ISO 639-1) for readers of
+/// the Latin-script language `to`, with the table for that pair. Characters
+/// outside the source script are kept as they are. Unpointed Hebrew outside
+/// a table of common words and names gets guessed vowels, e.g. "ספר"
+/// becomes "safar", not "sefer".
+pub fn transliterate(
+    text: &str,
+    from: &str,
+    to: &str,
+    scheme: TransliterationScheme,
+) -> Result<String, String> {
+    match table(from, to, scheme) {
+        Some(Table::Cyrillic(table)) => Ok(transliterate_cyrillic(text, table)),
+        Some(Table::Hebrew) => Ok(transliterate_hebrew(text)),
+        None if !LATIN_LANGUAGES.contains(&to) => Err(format!(
+            "No transliteration table from {} to {}: the target must be a Latin-script language",
+            from, to
+        )),
+        None => Err(format!("No transliteration table from {} to {}", from, to)),
+    }
+}
+
+/// [`transliterate`] between the languages of two layouts.
+pub fn transliterate_between(
+    text: &str,
+    from: &KeyboardLayout,
+    to: &KeyboardLayout,
+    scheme: TransliterationScheme,
+) -> Result<String, String> {
+    let language = |layout: &KeyboardLayout| {
+        layout
+            .language_code()
+            .ok_or_else(|| format!("Unknown language for layout {}", layout.lang_name))
+    };
+    transliterate(text, language(from)?, language(to)?, scheme)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,162 @@
+use super::*;
+use crate::keyboard_mapping::types::KeyboardDirection;
+
+fn ru(text: &str, scheme: TransliterationScheme) -> String {
+    transliterate(text, "ru", "en", scheme).unwrap()
+}
+
+#[test]
+fn test_scheme_from_str() {
+    assert_eq!("ISO9".parse(), Ok(TransliterationScheme::Iso9));
+    assert_eq!(" bgn ".parse(), Ok(TransliterationScheme::Bgn));
+    assert!("gost".parse::<TransliterationScheme>().is_err());
+}
+
+#[test]
+fn test_russian_bgn() {
+    let scheme = TransliterationScheme::Bgn;
+    assert_eq!(ru("Щукин", scheme), "Shchukin");
+    assert_eq!(ru("Елена Юрьевна", scheme), "Yelena Yur'yevna");
+    assert_eq!(ru("ЖУКОВ", scheme), "ZHUKOV");
+    assert_eq!(ru("Москва, 2024", scheme), "Moskva, 2024");
+}
+
+#[test]
+fn test_russian_iso9() {
+    let scheme = TransliterationScheme::Iso9;
+    assert_eq!(ru("Щукин", scheme), "Ŝukin");
+    assert_eq!(ru("Елена Юрьевна", scheme), "Elena Ûrʹevna");
+    assert_eq!(ru("ЖУКОВ", scheme), "ŽUKOV");
+}
+
+#[test]
+fn test_default_scheme_is_bgn_for_cyrillic() {
+    assert_eq!(
+        ru("Привет", TransliterationScheme::Default),
+        ru("Привет", TransliterationScheme::Bgn)
+    );
+}
+
+#[test]
+fn test_ukrainian_bgn_initial_forms() {
+    let uk = |text| transliterate(text, "uk", "en", TransliterationScheme::Bgn).unwrap();
+    assert_eq!(uk("Юлія"), "Yuliia");
+    assert_eq!(uk("Київ"), "Kyiv");
+    assert_eq!(uk("Гнатюк"), "Hnatiuk");
+}
+
+#[test]
+fn test_hebrew_unpointed() {
+    let he = |text| transliterate(text, "he", "en", TransliterationScheme::Default).unwrap();
+    assert_eq!(he("שלום"), "shalom");
+    assert_eq!(he("דוד כהן"), "david cohen");
+    assert_eq!(he("בוקר טוב, ישראל"), "boker tov, yisrael");
+    // Words outside the table get "a" between consonants.
+    assert_eq!(he("ספר"), "safar");
+}
+
+#[test]
+fn test_hebrew_pointed() {
+    let he = |text| transliterate(text, "he", "en", TransliterationScheme::Default).unwrap();
+    assert_eq!(he("שָׁלוֹם"), "shalom");
+    assert_eq!(he("מֹשֶׁה"), "moshe");
+    assert_eq!(he("יִשְׂרָאֵל"), "yisrael");
+}
+
+#[test]
+fn test_missing_table() {
+    assert_eq!(
+        transliterate("שלום", "he", "en", TransliterationScheme::Iso9),
+        Err("No transliteration table from he to en".to_string())
+    );
+    assert!(transliterate("Привет", "ru", "he", TransliterationScheme::Bgn).is_err());
+    assert!(transliterate("hello", "en", "de", TransliterationScheme::Default).is_err());
+}
+
+#[test]
+fn test_tables_per_language_pair() {
+    assert_eq!(
+        transliterate("Щукин", "ru", "de", TransliterationScheme::Iso9),
+        Ok("Ŝukin".to_string())
+    );
+    assert_eq!(
+        transliterate("Щукин", "ru", "de", TransliterationScheme::Default),
+        Err("No transliteration table from ru to de".to_string())
+    );
+    assert_eq!(
+        transliterate("שלום", "he", "fr", TransliterationScheme::Default),
+        Err("No transliteration table from he to fr".to_string())
+    );
+    assert_eq!(
+        transliterate("Щукин", "ru", "uk", TransliterationScheme::Iso9),
+        Err(
+            "No transliteration table from ru to uk: the target must be a Latin-script language"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn test_source_language_from_script() {
+    assert_eq!(source_language("Щукин", None), Some("ru"));
+    assert_eq!(source_language("Київ", None), Some("uk"));
+    assert_eq!(source_language("Мінск, Беларусь — ўсё", None), Some("be"));
+    assert_eq!(source_language("  דוד כהן", None), Some("he"));
+    assert_eq!(source_language("hello", None), None);
+    assert_eq!(source_language("123", None), None);
+}
+
+#[test]
+fn test_source_language_prefers_layout_of_the_same_script() {
+    let layout = |name: &str| KeyboardLayout {
+        id: name.to_string(),
+        lang_name: name.to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
+    };
+    assert_eq!(
+        source_language("Привет", Some(&layout("Ukrainian"))),
+        Some("uk")
+    );
+    assert_eq!(
+        source_language("Привет", Some(&layout("English (US)"))),
+        Some("ru")
+    );
+}
+
+#[test]
+fn test_transliterate_between_layouts() {
+    let russian = KeyboardLayout {
+        id: "Russian".to_string(),
+        lang_name: "Russian".to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
+    };
+    let english = KeyboardLayout {
+        id: "English (US)".to_string(),
+        lang_name: "English (US)".to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
+    };
+    assert_eq!(
+        transliterate_between("Иван", &russian, &english, TransliterationScheme::Default),
+        Ok("Ivan".to_string())
+    );
+    let unknown = KeyboardLayout {
+        id: "Klingon".to_string(),
+        lang_name: "Klingon".to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
+    };
+    assert!(
+        transliterate_between("Иван", &unknown, &english, TransliterationScheme::Default).is_err()
+    );
+}
@@ -1,21 +1,305 @@
+use super::physical_key::PhysicalKey;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct KeyboardLayout {
+    /// Identifies the layout across runs and machines: the XKB layout and
+    /// variant (`il(phonetic)`) on Linux, the keyboard layout id (KLID,
+    /// `0000040D`) on Windows.
+    pub id: String,
     pub lang_name: String,
     pub direction: KeyboardDirection,
+    /// The XKB variant the layout was compiled with, e.g. `dvorak`.
+    pub variant: Option<String>,
+    /// BCP 47 tag of the language the layout types, e.g. `he` or `en-US`.
+    pub language: Option<String>,
+    /// ISO 15924 code of the script the layout types, e.g. `Hebr`.
+    pub script: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// English language names (as reported by XKB and Windows) and XKB layout
+// names, paired with the ISO 639-1 code of the language they type.
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("german", "de"),
+    ("french", "fr"),
+    ("spanish", "es"),
+    ("russian", "ru"),
+    ("ukrainian", "uk"),
+    ("belarusian", "be"),
+    ("hebrew", "he"),
+    ("arabic", "ar"),
+    ("greek", "el"),
+    ("persian", "fa"),
+];
+
+const XKB_LAYOUT_LANGUAGES: &[(&str, &str)] = &[
+    ("us", "en"),
+    ("gb", "en"),
+    ("de", "de"),
+    ("fr", "fr"),
+    ("es", "es"),
+    ("ru", "ru"),
+    ("ua", "uk"),
+    ("by", "be"),
+    ("il", "he"),
+    ("ara", "ar"),
+    ("gr", "el"),
+    ("ir", "fa"),
+];
+
+// Scripts of languages written in a single script, from the IANA
+// language subtag registry's Suppress-Script field.
+const LANGUAGE_SCRIPTS: &[(&str, &str)] = &[
+    ("en", "Latn"),
+    ("de", "Latn"),
+    ("fr", "Latn"),
+    ("es", "Latn"),
+    ("it", "Latn"),
+    ("pt", "Latn"),
+    ("nl", "Latn"),
+    ("pl", "Latn"),
+    ("cs", "Latn"),
+    ("sk", "Latn"),
+    ("hu", "Latn"),
+    ("ro", "Latn"),
+    ("hr", "Latn"),
+    ("sl", "Latn"),
+    ("sv", "Latn"),
+    ("no", "Latn"),
+    ("nb", "Latn"),
+    ("da", "Latn"),
+    ("fi", "Latn"),
+    ("et", "Latn"),
+    ("lv", "Latn"),
+    ("lt", "Latn"),
+    ("tr", "Latn"),
+    ("vi", "Latn"),
+    ("ru", "Cyrl"),
+    ("uk", "Cyrl"),
+    ("be", "Cyrl"),
+    ("bg", "Cyrl"),
+    ("kk", "Cyrl"),
+    ("el", "Grek"),
+    ("he", "Hebr"),
+    ("ar", "Arab"),
+    ("fa", "Arab"),
+    ("ur", "Arab"),
+    ("hi", "Deva"),
+    ("bn", "Beng"),
+    ("th", "Thai"),
+    ("ka", "Geor"),
+    ("hy", "Armn"),
+    ("ja", "Jpan"),
+    ("ko", "Kore"),
+];
+
+/// The script a BCP 47 language tag is written in, when the language has a
+/// single script.
+pub fn script_for_language(language: &str) -> Option<&'static str> {
+    let primary = language.split('-').next()?.to_lowercase();
+    LANGUAGE_SCRIPTS
+        .iter()
+        .find(|(code, _)| *code == primary)
+        .map(|(_, script)| *script)
+}
+
+impl KeyboardLayout {
+    pub fn language_code(&self) -> Option<&'static str> {
+        if let Some(primary) = self
+            .language
+            .as_deref()
+            .and_then(|tag| tag.split('-').next())
+            .map(str::to_lowercase)
+            && let Some((_, code)) = LANGUAGE_NAMES.iter().find(|(_, code)| *code == primary)
+        {
+            return Some(code);
+        }
+        let name = self.lang_name.trim().to_lowercase();
+        XKB_LAYOUT_LANGUAGES
+            .iter()
+            .chain(LANGUAGE_NAMES.iter())
+            .find(|(known, code)| name == *known || name == *code)
+            .or_else(|| {
+                LANGUAGE_NAMES
+                    .iter()
+                    .find(|(known, _)| name.starts_with(known))
+            })
+            .map(|(_, code)| *code)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum KeyboardDirection {
     LTR,
     RTL,
 }
 
+/// Modifier combination a key is pressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShiftLevel {
+    Base,
+    Shift,
+    AltGr,
+    ShiftAltGr,
+}
+
+impl ShiftLevel {
+    pub const ALL: [ShiftLevel; 4] = [
+        ShiftLevel::Base,
+        ShiftLevel::Shift,
+        ShiftLevel::AltGr,
+        ShiftLevel::ShiftAltGr,
+    ];
+
+    pub fn has_shift(self) -> bool {
+        matches!(self, ShiftLevel::Shift | ShiftLevel::ShiftAltGr)
+    }
+
+    pub fn has_altgr(self) -> bool {
+        matches!(self, ShiftLevel::AltGr | ShiftLevel::ShiftAltGr)
+    }
+}
+
+/// Where a key sits on the keyboard. When several keys type the same
+/// character, earlier classes are preferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KeyClass {
+    /// The alphanumeric block, including the space bar.
+    Main,
+    Other,
+    Keypad,
+}
+
+/// A key pressed at a given shift level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct KeyStroke {
+    pub key: PhysicalKey,
+    pub level: ShiftLevel,
+}
+
+impl KeyStroke {
+    pub fn new(key: PhysicalKey, level: ShiftLevel) -> Self {
+        KeyStroke { key, level }
+    }
+
+    pub fn base(key: PhysicalKey) -> Self {
+        KeyStroke::new(key, ShiftLevel::Base)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LayoutMap {
     pub layout: KeyboardLayout,
-    pub map: HashMap<u16, String>,
+    pub map: HashMap<KeyStroke, String>,
+    /// Outputs that take more than one keystroke, such as a dead key
+    /// followed by a letter.
+    pub sequences: HashMap<Vec<KeyStroke>, String>,
+}
+
+impl LayoutMap {
+    pub fn new(layout: KeyboardLayout, map: HashMap<KeyStroke, String>) -> Self {
+        LayoutMap {
+            layout,
+            map,
+            sequences: HashMap::new(),
+        }
+    }
+
+    pub fn output(&self, key: PhysicalKey, level: ShiftLevel) -> Option<&str> {
+        self.map
+            .get(&KeyStroke::new(key, level))
+            .map(String::as_str)
+    }
+}
+
+/// Picks one layout out of the configured ones, by position or by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutSelector {
+    /// Position in the configured layout list, starting at 0.
+    Index(usize),
+    /// A layout id ("il(phonetic)"), a layout name ("Hebrew"), a prefix of
+    /// one ("English"), or a language or XKB layout code ("he", "us").
+    Name(String),
+}
+
+impl LayoutSelector {
+    /// The language a name selector names as a language or XKB layout code
+    /// or a language name ("he", "il", "Hebrew").
+    pub fn language_code(&self) -> Option<&'static str> {
+        let LayoutSelector::Name(name) = self else {
+            return None;
+        };
+        let name = name.trim().to_lowercase();
+        KeyboardLayout {
+            id: name.clone(),
+            lang_name: name,
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
+        }
+        .language_code()
+    }
+
+    /// Finds the selected layout: an exact id wins over an exact name, then a
+    /// matching language code, then a name prefix.
+    pub fn select<'a>(&self, layout_maps: &'a [LayoutMap]) -> Option<&'a LayoutMap> {
+        let name = match self {
+            LayoutSelector::Index(index) => return layout_maps.get(*index),
+            LayoutSelector::Name(name) => name.trim().to_lowercase(),
+        };
+        let code = self.language_code();
+        layout_maps
+            .iter()
+            .find(|lm| lm.layout.id.to_lowercase() == name)
+            .or_else(|| {
+                layout_maps
+                    .iter()
+                    .find(|lm| lm.layout.lang_name.to_lowercase() == name)
+            })
+            .or_else(|| {
+                code.and_then(|code| {
+                    layout_maps
+                        .iter()
+                        .find(|lm| lm.layout.language_code() == Some(code))
+                })
+            })
+            .or_else(|| {
+                layout_maps
+                    .iter()
+                    .find(|lm| lm.layout.lang_name.to_lowercase().starts_with(&name))
+            })
+    }
+}
+
+impl FromStr for LayoutSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if trimmed.is_empty() {
+            return Err("Layout cannot be empty".to_string());
+        }
+        Ok(match trimmed.parse::<usize>() {
+            Ok(index) => LayoutSelector::Index(index),
+            Err(_) => LayoutSelector::Name(trimmed.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for LayoutSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutSelector::Index(index) => write!(f, "{}", index),
+            LayoutSelector::Name(name) => write!(f, "{}", name),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -18,8 +18,12 @@ fn test_keyboard_direction_copy() {
 #[test]
 fn test_keyboard_layout_creation() {
     let layout = KeyboardLayout {
+        id: "English".to_string(),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     assert_eq!(layout.lang_name, "English");
@@ -29,18 +33,30 @@ fn test_keyboard_layout_creation() {
 #[test]
 fn test_keyboard_layout_equality() {
     let layout1 = KeyboardLayout {
+        id: "Hebrew".to_string(),
         lang_name: "Hebrew".to_string(),
         direction: KeyboardDirection::RTL,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     let layout2 = KeyboardLayout {
+        id: "Hebrew".to_string(),
         lang_name: "Hebrew".to_string(),
         direction: KeyboardDirection::RTL,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     let layout3 = KeyboardLayout {
+        id: "Arabic".to_string(),
         lang_name: "Arabic".to_string(),
         direction: KeyboardDirection::RTL,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     assert_eq!(layout1, layout2);
@@ -50,8 +66,12 @@ fn test_keyboard_layout_equality() {
 #[test]
 fn test_keyboard_layout_clone() {
     let original = KeyboardLayout {
+        id: "French".to_string(),
         lang_name: "French".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     let cloned = original.clone();
@@ -63,8 +83,12 @@ fn test_keyboard_layout_clone() {
 #[test]
 fn test_keyboard_layout_debug() {
     let layout = KeyboardLayout {
+        id: "German".to_string(),
         lang_name: "German".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     let debug_str = format!("{:?}", layout);
@@ -75,48 +99,68 @@ fn test_keyboard_layout_debug() {
 #[test]
 fn test_layout_map_creation() {
     let mut map = HashMap::new();
-    map.insert(0x41, "A".to_string());
-    map.insert(0x42, "B".to_string());
+    map.insert(KeyStroke::base(PhysicalKey::KeyA), "A".to_string());
+    map.insert(KeyStroke::base(PhysicalKey::KeyB), "B".to_string());
 
     let layout = KeyboardLayout {
+        id: "English".to_string(),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
-    let layout_map = LayoutMap { layout, map };
+    let layout_map = LayoutMap::new(layout, map);
 
     assert_eq!(layout_map.layout.lang_name, "English");
     assert_eq!(layout_map.map.len(), 2);
-    assert_eq!(layout_map.map.get(&0x41), Some(&"A".to_string()));
-    assert_eq!(layout_map.map.get(&0x42), Some(&"B".to_string()));
+    assert_eq!(
+        layout_map.map.get(&KeyStroke::base(PhysicalKey::KeyA)),
+        Some(&"A".to_string())
+    );
+    assert_eq!(
+        layout_map.map.get(&KeyStroke::base(PhysicalKey::KeyB)),
+        Some(&"B".to_string())
+    );
 }
 
 #[test]
 fn test_layout_map_equality() {
     let mut map1 = HashMap::new();
-    map1.insert(0x41, "A".to_string());
+    map1.insert(KeyStroke::base(PhysicalKey::KeyA), "A".to_string());
 
     let mut map2 = HashMap::new();
-    map2.insert(0x41, "A".to_string());
+    map2.insert(KeyStroke::base(PhysicalKey::KeyA), "A".to_string());
 
     let layout1 = KeyboardLayout {
+        id: "English".to_string(),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     let layout2 = KeyboardLayout {
+        id: "English".to_string(),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     let layout_map1 = LayoutMap {
         layout: layout1,
         map: map1,
+        sequences: HashMap::new(),
     };
 
     let layout_map2 = LayoutMap {
         layout: layout2,
         map: map2,
+        sequences: HashMap::new(),
     };
 
     assert_eq!(layout_map1, layout_map2);
@@ -125,16 +169,21 @@ fn test_layout_map_equality() {
 #[test]
 fn test_layout_map_clone() {
     let mut map = HashMap::new();
-    map.insert(0x41, "A".to_string());
+    map.insert(KeyStroke::base(PhysicalKey::KeyA), "A".to_string());
 
     let layout = KeyboardLayout {
+        id: "English".to_string(),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     let original = LayoutMap {
         layout: layout.clone(),
         map: map.clone(),
+        sequences: HashMap::new(),
     };
 
     let cloned = original.clone();
@@ -146,14 +195,18 @@ fn test_layout_map_clone() {
 #[test]
 fn test_layout_map_debug() {
     let mut map = HashMap::new();
-    map.insert(0x41, "A".to_string());
+    map.insert(KeyStroke::base(PhysicalKey::KeyA), "A".to_string());
 
     let layout = KeyboardLayout {
+        id: "English".to_string(),
         lang_name: "English".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
-    let layout_map = LayoutMap { layout, map };
+    let layout_map = LayoutMap::new(layout, map);
 
     let debug_str = format!("{:?}", layout_map);
     assert!(debug_str.contains("English"));
@@ -163,15 +216,210 @@ fn test_layout_map_debug() {
 #[test]
 fn test_empty_layout_map() {
     let layout = KeyboardLayout {
+        id: "Empty".to_string(),
         lang_name: "Empty".to_string(),
         direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
     };
 
     let layout_map = LayoutMap {
         layout,
         map: HashMap::new(),
+        sequences: HashMap::new(),
     };
 
     assert_eq!(layout_map.map.len(), 0);
     assert_eq!(layout_map.layout.lang_name, "Empty");
 }
+
+#[test]
+fn test_language_code_from_display_and_xkb_names() {
+    let layout = |name: &str| KeyboardLayout {
+        id: name.to_string(),
+        lang_name: name.to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
+    };
+
+    assert_eq!(layout("English (US)").language_code(), Some("en"));
+    assert_eq!(layout("Hebrew (Israel)").language_code(), Some("he"));
+    assert_eq!(layout("German").language_code(), Some("de"));
+    assert_eq!(layout("ua").language_code(), Some("uk"));
+    assert_eq!(layout("ru").language_code(), Some("ru"));
+    assert_eq!(layout("Klingon").language_code(), None);
+}
+
+#[test]
+fn test_shift_level_modifiers() {
+    assert!(!ShiftLevel::Base.has_shift());
+    assert!(!ShiftLevel::Base.has_altgr());
+    assert!(ShiftLevel::Shift.has_shift());
+    assert!(!ShiftLevel::Shift.has_altgr());
+    assert!(!ShiftLevel::AltGr.has_shift());
+    assert!(ShiftLevel::AltGr.has_altgr());
+    assert!(ShiftLevel::ShiftAltGr.has_shift());
+    assert!(ShiftLevel::ShiftAltGr.has_altgr());
+}
+
+#[test]
+fn test_layout_map_output_by_level() {
+    let mut map = HashMap::new();
+    map.insert(KeyStroke::base(PhysicalKey::KeyA), "a".to_string());
+    map.insert(
+        KeyStroke::new(PhysicalKey::KeyA, ShiftLevel::Shift),
+        "A".to_string(),
+    );
+
+    let layout_map = LayoutMap {
+        layout: KeyboardLayout {
+            id: "English".to_string(),
+            lang_name: "English".to_string(),
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        map,
+        sequences: HashMap::new(),
+    };
+
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("a")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyA, ShiftLevel::Shift),
+        Some("A")
+    );
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyA, ShiftLevel::AltGr),
+        None
+    );
+}
+
+#[test]
+fn test_layout_map_new_has_no_sequences() {
+    let layout = KeyboardLayout {
+        id: "English".to_string(),
+        lang_name: "English".to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: None,
+        script: None,
+    };
+    let mut map = HashMap::new();
+    map.insert(KeyStroke::base(PhysicalKey::KeyA), "a".to_string());
+    let layout_map = LayoutMap::new(layout, map);
+    assert_eq!(
+        layout_map.output(PhysicalKey::KeyA, ShiftLevel::Base),
+        Some("a")
+    );
+    assert!(layout_map.sequences.is_empty());
+}
+
+fn named_layout_map(name: &str) -> LayoutMap {
+    LayoutMap::new(
+        KeyboardLayout {
+            id: name.to_string(),
+            lang_name: name.to_string(),
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: None,
+            script: None,
+        },
+        HashMap::new(),
+    )
+}
+
+#[test]
+fn test_layout_selector_parse() {
+    assert_eq!("2".parse(), Ok(LayoutSelector::Index(2)));
+    assert_eq!(
+        " Hebrew ".parse(),
+        Ok(LayoutSelector::Name("Hebrew".to_string()))
+    );
+    assert!("".parse::<LayoutSelector>().is_err());
+}
+
+#[test]
+fn test_layout_selector_select() {
+    let layouts = vec![
+        named_layout_map("English (US)"),
+        named_layout_map("Hebrew"),
+        named_layout_map("English (Dvorak)"),
+    ];
+    let select = |s: &str| {
+        s.parse::<LayoutSelector>()
+            .unwrap()
+            .select(&layouts)
+            .map(|lm| lm.layout.lang_name.as_str())
+    };
+    assert_eq!(select("1"), Some("Hebrew"));
+    assert_eq!(select("hebrew"), Some("Hebrew"));
+    assert_eq!(select("he"), Some("Hebrew"));
+    assert_eq!(select("il"), Some("Hebrew"));
+    assert_eq!(select("us"), Some("English (US)"));
+    assert_eq!(select("english (dvorak)"), Some("English (Dvorak)"));
+    assert_eq!(select("3"), None);
+    assert_eq!(select("Russian"), None);
+}
+
+fn xkb_layout_map(id: &str, name: &str, language: &str) -> LayoutMap {
+    LayoutMap::new(
+        KeyboardLayout {
+            id: id.to_string(),
+            lang_name: name.to_string(),
+            direction: KeyboardDirection::LTR,
+            variant: None,
+            language: Some(language.to_string()),
+            script: script_for_language(language).map(str::to_string),
+        },
+        HashMap::new(),
+    )
+}
+
+#[test]
+fn test_layout_selector_prefers_ids() {
+    let layouts = vec![
+        xkb_layout_map("us", "English (US)", "en"),
+        xkb_layout_map("il", "Hebrew", "he"),
+        xkb_layout_map("il(phonetic)", "Hebrew (phonetic)", "he"),
+    ];
+    let select = |s: &str| {
+        s.parse::<LayoutSelector>()
+            .unwrap()
+            .select(&layouts)
+            .map(|lm| lm.layout.id.as_str())
+    };
+    assert_eq!(select("il(phonetic)"), Some("il(phonetic)"));
+    assert_eq!(select("IL"), Some("il"));
+    assert_eq!(select("he"), Some("il"));
+    assert_eq!(select("Hebrew (phonetic)"), Some("il(phonetic)"));
+}
+
+#[test]
+fn test_language_code_from_language_tag() {
+    let layout = |language: &str| KeyboardLayout {
+        id: "0000040D".to_string(),
+        lang_name: "Unnamed".to_string(),
+        direction: KeyboardDirection::LTR,
+        variant: None,
+        language: Some(language.to_string()),
+        script: None,
+    };
+    assert_eq!(layout("he-IL").language_code(), Some("he"));
+    assert_eq!(layout("RU").language_code(), Some("ru"));
+    assert_eq!(layout("tlh").language_code(), None);
+}
+
+#[test]
+fn test_script_for_language() {
+    assert_eq!(script_for_language("he"), Some("Hebr"));
+    assert_eq!(script_for_language("en-US"), Some("Latn"));
+    assert_eq!(script_for_language("uk"), Some("Cyrl"));
+    assert_eq!(script_for_language("sr"), None);
+}
@@ -1,14 +1,27 @@
 #![cfg(target_os = "windows")]
 use windows::Win32::{
     Globalization::{GetLocaleInfoEx, LCIDToLocaleName, LOCALE_SLANGUAGE},
+    System::Registry::{
+        HKEY, HKEY_LOCAL_MACHINE, KEY_READ, RRF_RT_REG_SZ, RegCloseKey, RegEnumKeyExW,
+        RegGetValueW, RegOpenKeyExW,
+    },
     UI::Input::KeyboardAndMouse::{
-        GetKeyboardLayout, GetKeyboardLayoutList, HKL, MAPVK_VK_TO_VSC_EX, MapVirtualKeyExW,
+        GetKeyboardLayout, GetKeyboardLayoutList, HKL, MAPVK_VSC_TO_VK_EX, MapVirtualKeyExW,
         ToUnicodeEx,
     },
 };
 
-use super::types::{KeyboardDirection, KeyboardLayout, LayoutMap};
+use super::cache::{cache_key, cached_layout_maps};
+use super::custom::with_configured_layouts;
+use super::physical_key::PhysicalKey;
+use super::types::{
+    KeyStroke, KeyboardDirection, KeyboardLayout, LayoutMap, ShiftLevel, script_for_language,
+};
+use crate::config::Config;
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::PathBuf;
+use windows::core::{HSTRING, PWSTR, w};
 
 #[cfg(target_os = "windows")]
 fn windows_langid_is_rtl(langid: u16) -> bool {
@@ -70,6 +83,125 @@ fn lang_name_from_langid(langid: u16) -> String {
     }
 }
 
+/// The BCP 47 tag of a language id, e.g. `he-IL`.
+fn language_from_langid(langid: u16) -> Option<String> {
+    unsafe {
+        const LOCALE_NAME_MAX_LENGTH: usize = 85;
+        let mut locale_name_buf = [0u16; LOCALE_NAME_MAX_LENGTH];
+        let len = LCIDToLocaleName(langid as u32, Some(&mut locale_name_buf), 0);
+        if len <= 0 {
+            return None;
+        }
+        Some(String::from_utf16_lossy(
+            &locale_name_buf[..(len as usize - 1)],
+        ))
+    }
+}
+
+/// The registry key listing the installed layouts, one subkey per KLID.
+const KEYBOARD_LAYOUTS_KEY: windows::core::PCWSTR =
+    w!(r"SYSTEM\CurrentControlSet\Control\Keyboard Layouts");
+
+/// The string value `name` of the layout `klid` under `key`.
+fn registry_layout_value(key: HKEY, klid: &str, name: windows::core::PCWSTR) -> Option<String> {
+    let mut buf = [0u16; 260];
+    let mut size = std::mem::size_of_val(&buf) as u32;
+    unsafe {
+        RegGetValueW(
+            key,
+            &HSTRING::from(klid),
+            name,
+            RRF_RT_REG_SZ,
+            None,
+            Some(buf.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+        .ok()
+        .ok()?;
+    }
+    let value = String::from_utf16_lossy(&buf[..size as usize / 2]);
+    Some(value.trim_end_matches('\0').trim().to_string())
+}
+
+/// The `Layout Id` value of the layout `klid` under `key`.
+fn registry_layout_id(key: HKEY, klid: &str) -> Option<u16> {
+    u16::from_str_radix(&registry_layout_value(key, klid, w!("Layout Id"))?, 16).ok()
+}
+
+/// Opens `KEYBOARD_LAYOUTS_KEY` for `read`, closing it afterwards.
+fn with_keyboard_layouts_key<T>(read: impl FnOnce(HKEY) -> Option<T>) -> Option<T> {
+    unsafe {
+        let mut key = HKEY::default();
+        RegOpenKeyExW(
+            HKEY_LOCAL_MACHINE,
+            KEYBOARD_LAYOUTS_KEY,
+            None,
+            KEY_READ,
+            &mut key,
+        )
+        .ok()
+        .ok()?;
+        let value = read(key);
+        let _ = RegCloseKey(key);
+        value
+    }
+}
+
+/// The DLL the layout `klid` is loaded from, named by its `Layout File`
+/// value and installed in the system directory.
+fn layout_dll(klid: &str) -> Option<PathBuf> {
+    let file =
+        with_keyboard_layouts_key(|key| registry_layout_value(key, klid, w!("Layout File")))?;
+    let system_root = std::env::var_os("SystemRoot").unwrap_or_else(|| r"C:\Windows".into());
+    Some(PathBuf::from(system_root).join("System32").join(file))
+}
+
+/// The KLID whose `Layout Id` is `layout_id`, looked up under
+/// `KEYBOARD_LAYOUTS_KEY`.
+fn klid_for_layout_id(layout_id: u16) -> Option<String> {
+    with_keyboard_layouts_key(|key| unsafe {
+        let mut found = None;
+        let mut name = [0u16; 256];
+        for index in 0.. {
+            let mut len = name.len() as u32;
+            let status = RegEnumKeyExW(
+                key,
+                index,
+                Some(PWSTR(name.as_mut_ptr())),
+                &mut len,
+                None,
+                None,
+                None,
+                None,
+            );
+            if status.is_err() {
+                break;
+            }
+            let klid = String::from_utf16_lossy(&name[..len as usize]);
+            if registry_layout_id(key, &klid) == Some(layout_id) {
+                found = Some(klid);
+                break;
+            }
+        }
+        found
+    })
+}
+
+/// The KLID of the layout behind `hkl`, e.g. `00000409` for US or
+/// `00010409` for US-Dvorak. The handle's high word is the layout for the
+/// default layout of a language, the full handle for an IME, and otherwise
+/// a per-session `0xFxxx` handle whose low 12 bits are the layout's `Layout
+/// Id`, which `find_layout_id` resolves. The handle stands in when that
+/// fails.
+fn klid_from_hkl(hkl: u32, find_layout_id: impl FnOnce(u16) -> Option<String>) -> String {
+    let device = (hkl >> 16) as u16;
+    match device & 0xF000 {
+        0xF000 => find_layout_id(device & 0x0FFF).unwrap_or_else(|| format!("{:08X}", hkl)),
+        0xE000 => format!("{:08X}", hkl),
+        _ => format!("{:08X}", device),
+    }
+}
+
 fn keyboard_layout_from_hkl(hkl: HKL) -> KeyboardLayout {
     let langid = (hkl.0 as usize & 0xFFFF) as u16;
     let direction = if windows_langid_is_rtl(langid) {
@@ -77,9 +209,20 @@ fn keyboard_layout_from_hkl(hkl: HKL) -> KeyboardLayout {
     } else {
         KeyboardDirection::LTR
     };
+    let language = language_from_langid(langid);
+    let script = language
+        .as_deref()
+        .and_then(script_for_language)
+        .map(str::to_string);
     KeyboardLayout {
+        // Unlike the handle, the KLID is the same on every machine, and it
+        // tells apart e.g. US and US-Dvorak.
+        id: klid_from_hkl(hkl.0 as usize as u32, klid_for_layout_id),
         lang_name: lang_name_from_langid(langid),
         direction,
+        variant: None,
+        language,
+        script,
     }
 }
 
@@ -93,26 +236,102 @@ pub fn list_layouts() -> Vec<KeyboardLayout> {
     hkls.into_iter().map(keyboard_layout_from_hkl).collect()
 }
 
+const VK_SHIFT: usize = 0x10;
+const VK_CONTROL: usize = 0x11;
+const VK_MENU: usize = 0x12;
+
+fn key_state_for_level(level: ShiftLevel) -> [u8; 256] {
+    let mut state = [0u8; 256];
+    if level.has_shift() {
+        state[VK_SHIFT] = 0x80;
+    }
+    // AltGr is reported to applications as Ctrl+Alt.
+    if level.has_altgr() {
+        state[VK_CONTROL] = 0x80;
+        state[VK_MENU] = 0x80;
+    }
+    state
+}
+
 pub fn vk_to_char_map_for_layout(hkl: HKL) -> LayoutMap {
-    let hkls = enumerate_hkls();
     let layout = keyboard_layout_from_hkl(hkl);
     unsafe {
-        let mut map: HashMap<u16, String> = HashMap::new();
-        let state = [0u8; 256];
+        let mut map: HashMap<KeyStroke, String> = HashMap::new();
+        let mut dead_keys: Vec<(KeyStroke, u32, u32)> = Vec::new();
+        let mut live_keys: Vec<(KeyStroke, u32, u32)> = Vec::new();
         let mut buf = [0u16; 8];
-        for vk in 0u16..=255u16 {
-            let sc = MapVirtualKeyExW(vk as u32, MAPVK_VK_TO_VSC_EX, Some(hkl));
-            if sc == 0 {
-                continue;
+        for level in ShiftLevel::ALL {
+            let state = key_state_for_level(level);
+            for key in PhysicalKey::all() {
+                let sc = key.scan_code() as u32;
+                let vk = MapVirtualKeyExW(sc, MAPVK_VSC_TO_VK_EX, Some(hkl));
+                if vk == 0 {
+                    continue;
+                }
+                let stroke = KeyStroke::new(key, level);
+                let written = ToUnicodeEx(vk, sc, &state, &mut buf, 0, Some(hkl));
+                if written < 0 {
+                    // Dead key: press it again to clear the pending state.
+                    let _ = ToUnicodeEx(vk, sc, &state, &mut buf, 0, Some(hkl));
+                    dead_keys.push((stroke, vk, sc));
+                    continue;
+                }
+                if written == 0 {
+                    continue;
+                }
+                let s = String::from_utf16_lossy(&buf[..written as usize]);
+                if level != ShiftLevel::Base
+                    && (s.chars().any(char::is_control)
+                        || map.get(&KeyStroke::base(key)) == Some(&s))
+                {
+                    continue;
+                }
+                if let Entry::Vacant(slot) = map.entry(stroke) {
+                    slot.insert(s);
+                    live_keys.push((stroke, vk, sc));
+                }
             }
-            let written = ToUnicodeEx(vk as u32, sc, &state, &mut buf, 0, Some(hkl));
-            if written > 0 {
+        }
+        let mut layout_map = LayoutMap::new(layout, map);
+        layout_map.sequences = dead_key_sequences(hkl, &dead_keys, &live_keys);
+        layout_map
+    }
+}
+
+/// Outputs of every dead key followed by a regular key, as composed by the
+/// layout itself. Keys come with their virtual-key and scan codes.
+unsafe fn dead_key_sequences(
+    hkl: HKL,
+    dead_keys: &[(KeyStroke, u32, u32)],
+    live_keys: &[(KeyStroke, u32, u32)],
+) -> HashMap<Vec<KeyStroke>, String> {
+    let mut sequences = HashMap::new();
+    let mut buf = [0u16; 8];
+    for (dead, dead_vk, dead_sc) in dead_keys {
+        let dead_state = key_state_for_level(dead.level);
+        for (live, live_vk, live_sc) in live_keys {
+            let live_state = key_state_for_level(live.level);
+            unsafe {
+                if ToUnicodeEx(*dead_vk, *dead_sc, &dead_state, &mut buf, 0, Some(hkl)) >= 0 {
+                    continue;
+                }
+                let written = ToUnicodeEx(*live_vk, *live_sc, &live_state, &mut buf, 0, Some(hkl));
+                if written < 0 {
+                    let _ = ToUnicodeEx(*live_vk, *live_sc, &live_state, &mut buf, 0, Some(hkl));
+                    continue;
+                }
+                if written <= 0 {
+                    continue;
+                }
                 let s = String::from_utf16_lossy(&buf[..written as usize]);
-                map.entry(vk).or_insert(s);
+                // Two characters means the layout couldn't combine them.
+                if s.chars().count() == 1 {
+                    sequences.insert(vec![*dead, *live], s);
+                }
             }
         }
-        LayoutMap { layout, map }
     }
+    sequences
 }
 
 pub fn vk_to_char_map_default() -> LayoutMap {
@@ -120,8 +339,28 @@ pub fn vk_to_char_map_default() -> LayoutMap {
 }
 
 pub fn all_layout_vk_maps() -> Vec<LayoutMap> {
+    let cfg = crate::config::load_config().unwrap_or_else(|_| Config::with_defaults());
+    all_layout_vk_maps_for(&cfg)
+}
+
+/// `all_layout_vk_maps` for an already loaded config.
+pub fn all_layout_vk_maps_for(cfg: &Config) -> Vec<LayoutMap> {
     let hkls = enumerate_hkls();
-    hkls.into_iter().map(vk_to_char_map_for_layout).collect()
+    // Installed layouts are identified by their HKLs, and stamped with the
+    // mtime of their DLLs so reinstalling a layout rebuilds its map.
+    let configuration = hkls
+        .iter()
+        .map(|hkl| format!("{:016X}", hkl.0 as usize))
+        .collect::<Vec<_>>()
+        .join(",");
+    let dlls: Vec<PathBuf> = hkls
+        .iter()
+        .filter_map(|hkl| layout_dll(&klid_from_hkl(hkl.0 as usize as u32, klid_for_layout_id)))
+        .collect();
+    let maps = cached_layout_maps(&cache_key(&configuration, &dlls), || {
+        hkls.into_iter().map(vk_to_char_map_for_layout).collect()
+    });
+    with_configured_layouts(maps, cfg)
 }
 
 #[cfg(all(test, target_os = "windows"))]
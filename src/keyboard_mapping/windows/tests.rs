@@ -1,4 +1,5 @@
 use super::*;
+use crate::test_utils::use_temp_cache_dir;
 
 #[test]
 fn test_list_layouts_returns_non_empty() {
@@ -56,16 +57,17 @@ fn test_vk_to_char_map_default_structure() {
 fn test_vk_to_char_map_default_contains_common_keys() {
     let layout_map = vk_to_char_map_default();
 
-    let common_vks = vec![0x41, 0x42, 0x43, 0x20, 0x0D];
-
-    for vk in common_vks {
-        if layout_map.map.contains_key(&vk) {
-            let output = &layout_map.map[&vk];
-            assert!(
-                !output.is_empty(),
-                "VK_{:02X} should have non-empty output",
-                vk
-            );
+    let common_keys = vec![
+        PhysicalKey::KeyA,
+        PhysicalKey::KeyB,
+        PhysicalKey::KeyC,
+        PhysicalKey::Space,
+        PhysicalKey::Enter,
+    ];
+
+    for key in common_keys {
+        if let Some(output) = layout_map.map.get(&KeyStroke::base(key)) {
+            assert!(!output.is_empty(), "{} should have non-empty output", key);
         }
     }
 }
@@ -92,6 +94,7 @@ fn test_vk_to_char_map_for_layout_structure() {
 
 #[test]
 fn test_all_layout_vk_maps_structure() {
+    use_temp_cache_dir();
     let layout_maps = all_layout_vk_maps();
 
     assert!(
@@ -113,6 +116,7 @@ fn test_all_layout_vk_maps_structure() {
 
 #[test]
 fn test_all_layout_vk_maps_consistency() {
+    use_temp_cache_dir();
     let layouts = list_layouts();
     let layout_maps = all_layout_vk_maps();
 
@@ -135,3 +139,17 @@ fn test_all_layout_vk_maps_consistency() {
         }
     }
 }
+
+#[test]
+fn test_klid_from_hkl() {
+    let none = |_| None;
+    // The default layout of a language: the high word is the layout.
+    assert_eq!(klid_from_hkl(0x0409_0409, none), "00000409");
+    assert_eq!(klid_from_hkl(0x040D_040D, none), "0000040D");
+    // An IME: the handle is the KLID.
+    assert_eq!(klid_from_hkl(0xE001_0411, none), "E0010411");
+    // Other layouts get a per-session handle naming their `Layout Id`.
+    let dvorak = |layout_id| (layout_id == 0x0002).then(|| "00010409".to_string());
+    assert_eq!(klid_from_hkl(0xF002_0409, dvorak), "00010409");
+    assert_eq!(klid_from_hkl(0xF003_0409, dvorak), "F0030409");
+}
@@ -7,36 +7,311 @@ pub mod platform;
 #[cfg(test)]
 pub mod test_utils;
 
+use config::Config;
+pub use config::ShiftMode;
 pub use get_highlighted::{get_highlighted_text, replace_highlighted_text};
+use keyboard_mapping::builtin::no_layouts_error;
+use keyboard_mapping::{Dictionaries, LanguageModels, LayoutMap, select_layout, source_language};
 pub use keyboard_mapping::{
-    all_layout_vk_maps, get_layout, get_text_leyaout_map, list_layouts, shift_text_language,
+    LayoutSelector, TransliterationScheme, all_layout_vk_maps, all_layout_vk_maps_for,
+    best_target_layout, get_layout, get_text_leyaout_map, invert_case, is_caps_inverted,
+    list_layouts, shift_text_between, shift_text_language, toggle_caps_levels, transliterate,
     vk_to_char_map_default, vk_to_char_map_for_layout,
 };
+use std::str::FromStr;
 
-pub fn shift_highlighted_text_to_next_layout() -> Result<(), String> {
-    let highlighted_text = match get_highlighted_text() {
-        Some(text) => text,
-        None => return Err("No text is currently highlighted".to_string()),
+/// What `run` does with the highlighted text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunAction {
+    /// Convert the text to another layout.
+    #[default]
+    Shift,
+    /// Undo Caps Lock case inversion without changing layout.
+    FixCaps,
+    /// Undo Caps Lock case inversion, then convert to another layout.
+    ShiftFixCaps,
+    /// Convert the text to the layout before the detected one.
+    Previous,
+    /// Romanize the text phonetically instead of remapping keys.
+    Transliterate,
+}
+
+impl FromStr for RunAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "shift" => Ok(RunAction::Shift),
+            "fix-caps" => Ok(RunAction::FixCaps),
+            "shift-fix-caps" => Ok(RunAction::ShiftFixCaps),
+            "previous" => Ok(RunAction::Previous),
+            "transliterate" => Ok(RunAction::Transliterate),
+            other => Err(format!(
+                "Unknown action: {} (expected shift, fix-caps, shift-fix-caps, previous or transliterate)",
+                other
+            )),
+        }
+    }
+}
+
+/// Layouts given explicitly: `from` replaces detecting the layout of the
+/// text, `to` replaces picking the target by shift mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutChoice {
+    pub from: Option<LayoutSelector>,
+    pub to: Option<LayoutSelector>,
+}
+
+pub fn shift_highlighted_text(mode: ShiftMode) -> Result<(), String> {
+    shift_highlighted_text_with(mode, false, &LayoutChoice::default())
+}
+
+pub fn shift_highlighted_text_between(
+    from: LayoutSelector,
+    to: LayoutSelector,
+) -> Result<(), String> {
+    let choice = LayoutChoice {
+        from: Some(from),
+        to: Some(to),
     };
+    shift_highlighted_text_with(ShiftMode::default(), false, &choice)
+}
+
+fn source_layout<'a>(
+    text: &str,
+    from: Option<&LayoutSelector>,
+    layout_maps: &'a [LayoutMap],
+) -> Result<&'a LayoutMap, String> {
+    match from {
+        Some(selector) => select_layout(selector, layout_maps),
+        None => get_text_leyaout_map(text, layout_maps)
+            .ok_or_else(|| "Could not determine the layout of the text".to_string()),
+    }
+}
 
-    let layout_maps = all_layout_vk_maps();
+fn highlighted_text() -> Result<String, String> {
+    get_highlighted_text().ok_or_else(|| "No text is currently highlighted".to_string())
+}
+
+/// The config and the layouts it configures, loading the config once. A
+/// config that fails to load is an error here: falling back to the defaults
+/// would convert with other layouts than the configured ones. So is having
+/// no layout at all.
+fn configured_layout_maps() -> Result<(Config, Vec<LayoutMap>), String> {
+    let cfg = config::load_config()?;
+    let layout_maps = all_layout_vk_maps_for(&cfg);
+    if layout_maps.is_empty() {
+        return Err(no_layouts_error());
+    }
+    Ok((cfg, layout_maps))
+}
+
+/// Replaces the highlighted text with what `convert` makes of it.
+fn convert_highlighted_text(
+    convert: impl FnOnce(&str, &[LayoutMap]) -> Result<String, String>,
+) -> Result<(), String> {
+    let (_, layout_maps) = configured_layout_maps()?;
+    convert_highlighted_text_in(&layout_maps, convert)
+}
 
-    let current_layout_map = match get_text_leyaout_map(&highlighted_text, &layout_maps) {
-        Some(layout) => layout,
-        None => return Err("Could not determine the layout of the highlighted text".to_string()),
+/// `convert_highlighted_text` over the given layouts.
+fn convert_highlighted_text_in(
+    layout_maps: &[LayoutMap],
+    convert: impl FnOnce(&str, &[LayoutMap]) -> Result<String, String>,
+) -> Result<(), String> {
+    let highlighted_text = highlighted_text()?;
+    let converted = convert(&highlighted_text, layout_maps)?;
+    replace_highlighted_text(&converted)
+}
+
+fn shift_highlighted_text_with(
+    mode: ShiftMode,
+    fix_caps: bool,
+    choice: &LayoutChoice,
+) -> Result<(), String> {
+    convert_highlighted_text(|text, layout_maps| {
+        shift_text_with(text, mode, fix_caps, choice, layout_maps)
+    })
+}
+
+fn shift_text_with(
+    text: &str,
+    mode: ShiftMode,
+    fix_caps: bool,
+    choice: &LayoutChoice,
+    layout_maps: &[LayoutMap],
+) -> Result<String, String> {
+    let current_layout_map = source_layout(text, choice.from.as_ref(), layout_maps)?;
+
+    // Fixing caps is asked for explicitly, so the case is inverted even
+    // where `is_caps_inverted` would take the text for an acronym.
+    let text = if fix_caps {
+        toggle_caps_levels(text, current_layout_map)
+    } else {
+        text.to_string()
     };
 
-    let shifted_text = layout_maps
+    if let Some(to) = &choice.to {
+        let target_layout_map = select_layout(to, layout_maps)?;
+        return Ok(shift_text_language(
+            &text,
+            current_layout_map,
+            target_layout_map,
+        ));
+    }
+
+    let best = match mode {
+        ShiftMode::Best => {
+            let codes: Vec<&str> = layout_maps
+                .iter()
+                .filter_map(|lm| lm.layout.language_code())
+                .collect();
+            let dictionaries = Dictionaries::discover(&codes);
+            best_target_layout(
+                &text,
+                current_layout_map,
+                layout_maps,
+                &dictionaries,
+                LanguageModels::shared(),
+            )
+            .map(|(_, shifted)| shifted)
+        }
+        ShiftMode::Cycle => None,
+    };
+
+    match best {
+        Some(shifted) => Ok(shifted),
+        None => shift_to_next_layout(&text, current_layout_map, layout_maps),
+    }
+}
+
+/// The layouts text can be converted to: a layout that types nothing, e.g.
+/// one that failed to compile, only keeps its place in the list.
+fn target_layouts<'a>(
+    current_layout_map: &'a LayoutMap,
+    layout_maps: &'a [LayoutMap],
+) -> impl DoubleEndedIterator<Item = &'a LayoutMap> + Clone {
+    layout_maps
         .iter()
+        .filter(move |lm| !lm.map.is_empty() || lm.layout.id == current_layout_map.layout.id)
+}
+
+fn shift_to_next_layout(
+    text: &str,
+    current_layout_map: &LayoutMap,
+    layout_maps: &[LayoutMap],
+) -> Result<String, String> {
+    target_layouts(current_layout_map, layout_maps)
         .cycle()
-        .skip_while(|layout| layout.layout.lang_name != current_layout_map.layout.lang_name)
+        .skip_while(|layout| layout.layout.id != current_layout_map.layout.id)
         .nth(1)
-        .map(|next_layout| shift_text_language(&highlighted_text, current_layout_map, next_layout))
-        .ok_or_else(|| "No next layout found".to_string())?;
+        .map(|next_layout| shift_text_language(text, current_layout_map, next_layout))
+        .ok_or_else(|| "No next layout found".to_string())
+}
 
-    replace_highlighted_text(&shifted_text)?;
+fn shift_to_previous_layout(
+    text: &str,
+    current_layout_map: &LayoutMap,
+    layout_maps: &[LayoutMap],
+) -> Result<String, String> {
+    target_layouts(current_layout_map, layout_maps)
+        .rev()
+        .cycle()
+        .skip_while(|layout| layout.layout.id != current_layout_map.layout.id)
+        .nth(1)
+        .map(|previous_layout| shift_text_language(text, current_layout_map, previous_layout))
+        .ok_or_else(|| "No previous layout found".to_string())
+}
 
-    Ok(())
+fn shift_text_to_previous(
+    text: &str,
+    from: Option<&LayoutSelector>,
+    layout_maps: &[LayoutMap],
+) -> Result<String, String> {
+    let current_layout_map = source_layout(text, from, layout_maps)?;
+    shift_to_previous_layout(text, current_layout_map, layout_maps)
+}
+
+pub fn shift_highlighted_text_to_previous_layout() -> Result<(), String> {
+    convert_highlighted_text(|text, layout_maps| shift_text_to_previous(text, None, layout_maps))
+}
+
+pub fn shift_highlighted_text_to_next_layout() -> Result<(), String> {
+    shift_highlighted_text(ShiftMode::Cycle)
+}
+
+pub fn shift_highlighted_text_to_best_layout() -> Result<(), String> {
+    shift_highlighted_text(ShiftMode::Best)
+}
+
+pub fn fix_caps_lock_highlighted_text() -> Result<(), String> {
+    convert_highlighted_text(|text, layout_maps| fix_caps_lock_text(text, None, layout_maps))
+}
+
+fn fix_caps_lock_text(
+    text: &str,
+    from: Option<&LayoutSelector>,
+    layout_maps: &[LayoutMap],
+) -> Result<String, String> {
+    Ok(match from {
+        Some(selector) => toggle_caps_levels(text, select_layout(selector, layout_maps)?),
+        None => match get_text_leyaout_map(text, layout_maps) {
+            Some(layout) => toggle_caps_levels(text, layout),
+            None => invert_case(text),
+        },
+    })
+}
+
+pub fn shift_highlighted_text_fixing_caps_lock(mode: ShiftMode) -> Result<(), String> {
+    shift_highlighted_text_with(mode, true, &LayoutChoice::default())
+}
+
+/// Romanizes the highlighted text. The source language comes from `from` or
+/// the script of the text, the target from `to` or English.
+pub fn transliterate_highlighted_text(
+    scheme: TransliterationScheme,
+    choice: &LayoutChoice,
+) -> Result<(), String> {
+    convert_highlighted_text(|text, layout_maps| {
+        transliterate_text_with(text, scheme, choice, layout_maps)
+    })
+}
+
+/// The language of the layout `selector` picks or, when no configured layout
+/// matches, of the language it names, so `--from ru` works without a Russian
+/// layout.
+fn selected_language(
+    selector: &LayoutSelector,
+    layout_maps: &[LayoutMap],
+) -> Result<&'static str, String> {
+    match select_layout(selector, layout_maps) {
+        Ok(selected) => selected
+            .layout
+            .language_code()
+            .ok_or_else(|| format!("Unknown language for layout {}", selected.layout.lang_name)),
+        Err(err) => selector.language_code().ok_or(err),
+    }
+}
+
+fn transliterate_text_with(
+    text: &str,
+    scheme: TransliterationScheme,
+    choice: &LayoutChoice,
+    layout_maps: &[LayoutMap],
+) -> Result<String, String> {
+    let from = match &choice.from {
+        Some(selector) => selected_language(selector, layout_maps)?,
+        None => {
+            let detected = get_text_leyaout_map(text, layout_maps).map(|lm| &lm.layout);
+            source_language(text, detected)
+                .ok_or_else(|| "Could not determine the language of the text".to_string())?
+        }
+    };
+    let to = match &choice.to {
+        Some(selector) => selected_language(selector, layout_maps)?,
+        None => "en",
+    };
+    transliterate(text, from, to, scheme)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -64,6 +339,87 @@ pub fn update_hotkey(hotkey: &HotkeySpec) -> Result<(), String> {
     binder.apply_hotkey(&cfg.hotkey)
 }
 
+pub fn update_previous_hotkey(hotkey: &HotkeySpec) -> Result<(), String> {
+    let mut cfg = config::load_config()?;
+    cfg.previous_hotkey = Some(hotkey.display.clone());
+    config::save_config(&cfg)?;
+    let binder = crate::platform::get_binder();
+    binder.apply_previous_hotkey(&hotkey.display)
+}
+
 pub fn run_transform_once() -> Result<(), String> {
-    shift_highlighted_text_to_next_layout()
+    run_action(
+        RunAction::Shift,
+        None,
+        &LayoutChoice::default(),
+        TransliterationScheme::default(),
+    )
 }
+
+pub fn run_transform(mode: ShiftMode) -> Result<(), String> {
+    shift_highlighted_text(mode)
+}
+
+/// `previous` always converts to the layout before the detected one, so a
+/// target layout or shift mode would be silently ignored.
+fn check_action_options(
+    action: RunAction,
+    mode: Option<ShiftMode>,
+    choice: &LayoutChoice,
+) -> Result<(), String> {
+    if action == RunAction::Previous && (mode.is_some() || choice.to.is_some()) {
+        return Err("--to and --mode don't apply to --action previous".to_string());
+    }
+    Ok(())
+}
+
+/// Applies `action` to the highlighted text.
+pub fn run_action(
+    action: RunAction,
+    mode: Option<ShiftMode>,
+    choice: &LayoutChoice,
+    scheme: TransliterationScheme,
+) -> Result<(), String> {
+    check_action_options(action, mode, choice)?;
+    let (cfg, layout_maps) = configured_layout_maps()?;
+    let mode = mode.unwrap_or(cfg.shift_mode);
+    convert_highlighted_text_in(&layout_maps, |text, layout_maps| {
+        convert_text_in(text, action, mode, choice, scheme, layout_maps)
+    })
+}
+
+/// Applies `action` to `text` and returns the result, for text that doesn't
+/// come from a selection (a terminal, a pipe).
+pub fn convert_text(
+    text: &str,
+    action: RunAction,
+    mode: Option<ShiftMode>,
+    choice: &LayoutChoice,
+    scheme: TransliterationScheme,
+) -> Result<String, String> {
+    check_action_options(action, mode, choice)?;
+    let (cfg, layout_maps) = configured_layout_maps()?;
+    let mode = mode.unwrap_or(cfg.shift_mode);
+    convert_text_in(text, action, mode, choice, scheme, &layout_maps)
+}
+
+/// `convert_text` over the given layouts.
+pub fn convert_text_in(
+    text: &str,
+    action: RunAction,
+    mode: ShiftMode,
+    choice: &LayoutChoice,
+    scheme: TransliterationScheme,
+    layout_maps: &[LayoutMap],
+) -> Result<String, String> {
+    match action {
+        RunAction::Shift => shift_text_with(text, mode, false, choice, layout_maps),
+        RunAction::FixCaps => fix_caps_lock_text(text, choice.from.as_ref(), layout_maps),
+        RunAction::ShiftFixCaps => shift_text_with(text, mode, true, choice, layout_maps),
+        RunAction::Previous => shift_text_to_previous(text, choice.from.as_ref(), layout_maps),
+        RunAction::Transliterate => transliterate_text_with(text, scheme, choice, layout_maps),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -1,11 +1,13 @@
 pub struct GnomeKeybindingPaths {
     pub custom_keybinding_base: &'static str,
+    pub previous_keybinding_base: &'static str,
     pub media_keys_schema: &'static str,
     pub custom_keybinding_schema: &'static str,
 }
 
 pub const GNOME_PATHS: GnomeKeybindingPaths = GnomeKeybindingPaths {
     custom_keybinding_base: "/org/gnome/settings-daemon/plugins/media-keys/custom-keybindings/keyboard-map-shift/",
+    previous_keybinding_base: "/org/gnome/settings-daemon/plugins/media-keys/custom-keybindings/keyboard-map-shift-previous/",
     media_keys_schema: "org.gnome.settings-daemon.plugins.media-keys",
     custom_keybinding_schema: "org.gnome.settings-daemon.plugins.media-keys.custom-keybinding",
 };
@@ -26,14 +28,18 @@ pub const GNOME_KEYS: GnomeKeyNames = GnomeKeyNames {
 
 pub struct KdeKeybindingPaths {
     pub desktop_relative_path: &'static str,
+    pub previous_desktop_relative_path: &'static str,
     pub kglobalshortcuts_relative_path: &'static str,
     pub component_group: &'static str,
+    pub previous_component_group: &'static str,
 }
 
 pub const KDE_PATHS: KdeKeybindingPaths = KdeKeybindingPaths {
     desktop_relative_path: ".local/share/applications/keyboard_map_shift.desktop",
+    previous_desktop_relative_path: ".local/share/applications/keyboard_map_shift_previous.desktop",
     kglobalshortcuts_relative_path: ".config/kglobalshortcutsrc",
     component_group: "keyboard_map_shift.desktop",
+    previous_component_group: "keyboard_map_shift_previous.desktop",
 };
 
 pub struct KdeKeyNames {
@@ -56,28 +62,38 @@ pub const KDE_TEMPLATES: KdeTemplates = KdeTemplates {
 
 pub struct AppStrings {
     pub app_name: &'static str,
+    pub previous_app_name: &'static str,
     pub exec_run_cmd: &'static str,
+    pub exec_previous_cmd: &'static str,
     pub kde_component_dbus_path: &'static str,
+    pub kde_previous_component_dbus_path: &'static str,
     pub app_run_subcommand: &'static str,
+    pub app_previous_arguments: &'static str,
 }
 
 pub const APP_STRINGS: AppStrings = AppStrings {
     app_name: "Keyboard Map Shift",
+    previous_app_name: "Keyboard Map Shift (previous layout)",
     exec_run_cmd: "keyboard_map_shift run",
+    exec_previous_cmd: "keyboard_map_shift run --action previous",
     kde_component_dbus_path: "/component/keyboard_map_shift.desktop",
+    kde_previous_component_dbus_path: "/component/keyboard_map_shift_previous.desktop",
     app_run_subcommand: "run",
+    app_previous_arguments: "run --action previous",
 };
 
 pub struct WindowsPaths {
     pub env_appdata: &'static str,
     pub start_menu_programs_rel: &'static str,
     pub shortcut_filename: &'static str,
+    pub previous_shortcut_filename: &'static str,
 }
 
 pub const WINDOWS_PATHS: WindowsPaths = WindowsPaths {
     env_appdata: "APPDATA",
     start_menu_programs_rel: "Microsoft\\Windows\\Start Menu\\Programs",
     shortcut_filename: "Keyboard Map Shift.lnk",
+    previous_shortcut_filename: "Keyboard Map Shift (previous layout).lnk",
 };
 
 #[cfg(test)]
@@ -0,0 +1,23 @@
+use crate::platform::HotkeyBinder;
+
+const UNSUPPORTED: &str = "global hotkeys are not supported on this platform";
+
+/// Binder for platforms without a global hotkey backend.
+pub struct FallbackBinder;
+
+impl HotkeyBinder for FallbackBinder {
+    fn apply_hotkey(&self, _display: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    fn apply_previous_hotkey(&self, _display: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    fn remove_hotkey(&self) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,9 @@
+use super::*;
+
+#[test]
+fn test_binder_reports_unsupported_platform() {
+    let err = Err("global hotkeys are not supported on this platform".to_string());
+    assert_eq!(FallbackBinder.apply_hotkey("Ctrl+Alt+K"), err);
+    assert_eq!(FallbackBinder.apply_previous_hotkey("Ctrl+Alt+J"), err);
+    assert_eq!(FallbackBinder.remove_hotkey(), err);
+}
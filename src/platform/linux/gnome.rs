@@ -1,16 +1,28 @@
 use crate::hotkey;
+use crate::platform::HotkeyAction;
 use crate::platform::constants::{APP_STRINGS, GNOME_KEYS, GNOME_PATHS};
 use std::process::Command;
 
-pub fn apply_gnome_binding(display: &str) -> Result<(), String> {
-    let base = GNOME_PATHS.custom_keybinding_base;
+pub fn apply_gnome_binding(action: HotkeyAction, display: &str) -> Result<(), String> {
+    let (base, name, command) = match action {
+        HotkeyAction::Shift => (
+            GNOME_PATHS.custom_keybinding_base,
+            APP_STRINGS.app_name,
+            APP_STRINGS.exec_run_cmd,
+        ),
+        HotkeyAction::Previous => (
+            GNOME_PATHS.previous_keybinding_base,
+            APP_STRINGS.previous_app_name,
+            APP_STRINGS.exec_previous_cmd,
+        ),
+    };
     let list_key = GNOME_PATHS.media_keys_schema;
     let binding_schema = GNOME_PATHS.custom_keybinding_schema;
 
     let current = gsettings_get(list_key, GNOME_KEYS.custom_keybindings_key)
         .unwrap_or_else(|_| String::from("[]"));
-    if !current.contains("keyboard-map-shift") {
-        let new_list = format!("['{}']", base);
+    if !current.contains(&format!("'{}'", base)) {
+        let new_list = append_keybinding_path(&current, base);
         gsettings_set(list_key, GNOME_KEYS.custom_keybindings_key, &new_list)?;
     }
 
@@ -18,19 +30,35 @@ pub fn apply_gnome_binding(display: &str) -> Result<(), String> {
         binding_schema,
         base,
         GNOME_KEYS.kb_name_key,
-        &format!("'{}'", APP_STRINGS.app_name),
+        &format!("'{}'", name),
     )?;
     gsettings_set_kb(
         binding_schema,
         base,
         GNOME_KEYS.kb_command_key,
-        &format!("'{}'", APP_STRINGS.exec_run_cmd),
+        &format!("'{}'", command),
     )?;
     let binding = format!("'{}'", hotkey::to_gnome_binding_from_display(display)?);
     gsettings_set_kb(binding_schema, base, GNOME_KEYS.kb_binding_key, &binding)?;
     Ok(())
 }
 
+/// Adds `path` to a gsettings string list such as `['/a/']` or `@as []`.
+fn append_keybinding_path(list: &str, path: &str) -> String {
+    let inner = list
+        .trim()
+        .trim_start_matches("@as")
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim();
+    if inner.is_empty() {
+        format!("['{}']", path)
+    } else {
+        format!("[{}, '{}']", inner, path)
+    }
+}
+
 fn gsettings_set(schema: &str, key: &str, value: &str) -> Result<(), String> {
     let status = Command::new("gsettings")
         .args(["set", schema, key, value])
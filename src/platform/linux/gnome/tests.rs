@@ -87,5 +87,15 @@ impl Drop for GsettingsGuard {
 #[test]
 fn apply_gnome_binding_smoke() {
     let _guard = GsettingsGuard::capture();
-    let _ = apply_gnome_binding("Ctrl+Alt+K");
+    let _ = apply_gnome_binding(HotkeyAction::Shift, "Ctrl+Alt+K");
+}
+
+#[test]
+fn append_keybinding_path_keeps_existing_entries() {
+    assert_eq!(append_keybinding_path("@as []", "/a/"), "['/a/']");
+    assert_eq!(append_keybinding_path("[]", "/a/"), "['/a/']");
+    assert_eq!(
+        append_keybinding_path("['/custom0/']", "/a/"),
+        "['/custom0/', '/a/']"
+    );
 }
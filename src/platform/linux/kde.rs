@@ -1,25 +1,64 @@
+use crate::platform::HotkeyAction;
 use crate::platform::constants::{APP_STRINGS, KDE_KEYS, KDE_PATHS, KDE_TEMPLATES};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub fn apply_kde_binding(display: &str) -> Result<(), String> {
+struct KdeBinding {
+    desktop_relative_path: &'static str,
+    component_group: &'static str,
+    component_dbus_path: &'static str,
+    name: &'static str,
+    exec: &'static str,
+}
+
+fn kde_binding(action: HotkeyAction) -> KdeBinding {
+    match action {
+        HotkeyAction::Shift => KdeBinding {
+            desktop_relative_path: KDE_PATHS.desktop_relative_path,
+            component_group: KDE_PATHS.component_group,
+            component_dbus_path: APP_STRINGS.kde_component_dbus_path,
+            name: APP_STRINGS.app_name,
+            exec: APP_STRINGS.exec_run_cmd,
+        },
+        HotkeyAction::Previous => KdeBinding {
+            desktop_relative_path: KDE_PATHS.previous_desktop_relative_path,
+            component_group: KDE_PATHS.previous_component_group,
+            component_dbus_path: APP_STRINGS.kde_previous_component_dbus_path,
+            name: APP_STRINGS.previous_app_name,
+            exec: APP_STRINGS.exec_previous_cmd,
+        },
+    }
+}
+
+pub fn apply_kde_binding(action: HotkeyAction, display: &str) -> Result<(), String> {
     let home = std::env::var("HOME").map_err(|e| format!("HOME not set: {}", e))?;
-    let home_path = PathBuf::from(home);
-    let desktop_path = desktop_file_path_from_home(&home_path)?;
+    apply_kde_binding_in(&PathBuf::from(home), action, display)
+}
+
+fn apply_kde_binding_in(
+    home_path: &Path,
+    action: HotkeyAction,
+    display: &str,
+) -> Result<(), String> {
+    let binding = kde_binding(action);
+    let desktop_path = desktop_file_path_from_home(home_path, &binding)?;
     let desktop_dir = desktop_path
         .parent()
         .ok_or_else(|| format!("Desktop path has no parent: {}", desktop_path.display()))?;
     fs::create_dir_all(desktop_dir)
         .map_err(|e| format!("Create applications dir failed: {}", e))?;
-    let desktop_contents = desktop_file_contents();
+    let desktop_contents = desktop_file_contents(&binding);
     fs::write(&desktop_path, desktop_contents)
         .map_err(|e| format!("Write desktop file failed: {}", e))?;
 
-    let kglobal = kglobalshortcuts_path_from_home(&home_path)?;
+    let kglobal = kglobalshortcuts_path_from_home(home_path)?;
+    if let Some(config_dir) = kglobal.parent() {
+        fs::create_dir_all(config_dir).map_err(|e| format!("Create config dir failed: {}", e))?;
+    }
     let mut ini = load_ini(&kglobal);
-    let group = KDE_PATHS.component_group.to_string();
+    let group = binding.component_group.to_string();
     let entry_key = KDE_KEYS.friendly_name_key.to_string();
-    ini_set(&mut ini, &group, &entry_key, APP_STRINGS.app_name);
+    ini_set(&mut ini, &group, &entry_key, binding.name);
 
     let trigger = display.to_string();
     ini_set(
@@ -33,7 +72,7 @@ pub fn apply_kde_binding(display: &str) -> Result<(), String> {
     let _ = std::process::Command::new("qdbus")
         .args([
             "org.kde.kglobalaccel",
-            APP_STRINGS.kde_component_dbus_path,
+            binding.component_dbus_path,
             "reconfigure",
         ])
         .status();
@@ -41,17 +80,17 @@ pub fn apply_kde_binding(display: &str) -> Result<(), String> {
     Ok(())
 }
 
-fn desktop_file_path_from_home(home: &Path) -> Result<PathBuf, String> {
+fn desktop_file_path_from_home(home: &Path, binding: &KdeBinding) -> Result<PathBuf, String> {
     let mut p = home.to_path_buf();
-    p.push(KDE_PATHS.desktop_relative_path);
+    p.push(binding.desktop_relative_path);
     Ok(p)
 }
 
-fn desktop_file_contents() -> String {
+fn desktop_file_contents(binding: &KdeBinding) -> String {
     KDE_TEMPLATES
         .desktop_entry_template
-        .replace("{name}", APP_STRINGS.app_name)
-        .replace("{exec}", APP_STRINGS.exec_run_cmd)
+        .replace("{name}", binding.name)
+        .replace("{exec}", binding.exec)
 }
 
 fn kglobalshortcuts_path_from_home(home: &Path) -> Result<PathBuf, String> {
@@ -32,5 +32,26 @@ fn apply_kde_binding_smoke_with_temp_home() {
     let home = temp_home();
     let _home_dir = TempDirGuard::new(home.clone());
     let _home_guard = HomeGuard::set(&home);
-    let _ = apply_kde_binding("Ctrl+Alt+K");
+    let _ = apply_kde_binding(HotkeyAction::Shift, "Ctrl+Alt+K");
+}
+
+#[test]
+fn apply_kde_previous_binding_writes_separate_component() {
+    let home = temp_home();
+    let _home_dir = TempDirGuard::new(home.clone());
+    apply_kde_binding_in(&home, HotkeyAction::Shift, "Ctrl+Alt+K").unwrap();
+    apply_kde_binding_in(&home, HotkeyAction::Previous, "Ctrl+Alt+J").unwrap();
+
+    let ini = load_ini(&home.join(KDE_PATHS.kglobalshortcuts_relative_path));
+    assert_eq!(
+        ini[KDE_PATHS.component_group][KDE_KEYS.trigger_key],
+        "Ctrl+Alt+K,none,Trigger"
+    );
+    assert_eq!(
+        ini[KDE_PATHS.previous_component_group][KDE_KEYS.trigger_key],
+        "Ctrl+Alt+J,none,Trigger"
+    );
+    let desktop =
+        std::fs::read_to_string(home.join(KDE_PATHS.previous_desktop_relative_path)).unwrap();
+    assert!(desktop.contains(APP_STRINGS.exec_previous_cmd));
 }
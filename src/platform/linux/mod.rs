@@ -1,7 +1,7 @@
 mod gnome;
 mod kde;
 
-use crate::platform::HotkeyBinder;
+use crate::platform::{HotkeyAction, HotkeyBinder};
 
 pub struct LinuxBinder;
 
@@ -11,16 +11,26 @@ impl LinuxBinder {
     }
 }
 
-impl HotkeyBinder for LinuxBinder {
-    fn apply_hotkey(&self, display: &str) -> Result<(), String> {
+impl LinuxBinder {
+    fn apply_binding(&self, action: HotkeyAction, display: &str) -> Result<(), String> {
         match detect_de() {
-            DesktopEnvironment::Gnome => gnome::apply_gnome_binding(display),
-            DesktopEnvironment::Kde => kde::apply_kde_binding(display),
+            DesktopEnvironment::Gnome => gnome::apply_gnome_binding(action, display),
+            DesktopEnvironment::Kde => kde::apply_kde_binding(action, display),
             DesktopEnvironment::Unknown => {
                 Err("Unsupported Linux desktop environment for automatic binding".to_string())
             }
         }
     }
+}
+
+impl HotkeyBinder for LinuxBinder {
+    fn apply_hotkey(&self, display: &str) -> Result<(), String> {
+        self.apply_binding(HotkeyAction::Shift, display)
+    }
+
+    fn apply_previous_hotkey(&self, display: &str) -> Result<(), String> {
+        self.apply_binding(HotkeyAction::Previous, display)
+    }
 
     fn remove_hotkey(&self) -> Result<(), String> {
         Ok(())
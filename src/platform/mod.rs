@@ -1,12 +1,26 @@
 pub mod constants;
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod fallback;
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "windows")]
 mod windows;
 
+/// What a global hotkey runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    /// `run` with the configured action and mode.
+    Shift,
+    /// `run --action previous`.
+    Previous,
+}
+
 pub trait HotkeyBinder {
     fn apply_hotkey(&self, display: &str) -> Result<(), String>;
 
+    /// Binds the hotkey that converts the selection to the previous layout.
+    fn apply_previous_hotkey(&self, display: &str) -> Result<(), String>;
+
     fn remove_hotkey(&self) -> Result<(), String>;
 }
 
@@ -20,4 +34,9 @@ pub fn get_binder() -> Box<dyn HotkeyBinder> {
     {
         Box::new(linux::LinuxBinder::new())
     }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        Box::new(fallback::FallbackBinder)
+    }
 }
@@ -11,8 +11,8 @@ use windows::Win32::UI::Shell::{IShellLinkW, ShellLink};
 use windows::core::{Interface, PCWSTR};
 
 use crate::hotkey;
-use crate::platform::HotkeyBinder;
 use crate::platform::constants::{APP_STRINGS, WINDOWS_PATHS};
+use crate::platform::{HotkeyAction, HotkeyBinder};
 
 pub struct WindowsBinder;
 
@@ -22,8 +22,22 @@ impl WindowsBinder {
     }
 }
 
-impl HotkeyBinder for WindowsBinder {
-    fn apply_hotkey(&self, display: &str) -> Result<(), String> {
+fn shortcut_for(action: HotkeyAction) -> (&'static str, &'static str) {
+    match action {
+        HotkeyAction::Shift => (
+            WINDOWS_PATHS.shortcut_filename,
+            APP_STRINGS.app_run_subcommand,
+        ),
+        HotkeyAction::Previous => (
+            WINDOWS_PATHS.previous_shortcut_filename,
+            APP_STRINGS.app_previous_arguments,
+        ),
+    }
+}
+
+impl WindowsBinder {
+    fn apply_binding(&self, action: HotkeyAction, display: &str) -> Result<(), String> {
+        let (filename, arguments) = shortcut_for(action);
         unsafe {
             CoInitializeEx(None, COINIT_APARTMENTTHREADED)
                 .ok()
@@ -31,7 +45,7 @@ impl HotkeyBinder for WindowsBinder {
         }
         let res = (|| {
             let exe = std::env::current_exe().map_err(|e| format!("Exe path: {}", e))?;
-            let shortcut_path = shortcut_path()?;
+            let shortcut_path = shortcut_path(filename)?;
 
             let shell_link: IShellLinkW = unsafe {
                 CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)
@@ -39,7 +53,7 @@ impl HotkeyBinder for WindowsBinder {
             };
 
             set_link_path(&shell_link, &exe)?;
-            set_link_arguments(&shell_link, APP_STRINGS.app_run_subcommand)?;
+            set_link_arguments(&shell_link, arguments)?;
 
             let hotkey_word = hotkey::to_windows_hotkey_word_from_display(display)?;
             unsafe { shell_link.SetHotkey(hotkey_word) }
@@ -57,24 +71,39 @@ impl HotkeyBinder for WindowsBinder {
         unsafe { CoUninitialize() };
         res
     }
+}
+
+impl HotkeyBinder for WindowsBinder {
+    fn apply_hotkey(&self, display: &str) -> Result<(), String> {
+        self.apply_binding(HotkeyAction::Shift, display)
+    }
+
+    fn apply_previous_hotkey(&self, display: &str) -> Result<(), String> {
+        self.apply_binding(HotkeyAction::Previous, display)
+    }
 
     fn remove_hotkey(&self) -> Result<(), String> {
-        let shortcut_path = shortcut_path()?;
-        if shortcut_path.exists() {
-            std::fs::remove_file(&shortcut_path)
-                .map_err(|e| format!("Failed to remove shortcut: {}", e))?;
+        for filename in [
+            WINDOWS_PATHS.shortcut_filename,
+            WINDOWS_PATHS.previous_shortcut_filename,
+        ] {
+            let shortcut_path = shortcut_path(filename)?;
+            if shortcut_path.exists() {
+                std::fs::remove_file(&shortcut_path)
+                    .map_err(|e| format!("Failed to remove shortcut: {}", e))?;
+            }
         }
         Ok(())
     }
 }
 
-fn shortcut_path() -> Result<PathBuf, String> {
+fn shortcut_path(filename: &str) -> Result<PathBuf, String> {
     let appdata =
         std::env::var(WINDOWS_PATHS.env_appdata).map_err(|e| format!("APPDATA not set: {}", e))?;
     let mut p = PathBuf::from(appdata);
     p.push(WINDOWS_PATHS.start_menu_programs_rel);
     std::fs::create_dir_all(&p).map_err(|e| format!("Create Start Menu dir failed: {}", e))?;
-    p.push(WINDOWS_PATHS.shortcut_filename);
+    p.push(filename);
     Ok(p)
 }
 
@@ -34,5 +34,6 @@ fn windows_binder_smoke() {
 
     let binder = WindowsBinder::new();
     let _ = binder.apply_hotkey("Ctrl+Alt+K");
+    let _ = binder.apply_previous_hotkey("Ctrl+Alt+J");
     let _ = binder.remove_hotkey();
 }
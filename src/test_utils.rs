@@ -60,3 +60,32 @@ impl Drop for TempDirGuard {
         let _ = std::fs::remove_dir_all(&self.path);
     }
 }
+
+/// Creates a fresh directory under the system temp dir named after `tag`;
+/// pair it with a `TempDirGuard` to remove it.
+pub fn temp_dir(tag: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "kms_{}_{}",
+        tag,
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Points the layout cache at a directory under the system temp dir for the
+/// rest of the test run, so tests never write to the user's cache. The
+/// variable is set once and left set, which keeps it stable while other
+/// tests run in parallel.
+pub fn use_temp_cache_dir() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| unsafe {
+        std::env::set_var(
+            crate::config::CACHE_DIR_ENV,
+            std::env::temp_dir().join("keyboard_map_shift_test_cache"),
+        );
+    });
+}
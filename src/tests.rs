@@ -0,0 +1,219 @@
+use super::*;
+use keyboard_mapping::KeyboardLayout;
+use keyboard_mapping::builtin::builtin_layout_maps_for;
+
+fn layouts(ids: &[&str]) -> Vec<LayoutMap> {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    builtin_layout_maps_for(&ids)
+}
+
+fn choice(from: Option<&str>, to: Option<&str>) -> LayoutChoice {
+    LayoutChoice {
+        from: from.map(|s| s.parse().unwrap()),
+        to: to.map(|s| s.parse().unwrap()),
+    }
+}
+
+#[test]
+fn test_convert_text_in_detects_and_cycles() {
+    let converted = convert_text_in(
+        "ghbdtn",
+        RunAction::Shift,
+        ShiftMode::Cycle,
+        &LayoutChoice::default(),
+        TransliterationScheme::default(),
+        &layouts(&["us", "ru"]),
+    );
+    assert_eq!(converted, Ok("привет".to_string()));
+}
+
+#[test]
+fn test_cycling_skips_layouts_that_type_nothing() {
+    let mut layout_maps = layouts(&["us", "ru"]);
+    let broken = KeyboardLayout {
+        id: "no_such_layout".to_string(),
+        ..layout_maps[0].layout.clone()
+    };
+    layout_maps.insert(1, LayoutMap::new(broken, Default::default()));
+    for action in [RunAction::Shift, RunAction::Previous] {
+        let converted = convert_text_in(
+            "ghbdtn",
+            action,
+            ShiftMode::Cycle,
+            &LayoutChoice::default(),
+            TransliterationScheme::default(),
+            &layout_maps,
+        );
+        assert_eq!(converted, Ok("привет".to_string()));
+    }
+}
+
+#[test]
+fn test_convert_text_in_between_given_layouts() {
+    let converted = convert_text_in(
+        "akuo",
+        RunAction::Shift,
+        ShiftMode::Cycle,
+        &choice(Some("us"), Some("il")),
+        TransliterationScheme::default(),
+        &layouts(&["us", "ru", "il"]),
+    );
+    assert_eq!(converted, Ok("שלום".to_string()));
+}
+
+#[test]
+fn test_convert_text_in_previous() {
+    let converted = convert_text_in(
+        "ghbdtn",
+        RunAction::Previous,
+        ShiftMode::Cycle,
+        &choice(Some("us"), None),
+        TransliterationScheme::default(),
+        &layouts(&["us", "il", "ru"]),
+    );
+    assert_eq!(converted, Ok("привет".to_string()));
+}
+
+#[test]
+fn test_convert_text_in_unknown_layout() {
+    let converted = convert_text_in(
+        "abc",
+        RunAction::Shift,
+        ShiftMode::Cycle,
+        &choice(None, Some("xx")),
+        TransliterationScheme::default(),
+        &layouts(&["us", "ru"]),
+    );
+    assert_eq!(
+        converted,
+        Err("No configured layout matches \"xx\"".to_string())
+    );
+}
+
+#[test]
+fn test_transliterate_without_a_layout_for_the_source() {
+    let transliterate = |text, choice: &LayoutChoice, scheme| {
+        convert_text_in(
+            text,
+            RunAction::Transliterate,
+            ShiftMode::Cycle,
+            choice,
+            scheme,
+            &layouts(&["us"]),
+        )
+    };
+    let default = TransliterationScheme::default();
+    assert_eq!(
+        transliterate("Щукин", &LayoutChoice::default(), default),
+        Ok("Shchukin".to_string())
+    );
+    assert_eq!(
+        transliterate(
+            "Щукин",
+            &choice(Some("ru"), Some("de")),
+            TransliterationScheme::Iso9
+        ),
+        Ok("Ŝukin".to_string())
+    );
+    assert_eq!(
+        transliterate("Щукин", &choice(None, Some("de")), default),
+        Err("No transliteration table from ru to de".to_string())
+    );
+    assert_eq!(
+        transliterate("12345", &LayoutChoice::default(), default),
+        Err("Could not determine the language of the text".to_string())
+    );
+}
+
+#[test]
+fn test_convert_text_in_shift_fix_caps_repairs_all_caps() {
+    let convert = |text| {
+        convert_text_in(
+            text,
+            RunAction::ShiftFixCaps,
+            ShiftMode::Cycle,
+            &choice(Some("ru"), Some("us")),
+            TransliterationScheme::default(),
+            &layouts(&["us", "ru"]),
+        )
+    };
+    assert_eq!(convert("ЩЫЦ"), Ok("osw".to_string()));
+    assert_eq!(convert("ЩЫЦ ЦЫЩ"), Ok("osw wso".to_string()));
+}
+
+#[test]
+fn test_convert_text_in_shift_fix_caps_always_inverts() {
+    // An explicit fix-caps action inverts the case even of text that
+    // `is_caps_inverted` takes for an acronym or normal case.
+    let convert = |text| {
+        convert_text_in(
+            text,
+            RunAction::ShiftFixCaps,
+            ShiftMode::Cycle,
+            &choice(Some("us"), Some("ru")),
+            TransliterationScheme::default(),
+            &layouts(&["us", "ru"]),
+        )
+    };
+    assert_eq!(convert("NASA"), Ok("тфыф".to_string()));
+    assert_eq!(convert("gHBDTN"), Ok("Привет".to_string()));
+}
+
+#[test]
+fn test_convert_text_in_fix_caps_all_caps() {
+    let convert = |text| {
+        convert_text_in(
+            text,
+            RunAction::FixCaps,
+            ShiftMode::Cycle,
+            &choice(Some("ru"), None),
+            TransliterationScheme::default(),
+            &layouts(&["us", "ru"]),
+        )
+    };
+    assert_eq!(convert("ЩЫЦ"), Ok("щыц".to_string()));
+    assert_eq!(convert("ЩЫЦ ЦЫЩ"), Ok("щыц цыщ".to_string()));
+}
+
+#[test]
+fn test_previous_rejects_target_and_mode() {
+    let err = "--to and --mode don't apply to --action previous".to_string();
+    let convert = |mode, choice: &LayoutChoice| {
+        convert_text(
+            "ghbdtn",
+            RunAction::Previous,
+            mode,
+            choice,
+            TransliterationScheme::default(),
+        )
+    };
+    assert_eq!(
+        convert(Some(ShiftMode::Best), &choice(None, None)),
+        Err(err.clone())
+    );
+    assert_eq!(
+        convert(None, &choice(Some("us"), Some("ru"))),
+        Err(err.clone())
+    );
+    assert_eq!(
+        run_action(
+            RunAction::Previous,
+            Some(ShiftMode::Cycle),
+            &LayoutChoice::default(),
+            TransliterationScheme::default()
+        ),
+        Err(err)
+    );
+    assert_eq!(
+        check_action_options(RunAction::Previous, None, &choice(Some("us"), None)),
+        Ok(())
+    );
+    assert_eq!(
+        check_action_options(
+            RunAction::Shift,
+            Some(ShiftMode::Best),
+            &choice(None, Some("ru"))
+        ),
+        Ok(())
+    );
+}